@@ -1,11 +1,18 @@
 use gurtlib::prelude::*;
+use gurtlib::{Cookie, GurtMethod, PeerIdentity};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, Algorithm};
 use serde::{Deserialize, Serialize};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use std::time::{SystemTime, UNIX_EPOCH};
 use chrono::{DateTime, Utc};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The cookie login and register set the JWT in, accepted in place of an `Authorization`
+/// header on GET and HEAD requests only. Cookies are sent with requests any page can trigger,
+/// so state-changing requests must carry the header.
+pub const TOKEN_COOKIE: &str = "token";
+const TOKEN_LIFETIME_SECS: u64 = 86400 * 7; // 7 days
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub user_id: i32,
     pub username: String,
@@ -43,7 +50,7 @@ pub fn generate_jwt(user_id: i32, username: &str, secret: &str) -> std::result::
     let expiration = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs() + 86400 * 7; // 7 days
+        .as_secs() + TOKEN_LIFETIME_SECS;
 
     let claims = Claims {
         user_id,
@@ -62,6 +69,14 @@ pub fn validate_jwt(token: &str, secret: &str) -> std::result::Result<Claims, js
         .map(|token_data| token_data.claims)
 }
 
+/// The `set-cookie` that hands `token` to clients with a cookie jar.
+pub fn token_cookie(token: &str) -> Cookie {
+    Cookie::new(TOKEN_COOKIE, token)
+        .with_path("/")
+        .with_max_age(std::time::Duration::from_secs(TOKEN_LIFETIME_SECS))
+        .with_http_only(true)
+}
+
 pub fn hash_password(password: &str) -> std::result::Result<String, bcrypt::BcryptError> {
     hash(password, DEFAULT_COST)
 }
@@ -74,21 +89,25 @@ pub async fn jwt_middleware_gurt(ctx: &ServerContext, jwt_secret: &str) -> Resul
     let start_time = std::time::Instant::now();
     log::info!("JWT middleware started for {} {}", ctx.method(), ctx.path());
     
-    let auth_header = ctx.header("authorization")
-        .or_else(|| ctx.header("Authorization"))
-        .ok_or_else(|| {
+    let token = match ctx.header("authorization") {
+        Some(auth_header) => match auth_header.strip_prefix("Bearer ") {
+            Some(token) => token.to_string(),
+            None => {
+                log::warn!("JWT middleware failed: Invalid header format in {:?}", start_time.elapsed());
+                return Err(GurtError::invalid_message("Invalid Authorization header format"));
+            }
+        },
+        None if matches!(ctx.method(), GurtMethod::GET | GurtMethod::HEAD) => ctx.cookie(TOKEN_COOKIE).ok_or_else(|| {
             log::warn!("JWT middleware failed: Missing Authorization header in {:?}", start_time.elapsed());
             GurtError::invalid_message("Missing Authorization header")
-        })?;
-
-    if !auth_header.starts_with("Bearer ") {
-        log::warn!("JWT middleware failed: Invalid header format in {:?}", start_time.elapsed());
-        return Err(GurtError::invalid_message("Invalid Authorization header format"));
-    }
+        })?,
+        None => {
+            log::warn!("JWT middleware failed: Missing Authorization header in {:?}", start_time.elapsed());
+            return Err(GurtError::invalid_message("Missing Authorization header"));
+        }
+    };
 
-    let token = &auth_header[7..]; // Remove "Bearer " prefix
-    
-    let result = validate_jwt(token, jwt_secret)
+    let result = validate_jwt(&token, jwt_secret)
         .map_err(|e| GurtError::invalid_message(format!("Invalid JWT token: {}", e)));
         
     match &result {
@@ -97,4 +116,52 @@ pub async fn jwt_middleware_gurt(ctx: &ServerContext, jwt_secret: &str) -> Resul
     }
     
     result
-}
\ No newline at end of file
+}
+
+/// Rejects requests without a valid bearer token and exposes the token's `Claims`
+/// to handlers through `ctx.extension::<Claims>()`. Trusted services authenticated by
+/// client certificate are let through with their `PeerIdentity` instead.
+#[derive(Clone)]
+pub struct JwtAuth {
+    jwt_secret: String,
+    trusted_services: Vec<String>,
+}
+
+impl JwtAuth {
+    pub fn new(jwt_secret: String) -> Self {
+        Self { jwt_secret, trusted_services: Vec::new() }
+    }
+
+    pub fn with_trusted_services(mut self, trusted_services: Vec<String>) -> Self {
+        self.trusted_services = trusted_services;
+        self
+    }
+
+    /// The verified client certificate of the request, if it names a trusted service.
+    fn trusted_peer(&self, ctx: &ServerContext) -> Option<PeerIdentity> {
+        ctx.peer.as_ref()
+            .filter(|peer| self.trusted_services.iter().any(|name| peer.has_name(name)))
+            .cloned()
+    }
+}
+
+impl GurtMiddleware for JwtAuth {
+    fn before<'a>(&'a self, ctx: &'a mut ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<GurtResponse>>> + Send + 'a>> {
+        Box::pin(async move {
+            if let Some(peer) = self.trusted_peer(ctx) {
+                log::info!("Authenticated service {} by client certificate", peer.name().unwrap_or_default());
+                ctx.insert_extension(peer);
+                return Ok(None);
+            }
+
+            match jwt_middleware_gurt(ctx, &self.jwt_secret).await {
+                Ok(claims) => {
+                    ctx.insert_extension(claims);
+                    Ok(None)
+                }
+                Err(e) => Ok(Some(GurtResponse::new(gurtlib::GurtStatusCode::Unauthorized)
+                    .with_string_body(format!("Authentication failed: {}", e)))),
+            }
+        })
+    }
+}
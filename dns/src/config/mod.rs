@@ -27,6 +27,7 @@ impl Config {
                 },
                 cert_path: "localhost+2.pem".into(),
                 key_path: "localhost+2-key.pem".into(),
+                client_ca_path: None,
             },
             discord: Discord {
                 bot_token: "".into(),
@@ -34,6 +35,7 @@ impl Config {
             },
             auth: Auth {
                 jwt_secret: "your-secret-key-here".into(),
+                trusted_services: vec![],
             },
             settings: Settings {
                 tld_list: default_tld_list.iter().map(|s| s.to_string()).collect(),
@@ -17,6 +17,10 @@ pub struct Server {
     pub(crate) database: Database,
     pub(crate) cert_path: String,
     pub(crate) key_path: String,
+    /// CA bundle that internal services' client certificates are issued by. When set,
+    /// connecting clients are asked for a certificate, but not required to present one.
+    #[serde(default)]
+    pub(crate) client_ca_path: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -40,4 +44,8 @@ pub struct Discord {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Auth {
     pub(crate) jwt_secret: String,
+    /// Names of internal services allowed on authenticated routes by presenting a client
+    /// certificate for that name, issued by `client_ca_path`, instead of a JWT.
+    #[serde(default)]
+    pub(crate) trusted_services: Vec<String>,
 }
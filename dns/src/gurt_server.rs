@@ -4,7 +4,7 @@ mod models;
 mod routes;
 mod ca;
 
-use crate::{auth::jwt_middleware_gurt, config::Config, discord_bot};
+use crate::{auth::{Claims, JwtAuth}, config::Config, discord_bot};
 use colored::Colorize;
 use macros_rs::fmt::{crashln, string};
 use std::{sync::Arc, collections::HashMap};
@@ -69,21 +69,11 @@ struct AppHandler {
     handler_type: HandlerType,
 }
 
-// Macro to reduce JWT middleware duplication
-macro_rules! handle_authenticated {
-    ($ctx:expr, $app_state:expr, $handler:expr) => {
-        match jwt_middleware_gurt(&$ctx, &$app_state.jwt_secret).await {
-            Ok(claims) => $handler(&$ctx, $app_state, claims).await,
-            Err(e) => Ok(GurtResponse::new(GurtStatusCode::Unauthorized)
-                .with_string_body(&format!("Authentication failed: {}", e))),
-        }
-    };
-}
-
 #[derive(Clone, Debug)]
 enum HandlerType {
     Index,
     GetDomain,
+    DomainStatusEvents,
     GetDomains, 
     GetTlds,
     CheckDomain,
@@ -98,7 +88,9 @@ enum HandlerType {
     UpdateDomain,
     DeleteDomain,
     GetUserDomains,
+    GetDomainRecords,
     CreateDomainRecord,
+    DeleteDomainRecord,
     ResolveDomain,
     ResolveFullDomain,
     VerifyDomainOwnership,
@@ -114,47 +106,34 @@ impl GurtHandler for AppHandler {
         let rate_limit_state = self.rate_limit_state.clone();
         let handler_type = self.handler_type.clone();
         
-        let ctx_data = (
-            ctx.remote_addr,
-            ctx.request.clone(),
-        );
+        let ctx = ctx.clone();
         
         Box::pin(async move {
             let start_time = std::time::Instant::now();
-            let ctx = ServerContext {
-                remote_addr: ctx_data.0,
-                request: ctx_data.1,
-            };
             
             log::info!("Handler started for {} {} from {}", ctx.method(), ctx.path(), ctx.remote_addr);
             
+            // Set by the `JwtAuth` middleware on authenticated routes
+            let claims = || ctx.extension::<Claims>().cloned()
+                .ok_or_else(|| GurtError::invalid_message("Route requires authentication"));
+            
             let result = match handler_type {
                 HandlerType::Index => routes::index(&ctx, app_state).await,
-                HandlerType::GetDomain => {
-                    if ctx.path().contains("/records") {
-                        handle_authenticated!(ctx, app_state, routes::get_domain_records)
-                    } else {
-                        handle_authenticated!(ctx, app_state, routes::get_domain)
-                    }
-                },
+                HandlerType::GetDomain => routes::get_domain(&ctx, app_state, claims()?).await,
+                HandlerType::DomainStatusEvents => routes::domain_status_events(&ctx, app_state, claims()?).await,
                 HandlerType::GetDomains => routes::get_domains(&ctx, app_state).await,
                 HandlerType::GetTlds => routes::get_tlds(app_state).await,
                 HandlerType::CheckDomain => routes::check_domain(&ctx, app_state).await,
                 HandlerType::Register => auth_routes::register(&ctx, app_state).await,
                 HandlerType::Login => auth_routes::login(&ctx, app_state).await,
-                HandlerType::GetUserInfo => handle_authenticated!(ctx, app_state, auth_routes::get_user_info),
-                HandlerType::CreateInvite => handle_authenticated!(ctx, app_state, auth_routes::create_invite),
-                HandlerType::RedeemInvite => handle_authenticated!(ctx, app_state, auth_routes::redeem_invite),
-                HandlerType::CreateDomainInvite => handle_authenticated!(ctx, app_state, auth_routes::create_domain_invite),
-                HandlerType::RedeemDomainInvite => handle_authenticated!(ctx, app_state, auth_routes::redeem_domain_invite),
-                HandlerType::GetUserDomains => handle_authenticated!(ctx, app_state, routes::get_user_domains),
-                HandlerType::CreateDomainRecord => {
-                    if ctx.path().contains("/records") {
-                        handle_authenticated!(ctx, app_state, routes::create_domain_record)
-                    } else {
-                        Ok(GurtResponse::new(GurtStatusCode::MethodNotAllowed).with_string_body("Method not allowed"))
-                    }
-                },
+                HandlerType::GetUserInfo => auth_routes::get_user_info(&ctx, app_state, claims()?).await,
+                HandlerType::CreateInvite => auth_routes::create_invite(&ctx, app_state, claims()?).await,
+                HandlerType::RedeemInvite => auth_routes::redeem_invite(&ctx, app_state, claims()?).await,
+                HandlerType::CreateDomainInvite => auth_routes::create_domain_invite(&ctx, app_state, claims()?).await,
+                HandlerType::RedeemDomainInvite => auth_routes::redeem_domain_invite(&ctx, app_state, claims()?).await,
+                HandlerType::GetUserDomains => routes::get_user_domains(&ctx, app_state, claims()?).await,
+                HandlerType::GetDomainRecords => routes::get_domain_records(&ctx, app_state, claims()?).await,
+                HandlerType::CreateDomainRecord => routes::create_domain_record(&ctx, app_state, claims()?).await,
                 HandlerType::CreateDomain => {
                     // Check rate limit first
                     if let Some(ref rate_limit_state) = rate_limit_state {
@@ -164,16 +143,11 @@ impl GurtHandler for AppHandler {
                         }
                     }
 
-                    handle_authenticated!(ctx, app_state, routes::create_domain)
-                },
-                HandlerType::UpdateDomain => handle_authenticated!(ctx, app_state, routes::update_domain),
-                HandlerType::DeleteDomain => {
-                    if ctx.path().contains("/records/") {
-                        handle_authenticated!(ctx, app_state, routes::delete_domain_record)
-                    } else {
-                        handle_authenticated!(ctx, app_state, routes::delete_domain)
-                    }
+                    routes::create_domain(&ctx, app_state, claims()?).await
                 },
+                HandlerType::UpdateDomain => routes::update_domain(&ctx, app_state, claims()?).await,
+                HandlerType::DeleteDomain => routes::delete_domain(&ctx, app_state, claims()?).await,
+                HandlerType::DeleteDomainRecord => routes::delete_domain_record(&ctx, app_state, claims()?).await,
                 HandlerType::ResolveDomain => routes::resolve_domain(&ctx, app_state).await,
                 HandlerType::ResolveFullDomain => routes::resolve_full_domain(&ctx, app_state).await,
                 HandlerType::VerifyDomainOwnership => routes::verify_domain_ownership(&ctx, app_state).await,
@@ -216,6 +190,7 @@ pub async fn start(cli: crate::Cli) -> std::io::Result<()> {
     }
 
     let jwt_secret = config.auth.jwt_secret.clone();
+    let auth = JwtAuth::new(jwt_secret.clone()).with_trusted_services(config.auth.trusted_services.clone());
     let app_state = AppState::new(config.clone(), db, jwt_secret);
     let rate_limit_state = RateLimitState::new();
 
@@ -227,6 +202,14 @@ pub async fn start(cli: crate::Cli) -> std::io::Result<()> {
         crashln!("Failed to load TLS certificates: {}", e);
     }
 
+    if let Some(client_ca_path) = &config.server.client_ca_path {
+        if let Err(e) = server.load_client_auth(client_ca_path, ClientAuthMode::Request) {
+            crashln!("Failed to load client CA certificates: {}", e);
+        }
+    } else if !config.auth.trusted_services.is_empty() {
+        log::warn!("auth.trusted_services is set without server.client_ca_path, so no service can authenticate by certificate");
+    }
+
     server = server
         .route(Route::get("/"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::Index })
         .route(Route::get("/domains"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetDomains })
@@ -234,22 +217,25 @@ pub async fn start(cli: crate::Cli) -> std::io::Result<()> {
         .route(Route::get("/check"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CheckDomain })
         .route(Route::post("/auth/register"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::Register })
         .route(Route::post("/auth/login"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::Login })
-        .route(Route::get("/auth/me"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetUserInfo })
-        .route(Route::post("/auth/invite"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateInvite })
-        .route(Route::post("/auth/redeem-invite"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::RedeemInvite })
-        .route(Route::post("/auth/create-domain-invite"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateDomainInvite })
-        .route(Route::post("/auth/redeem-domain-invite"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::RedeemDomainInvite })
-        .route(Route::get("/auth/domains"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetUserDomains })
-        .route(Route::post("/domain"), AppHandler { app_state: app_state.clone(), rate_limit_state: Some(rate_limit_state), handler_type: HandlerType::CreateDomain })
-        .route(Route::get("/domain/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetDomain })
-        .route(Route::post("/domain/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateDomainRecord })
-        .route(Route::put("/domain/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::UpdateDomain })
-        .route(Route::delete("/domain/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::DeleteDomain })
+        .route(Route::get("/auth/me").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetUserInfo })
+        .route(Route::post("/auth/invite").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateInvite })
+        .route(Route::post("/auth/redeem-invite").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::RedeemInvite })
+        .route(Route::post("/auth/create-domain-invite").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateDomainInvite })
+        .route(Route::post("/auth/redeem-domain-invite").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::RedeemDomainInvite })
+        .route(Route::get("/auth/domains").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetUserDomains })
+        .route(Route::post("/domain").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: Some(rate_limit_state), handler_type: HandlerType::CreateDomain })
+        .route(Route::get("/domain/:domain").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetDomain })
+        .route(Route::get("/domain/:domain/events").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::DomainStatusEvents })
+        .route(Route::get("/domain/:domain/records").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetDomainRecords })
+        .route(Route::post("/domain/:domain/records").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::CreateDomainRecord })
+        .route(Route::delete("/domain/:domain/records/:id").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::DeleteDomainRecord })
+        .route(Route::put("/domain/*").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::UpdateDomain })
+        .route(Route::delete("/domain/:name/:tld").with_middleware(auth.clone()), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::DeleteDomain })
         .route(Route::post("/resolve"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::ResolveDomain })
         .route(Route::post("/resolve-full"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::ResolveFullDomain })
-        .route(Route::get("/verify-ownership/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::VerifyDomainOwnership })
+        .route(Route::get("/verify-ownership/:domain"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::VerifyDomainOwnership })
         .route(Route::post("/ca/request-certificate"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::RequestCertificate })
-        .route(Route::get("/ca/certificate/*"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetCertificate })
+        .route(Route::get("/ca/certificate/:token"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetCertificate })
         .route(Route::get("/ca/root"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::GetCaCertificate })
         .route(Route::get("/register.html"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::StaticFile })
         .route(Route::get("/signup.html"), AppHandler { app_state: app_state.clone(), rate_limit_state: None, handler_type: HandlerType::StaticFile })
@@ -269,18 +255,16 @@ pub async fn start(cli: crate::Cli) -> std::io::Result<()> {
     log::info!("Starting CA bootstrap HTTP server on {}:{}", config.server.address, http_port);
     log::info!("GURT server listening on {}", config.get_address());
     
-    let result = tokio::try_join!(
-        ca_bootstrap_server,
-        async {
-            server.listen(&config.get_address()).await.map_err(|e| {
+    // The GURT server returns once it has drained after a shutdown signal; the
+    // bootstrap server is dropped along with it.
+    let address = config.get_address();
+    tokio::select! {
+        result = ca_bootstrap_server => result,
+        result = server.listen_with_shutdown(&address, gurtlib::server::shutdown_signal()) => {
+            result.map_err(|e| {
                 std::io::Error::new(std::io::ErrorKind::Other, format!("GURT server error: {}", e))
             })
         }
-    );
-    
-    match result {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
     }
 }
 
@@ -40,6 +40,7 @@ pub(crate) async fn register(ctx: &ServerContext, app_state: AppState) -> Result
             // Generate JWT
             match generate_jwt(user_id, &user.username, &app_state.jwt_secret) {
                 Ok(token) => {
+                    let cookie = token_cookie(&token);
                     let response = LoginResponse {
                         token,
                         user: UserInfo {
@@ -50,7 +51,7 @@ pub(crate) async fn register(ctx: &ServerContext, app_state: AppState) -> Result
                             created_at: Utc::now(),
                         },
                     };
-                    Ok(GurtResponse::ok().with_json_body(&response)?)
+                    Ok(GurtResponse::ok().with_json_body(&response)?.with_cookie(cookie))
                 }
                 Err(_) => {
                     Ok(GurtResponse::internal_server_error().with_json_body(&Error {
@@ -62,7 +63,7 @@ pub(crate) async fn register(ctx: &ServerContext, app_state: AppState) -> Result
         }
         Err(e) => {
             if e.to_string().contains("duplicate key") {
-                Ok(GurtResponse::bad_request().with_json_body(&Error {
+                Ok(GurtResponse::conflict().with_json_body(&Error {
                     msg: "Username already exists",
                     error: "DUPLICATE_USERNAME".into(),
                 })?)
@@ -101,6 +102,7 @@ pub(crate) async fn login(ctx: &ServerContext, app_state: AppState) -> Result<Gu
                     // Generate JWT
                     match generate_jwt(user.id, &user.username, &app_state.jwt_secret) {
                         Ok(token) => {
+                            let cookie = token_cookie(&token);
                             let response = LoginResponse {
                                 token,
                                 user: UserInfo {
@@ -111,7 +113,7 @@ pub(crate) async fn login(ctx: &ServerContext, app_state: AppState) -> Result<Gu
                                     created_at: user.created_at,
                                 },
                             };
-                            Ok(GurtResponse::ok().with_json_body(&response)?)
+                            Ok(GurtResponse::ok().with_json_body(&response)?.with_cookie(cookie))
                         }
                         Err(_) => {
                             Ok(GurtResponse::internal_server_error().with_json_body(&Error {
@@ -3,23 +3,14 @@ use crate::auth::Claims;
 use crate::discord_bot::{send_domain_approval_request, DomainRegistration};
 use base64::{engine::general_purpose, Engine as _};
 use gurtlib::prelude::*;
+use gurtlib::Event;
 use rand::{rngs::OsRng, Rng};
 use sha2::{Digest, Sha256};
 use std::time::{SystemTime, UNIX_EPOCH};
-use std::{collections::HashMap, env};
+use std::env;
 
 const VALID_DNS_RECORD_TYPES: &[&str] = &["A", "AAAA", "CNAME", "TXT"];
 
-fn parse_query_string(query: &str) -> HashMap<String, String> {
-    let mut params = HashMap::new();
-    for pair in query.split('&') {
-        if let Some((key, value)) = pair.split_once('=') {
-            params.insert(key.to_string(), value.to_string());
-        }
-    }
-    params
-}
-
 pub(crate) async fn index(ctx: &ServerContext, _app_state: AppState) -> Result<GurtResponse> {
     let host_header = ctx.request.header("host")
         .map(|s| s.as_str())
@@ -174,13 +165,7 @@ pub(crate) async fn get_domain(
     app_state: AppState,
     claims: Claims,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 3 {
-        return Ok(GurtResponse::bad_request()
-            .with_string_body("Invalid path format. Expected /domain/{domainName}"));
-    }
-
-    let domain_name = path_parts[2];
+    let domain_name = ctx.param("domain").unwrap_or_default();
 
     let domain_parts: Vec<&str> = domain_name.split('.').collect();
     if domain_parts.len() < 2 {
@@ -188,51 +173,107 @@ pub(crate) async fn get_domain(
             .with_string_body("Invalid domain format. Expected name.tld"));
     }
 
-    let name = domain_parts[0];
-    let tld = domain_parts[1];
+    match find_user_domain(&app_state, domain_parts[0], domain_parts[1], claims.user_id).await? {
+        Some(domain) => Ok(GurtResponse::ok().with_json_body(&domain)?),
+        None => Ok(GurtResponse::not_found().with_string_body("Domain not found")),
+    }
+}
 
+async fn find_user_domain(app_state: &AppState, name: &str, tld: &str, user_id: i32) -> Result<Option<DomainDetail>> {
     let domain: Option<Domain> = sqlx::query_as::<_, Domain>(
         "SELECT id, name, tld, ip, user_id, status, denial_reason, created_at FROM domains WHERE name = $1 AND tld = $2 AND user_id = $3"
     )
     .bind(name)
     .bind(tld)
-    .bind(claims.user_id)
+    .bind(user_id)
     .fetch_optional(&app_state.db)
     .await
     .map_err(|_| GurtError::invalid_message("Database error"))?;
 
-    match domain {
-        Some(domain) => {
-            let response_domain = DomainDetail {
-                name: domain.name,
-                tld: domain.tld,
-                status: domain.status,
+    Ok(domain.map(|domain| DomainDetail {
+        name: domain.name,
+        tld: domain.tld,
+        status: domain.status,
+    }))
+}
+
+const STATUS_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Streams `status` events for one of the user's domains: the current status right away,
+/// then every change made through the Discord review. Ends with a `deleted` event if
+/// the domain goes away.
+pub(crate) async fn domain_status_events(
+    ctx: &ServerContext,
+    app_state: AppState,
+    claims: Claims,
+) -> Result<GurtResponse> {
+    let domain_name = ctx.param("domain").unwrap_or_default();
+
+    let domain_parts: Vec<&str> = domain_name.split('.').collect();
+    if domain_parts.len() < 2 {
+        return Ok(GurtResponse::bad_request()
+            .with_string_body("Invalid domain format. Expected name.tld"));
+    }
+
+    let name = domain_parts[0].to_string();
+    let tld = domain_parts[1].to_string();
+
+    let Some(mut domain) = find_user_domain(&app_state, &name, &tld, claims.user_id).await? else {
+        return Ok(GurtResponse::not_found().with_string_body("Domain not found"));
+    };
+
+    let (events, response) = GurtResponse::event_stream(8);
+    events.send(Event::json(&domain)?.with_event("status")).await?;
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATUS_POLL_INTERVAL);
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = events.closed() => break,
+            }
+
+            let event = match find_user_domain(&app_state, &name, &tld, claims.user_id).await {
+                Ok(Some(current)) if current.status != domain.status => {
+                    domain = current;
+                    match Event::json(&domain) {
+                        Ok(event) => event.with_event("status"),
+                        Err(_) => break,
+                    }
+                }
+                Ok(Some(_)) => continue,
+                Ok(None) => {
+                    let _ = events.send(Event::new(format!("{}.{}", name, tld)).with_event("deleted")).await;
+                    break;
+                }
+                Err(e) => {
+                    log::error!("Failed to poll status of {}.{}: {}", name, tld, e);
+                    break;
+                }
             };
-            Ok(GurtResponse::ok().with_json_body(&response_domain)?)
+
+            if events.send(event).await.is_err() {
+                break;
+            }
         }
-        None => Ok(GurtResponse::not_found().with_string_body("Domain not found")),
-    }
+    });
+
+    Ok(response)
 }
 
 pub(crate) async fn get_domains(ctx: &ServerContext, app_state: AppState) -> Result<GurtResponse> {
     // Parse pagination from query parameters
-    let path = ctx.path();
-    let query_params = if let Some(query_start) = path.find('?') {
-        let query_string = &path[query_start + 1..];
-        parse_query_string(query_string)
-    } else {
-        HashMap::new()
-    };
+    let query_params = ctx.query_params();
 
     let page = query_params
-        .get("page")
-        .and_then(|p| p.parse::<u32>().ok())
+        .get_as::<u32>("page")
         .unwrap_or(1)
         .max(1); // Ensure page is at least 1
 
     let page_size = query_params
-        .get("limit")
-        .and_then(|l| l.parse::<u32>().ok())
+        .get_as::<u32>("limit")
         .unwrap_or(100)
         .clamp(1, 1000); // Limit between 1 and 1000
 
@@ -270,14 +311,11 @@ pub(crate) async fn get_tlds(app_state: AppState) -> Result<GurtResponse> {
 }
 
 pub(crate) async fn check_domain(ctx: &ServerContext, app_state: AppState) -> Result<GurtResponse> {
-    let path = ctx.path();
-    let query_params = if let Some(query_start) = path.find('?') {
-        let query_string = &path[query_start + 1..];
-        parse_query_string(query_string)
-    } else {
+    let query_params = ctx.query_params();
+    if query_params.is_empty() {
         return Ok(GurtResponse::bad_request()
             .with_string_body("Missing query parameters. Expected ?name=<name>&tld=<tld>"));
-    };
+    }
 
     let name = query_params
         .get("name")
@@ -317,14 +355,8 @@ pub(crate) async fn delete_domain(
     app_state: AppState,
     claims: Claims,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 4 {
-        return Ok(GurtResponse::bad_request()
-            .with_string_body("Invalid path format. Expected /domain/{name}/{tld}"));
-    }
-
-    let name = path_parts[2];
-    let tld = path_parts[3];
+    let name = ctx.param("name").unwrap_or_default();
+    let tld = ctx.param("tld").unwrap_or_default();
 
     // Verify user owns this domain
     let domain: Option<Domain> = sqlx::query_as::<_, Domain>(
@@ -358,23 +390,15 @@ pub(crate) async fn get_user_domains(
     claims: Claims,
 ) -> Result<GurtResponse> {
     // Parse pagination from query parameters
-    let path = ctx.path();
-    let query_params = if let Some(query_start) = path.find('?') {
-        let query_string = &path[query_start + 1..];
-        parse_query_string(query_string)
-    } else {
-        HashMap::new()
-    };
+    let query_params = ctx.query_params();
 
     let page = query_params
-        .get("page")
-        .and_then(|p| p.parse::<u32>().ok())
+        .get_as::<u32>("page")
         .unwrap_or(1)
         .max(1);
 
     let page_size = query_params
-        .get("limit")
-        .and_then(|l| l.parse::<u32>().ok())
+        .get_as::<u32>("limit")
         .unwrap_or(100)
         .clamp(1, 1000);
 
@@ -414,13 +438,7 @@ pub(crate) async fn get_domain_records(
     app_state: AppState,
     claims: Claims,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 4 {
-        return Ok(GurtResponse::bad_request()
-            .with_string_body("Invalid path format. Expected /domain/{domainName}/records"));
-    }
-
-    let domain_name = path_parts[2];
+    let domain_name = ctx.param("domain").unwrap_or_default();
 
     let domain_parts: Vec<&str> = domain_name.split('.').collect();
     if domain_parts.len() < 2 {
@@ -478,13 +496,7 @@ pub(crate) async fn create_domain_record(
     app_state: AppState,
     claims: Claims,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 4 {
-        return Ok(GurtResponse::bad_request()
-            .with_string_body("Invalid path format. Expected /domain/{domainName}/records"));
-    }
-
-    let domain_name = path_parts[2];
+    let domain_name = ctx.param("domain").unwrap_or_default();
 
     let domain_parts: Vec<&str> = domain_name.split('.').collect();
     if domain_parts.len() < 2 {
@@ -598,18 +610,8 @@ pub(crate) async fn delete_domain_record(
     app_state: AppState,
     claims: Claims,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 5 {
-        return Ok(GurtResponse::bad_request().with_string_body(
-            "Invalid path format. Expected /domain/{domainName}/records/{recordId}",
-        ));
-    }
-
-    let domain_name = path_parts[2];
-    let record_id_str = path_parts[4];
-
-    let record_id: i32 = record_id_str
-        .parse()
+    let domain_name = ctx.param("domain").unwrap_or_default();
+    let record_id: i32 = ctx.param_as("id")
         .map_err(|_| GurtError::invalid_message("Invalid record ID"))?;
 
     let domain_parts: Vec<&str> = domain_name.split('.').collect();
@@ -887,12 +889,7 @@ pub(crate) async fn verify_domain_ownership(
     ctx: &ServerContext,
     app_state: AppState,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 3 {
-        return Ok(GurtResponse::bad_request().with_string_body("Invalid path format"));
-    }
-
-    let domain = path_parts[2];
+    let domain = ctx.param("domain").unwrap_or_default();
 
     let domain_parts: Vec<&str> = domain.split('.').collect();
     if domain_parts.len() < 2 {
@@ -984,12 +981,7 @@ pub(crate) async fn get_certificate(
     ctx: &ServerContext,
     app_state: AppState,
 ) -> Result<GurtResponse> {
-    let path_parts: Vec<&str> = ctx.path().split('/').collect();
-    if path_parts.len() < 4 {
-        return Ok(GurtResponse::bad_request().with_string_body("Invalid path format"));
-    }
-
-    let token = path_parts[3];
+    let token = ctx.param("token").unwrap_or_default();
 
     let challenge: Option<(String, String, String, Option<String>, chrono::DateTime<chrono::Utc>)> = sqlx::query_as(
         "SELECT domain, challenge_type, verification_data, csr_pem, expires_at FROM certificate_challenges WHERE token = $1"
@@ -138,7 +138,7 @@ impl ServeCommandHandler {
         
         info!("Starting GURT server on {}", config.address());
         
-        if let Err(e) = server.listen(&config.address()).await {
+        if let Err(e) = server.listen_with_shutdown(&config.address(), gurtlib::server::shutdown_signal()).await {
             error!("Server error: {}", e);
             std::process::exit(1);
         }
@@ -1,10 +1,11 @@
 use serde::{Deserialize, Serialize};
+use gurtlib::{ConnectionLimitPolicy, ContentEncoding};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GurtConfig {
     pub server: ServerConfig,
     pub tls: Option<TlsConfig>,
@@ -12,6 +13,7 @@ pub struct GurtConfig {
     pub security: Option<SecurityConfig>,
     pub error_pages: Option<ErrorPagesConfig>,
     pub headers: Option<HashMap<String, String>>,
+    pub compression: Option<CompressionConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +35,9 @@ pub struct ServerConfig {
     #[serde(default = "default_max_connections")]
     pub max_connections: u32,
     
+    #[serde(default = "default_connection_limit_policy")]
+    pub on_connection_limit: String,
+    
     #[serde(default = "default_max_message_size")]
     pub max_message_size: String,
     
@@ -51,11 +56,17 @@ pub struct TimeoutsConfig {
     #[serde(default = "default_request_timeout")]
     pub request: u64,
     
+    #[serde(default = "default_handler_timeout")]
+    pub handler: u64,
+    
     #[serde(default = "default_connection_timeout")]
     pub connection: u64,
     
     #[serde(default = "default_pool_idle_timeout")]
     pub pool_idle: u64,
+    
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,6 +105,18 @@ pub struct SecurityConfig {
     pub rate_limit_connections: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default = "default_compression_enabled")]
+    pub enabled: bool,
+    
+    #[serde(default = "default_compression_min_size")]
+    pub min_size: String,
+    
+    #[serde(default = "default_compression_algorithms")]
+    pub algorithms: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorPagesConfig {
     #[serde(flatten)]
@@ -125,8 +148,8 @@ impl ErrorPagesConfig {
     pub fn get_page_content(&self, status_code: u16, base_dir: &std::path::Path) -> Option<String> {
         if let Some(page_value) = self.get_page(status_code) {
             if page_value.starts_with('/') || page_value.starts_with("./") {
-                let file_path = if page_value.starts_with('/') {
-                    base_dir.join(&page_value[1..])
+                let file_path = if let Some(stripped) = page_value.strip_prefix('/') {
+                    base_dir.join(stripped)
                 } else {
                     base_dir.join(page_value)
                 };
@@ -161,11 +184,14 @@ fn default_port() -> u16 { 4878 }
 fn default_protocol_version() -> String { "1.0.0".to_string() }
 fn default_alpn_identifier() -> String { "GURT/1.0".to_string() }
 fn default_max_connections() -> u32 { 10 }
+fn default_connection_limit_policy() -> String { "wait".to_string() }
 fn default_max_message_size() -> String { "10MB".to_string() }
 fn default_handshake_timeout() -> u64 { 5 }
 fn default_request_timeout() -> u64 { 30 }
+fn default_handler_timeout() -> u64 { 30 }
 fn default_connection_timeout() -> u64 { 10 }
 fn default_pool_idle_timeout() -> u64 { 300 }
+fn default_shutdown_timeout() -> u64 { 30 }
 fn default_log_level() -> String { "info".to_string() }
 fn default_log_requests() -> bool { true }
 fn default_allowed_methods() -> Vec<String> {
@@ -174,16 +200,18 @@ fn default_allowed_methods() -> Vec<String> {
 }
 fn default_rate_limit_requests() -> u32 { 100 }
 fn default_rate_limit_connections() -> u32 { 10 }
+fn default_compression_enabled() -> bool { true }
+fn default_compression_min_size() -> String { "1KB".to_string() }
+fn default_compression_algorithms() -> Vec<String> {
+    ContentEncoding::ALL.iter().map(|encoding| encoding.as_str().to_string()).collect()
+}
 
-impl Default for GurtConfig {
+impl Default for CompressionConfig {
     fn default() -> Self {
         Self {
-            server: ServerConfig::default(),
-            tls: None,
-            logging: None,
-            security: None,
-            error_pages: None,
-            headers: None,
+            enabled: default_compression_enabled(),
+            min_size: default_compression_min_size(),
+            algorithms: default_compression_algorithms(),
         }
     }
 }
@@ -197,6 +225,7 @@ impl Default for ServerConfig {
             alpn_identifier: default_alpn_identifier(),
             timeouts: None,
             max_connections: default_max_connections(),
+            on_connection_limit: default_connection_limit_policy(),
             max_message_size: default_max_message_size(),
             base_directory: Arc::new(PathBuf::from(".")),
             verbose: false,
@@ -245,6 +274,15 @@ impl GurtConfig {
         )
     }
 
+    pub fn get_handler_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.server.timeouts
+                .as_ref()
+                .map(|t| t.handler)
+                .unwrap_or(default_handler_timeout())
+        )
+    }
+
     pub fn get_connection_timeout(&self) -> Duration {
         Duration::from_secs(
             self.server.timeouts
@@ -254,6 +292,43 @@ impl GurtConfig {
         )
     }
 
+    pub fn get_connection_limit_policy(&self) -> ConnectionLimitPolicy {
+        match self.server.on_connection_limit.as_str() {
+            "reject" => ConnectionLimitPolicy::Reject,
+            _ => ConnectionLimitPolicy::Wait,
+        }
+    }
+
+    pub fn get_shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.server.timeouts
+                .as_ref()
+                .map(|t| t.shutdown)
+                .unwrap_or(default_shutdown_timeout())
+        )
+    }
+
+    /// The codings gurty may compress responses with, most preferred first. Empty when
+    /// compression is disabled.
+    pub fn get_compression_encodings(&self) -> Vec<ContentEncoding> {
+        let default = CompressionConfig::default();
+        let compression = self.compression.as_ref().unwrap_or(&default);
+
+        if !compression.enabled {
+            return Vec::new();
+        }
+        compression.algorithms.iter()
+            .filter_map(|name| ContentEncoding::parse(name))
+            .collect()
+    }
+
+    /// Bodies smaller than this are sent uncompressed.
+    pub fn get_compression_min_size(&self) -> u64 {
+        self.compression.as_ref()
+            .and_then(|compression| parse_size(&compression.min_size).ok())
+            .unwrap_or(1_000)
+    }
+
     pub fn should_deny_file(&self, file_path: &str) -> bool {
         if let Some(security) = &self.security {
             for pattern in &security.deny_files {
@@ -294,6 +369,22 @@ impl GurtConfig {
             ));
         }
 
+        if !matches!(self.server.on_connection_limit.as_str(), "wait" | "reject") {
+            return Err(crate::ServerError::InvalidConfiguration(
+                format!("Invalid on_connection_limit '{}', expected \"wait\" or \"reject\"", self.server.on_connection_limit)
+            ));
+        }
+
+        if let Some(compression) = &self.compression {
+            parse_size(&compression.min_size)?;
+
+            if let Some(unknown) = compression.algorithms.iter().find(|name| ContentEncoding::parse(name).is_none()) {
+                return Err(crate::ServerError::InvalidConfiguration(
+                    format!("Unknown compression algorithm '{}', expected \"zstd\", \"br\" or \"gzip\"", unknown)
+                ));
+            }
+        }
+
         if let Some(tls) = &self.tls {
             if !tls.certificate.exists() {
                 return Err(crate::ServerError::TlsConfiguration(
@@ -443,8 +534,7 @@ fn parse_size(size_str: &str) -> crate::Result<u64> {
 }
 
 fn matches_pattern(path: &str, pattern: &str) -> bool {
-    if pattern.ends_with("/*") {
-        let prefix = &pattern[..pattern.len() - 2];
+    if let Some(prefix) = pattern.strip_suffix("/*") {
         path.starts_with(prefix)
     } else if pattern.starts_with("*.") {
         let suffix = &pattern[1..];
@@ -462,6 +552,7 @@ fn merge_configs(base: GurtConfig, override_config: GurtConfig) -> GurtConfig {
         security: override_config.security.or(base.security),
         error_pages: override_config.error_pages.or(base.error_pages),
         headers: override_config.headers.or(base.headers),
+        compression: override_config.compression.or(base.compression),
     }
 }
 
@@ -485,6 +576,11 @@ fn merge_server_configs(base: ServerConfig, override_config: ServerConfig) -> Se
         } else { 
             base.max_connections 
         },
+        on_connection_limit: if override_config.on_connection_limit != default_connection_limit_policy() { 
+            override_config.on_connection_limit 
+        } else { 
+            base.on_connection_limit 
+        },
         max_message_size: if override_config.max_message_size != default_max_message_size() { 
             override_config.max_message_size 
         } else { 
@@ -572,12 +668,13 @@ host = "0.0.0.0"
 
     #[test]
     fn test_tls_config_validation() {
-        let mut config = GurtConfig::default();
-        
-        config.tls = Some(TlsConfig {
-            certificate: PathBuf::from("/nonexistent/cert.pem"),
-            private_key: PathBuf::from("/nonexistent/key.pem"),
-        });
+        let config = GurtConfig {
+            tls: Some(TlsConfig {
+                certificate: PathBuf::from("/nonexistent/cert.pem"),
+                private_key: PathBuf::from("/nonexistent/key.pem"),
+            }),
+            ..Default::default()
+        };
         
         assert!(config.tls.is_some());
         let tls = config.tls.unwrap();
@@ -602,6 +699,43 @@ host = "0.0.0.0"
         
         assert_eq!(config.get_handshake_timeout(), Duration::from_secs(5));
         assert_eq!(config.get_request_timeout(), Duration::from_secs(30));
+        assert_eq!(config.get_handler_timeout(), Duration::from_secs(30));
         assert_eq!(config.get_connection_timeout(), Duration::from_secs(10));
     }
+
+    #[test]
+    fn test_connection_limit_policy() {
+        let mut config = GurtConfig::default();
+        assert_eq!(config.get_connection_limit_policy(), ConnectionLimitPolicy::Wait);
+        
+        config.server.on_connection_limit = "reject".to_string();
+        assert_eq!(config.get_connection_limit_policy(), ConnectionLimitPolicy::Reject);
+        
+        config.server.on_connection_limit = "drop".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_compression_config() {
+        let config = GurtConfig::default();
+        assert_eq!(config.get_compression_encodings(), ContentEncoding::ALL.to_vec());
+        assert_eq!(config.get_compression_min_size(), 1_000);
+
+        let toml_content = r#"
+[server]
+
+[compression]
+min_size = "4KB"
+algorithms = ["gzip"]
+"#;
+        let config = GurtConfig::from_toml(toml_content, PathBuf::from("/tmp")).unwrap();
+        assert_eq!(config.get_compression_encodings(), vec![ContentEncoding::Gzip]);
+        assert_eq!(config.get_compression_min_size(), 4_000);
+
+        let config = GurtConfig::from_toml("[server]\n[compression]\nenabled = false", PathBuf::from("/tmp")).unwrap();
+        assert!(config.get_compression_encodings().is_empty());
+
+        let config = GurtConfig::from_toml("[server]\n[compression]\nalgorithms = [\"lzma\"]", PathBuf::from("/tmp")).unwrap();
+        assert!(config.validate().is_err());
+    }
 }
@@ -1,9 +1,27 @@
+use gurtlib::{ByteRange, GurtBodyStream};
+use std::io::{Seek, SeekFrom};
 use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+/// Files larger than this are streamed from disk instead of read into memory.
+pub const STREAMING_THRESHOLD: u64 = 1024 * 1024;
 
 pub trait FileHandler: Send + Sync {
     fn can_handle(&self, path: &Path) -> bool;
     fn get_content_type(&self, path: &Path) -> String;
     fn handle_file(&self, path: &Path) -> crate::Result<Vec<u8>>;
+
+    /// Opens the file as a body stream along with its length. Returning `None`
+    /// serves the file through `handle_file` instead.
+    fn stream_file(&self, _path: &Path) -> crate::Result<Option<(GurtBodyStream, u64)>> {
+        Ok(None)
+    }
+
+    /// Opens `range` of the file as a body stream. Returning `None` means ranges are
+    /// not supported and the whole file is served instead.
+    fn stream_range(&self, _path: &Path, _range: ByteRange) -> crate::Result<Option<GurtBodyStream>> {
+        Ok(None)
+    }
 }
 
 pub struct DefaultFileHandler;
@@ -18,6 +36,7 @@ impl FileHandler for DefaultFileHandler {
             Some("html") | Some("htm") => "text/html".to_string(),
             Some("css") => "text/css".to_string(),
             Some("js") => "application/javascript".to_string(),
+            Some("lua") => "text/x-lua".to_string(),
             Some("json") => "application/json".to_string(),
             Some("png") => "image/png".to_string(),
             Some("jpg") | Some("jpeg") => "image/jpeg".to_string(),
@@ -34,6 +53,26 @@ impl FileHandler for DefaultFileHandler {
     fn handle_file(&self, path: &Path) -> crate::Result<Vec<u8>> {
         std::fs::read(path).map_err(crate::ServerError::from)
     }
+
+    fn stream_file(&self, path: &Path) -> crate::Result<Option<(GurtBodyStream, u64)>> {
+        let file = std::fs::File::open(path)?;
+        let length = file.metadata()?.len();
+
+        if length <= STREAMING_THRESHOLD {
+            return Ok(None);
+        }
+
+        let stream = GurtBodyStream::from_reader(tokio::fs::File::from_std(file));
+        Ok(Some((stream, length)))
+    }
+
+    fn stream_range(&self, path: &Path, range: ByteRange) -> crate::Result<Option<GurtBodyStream>> {
+        let mut file = std::fs::File::open(path)?;
+        file.seek(SeekFrom::Start(range.start))?;
+
+        let reader = tokio::fs::File::from_std(file).take(range.len());
+        Ok(Some(GurtBodyStream::from_reader(reader)))
+    }
 }
 
 pub trait DirectoryHandler: Send + Sync {
@@ -120,6 +159,28 @@ mod tests {
         assert_eq!(handler.get_content_type(Path::new("noextension")), "application/octet-stream");
     }
 
+    #[test]
+    fn test_stream_file_threshold() {
+        use std::fs;
+        use std::env;
+
+        let temp_dir = env::temp_dir().join("gurty_test_stream");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let small = temp_dir.join("small.txt");
+        let large = temp_dir.join("large.bin");
+        let _ = fs::write(&small, "small");
+        let _ = fs::write(&large, vec![0u8; STREAMING_THRESHOLD as usize + 1]);
+
+        let handler = DefaultFileHandler;
+        assert!(handler.stream_file(&small).unwrap().is_none());
+
+        let (_, length) = handler.stream_file(&large).unwrap().expect("large file should stream");
+        assert_eq!(length, STREAMING_THRESHOLD + 1);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
     #[test]
     fn test_directory_handler_generates_valid_html() {
         use std::fs;
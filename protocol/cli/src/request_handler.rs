@@ -1,11 +1,15 @@
 use crate::{
     handlers::{FileHandler, DirectoryHandler, DefaultFileHandler, DefaultDirectoryHandler},
     config::GurtConfig,
-    security::SecurityMiddleware,
+    security::{ConnectionGuard, SecurityMiddleware},
 };
 use gurtlib::prelude::*;
+use gurtlib::{compression, range, ContentEncoding, GurtMethod, GurtStatusCode, RangeRequest, Validators};
 use std::path::Path;
-use std::sync::Arc;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
 use tracing;
 
 pub struct RequestHandlerBuilder {
@@ -42,11 +46,15 @@ impl RequestHandlerBuilder {
 
     pub fn build(self) -> RequestHandler {
         let security = self.config.as_ref().map(|config| SecurityMiddleware::new(config.clone()));
+        let default_config = GurtConfig::default();
+        let compression_config = self.config.as_deref().unwrap_or(&default_config);
         
         RequestHandler {
             file_handler: self.file_handler,
             directory_handler: self.directory_handler,
             base_directory: self.base_directory,
+            compression_encodings: compression_config.get_compression_encodings(),
+            compression_min_size: compression_config.get_compression_min_size(),
             config: self.config,
             security,
         }
@@ -59,6 +67,8 @@ pub struct RequestHandler {
     base_directory: std::path::PathBuf,
     config: Option<Arc<GurtConfig>>,
     security: Option<SecurityMiddleware>,
+    compression_encodings: Vec<ContentEncoding>,
+    compression_min_size: u64,
 }
 
 impl RequestHandler {
@@ -197,61 +207,30 @@ impl RequestHandler {
     }
 
     pub async fn handle_root_request_with_context(&self, ctx: ServerContext) -> std::result::Result<GurtResponse, GurtError> {
-        let client_ip = ctx.client_ip();
-        
-        self.register_connection(client_ip);
-        
-        if let Some(security_response) = self.check_security(&ctx) {
-            self.unregister_connection(client_ip);
-            return security_response;
-        }
-        
-        let result = self.handle_root_request().await;
-        self.unregister_connection(client_ip);
-        result
+        self.serve_root(Some(&ctx.request)).await
     }
 
     pub async fn handle_file_request_with_context(&self, request_path: &str, ctx: ServerContext) -> std::result::Result<GurtResponse, GurtError> {
-        let client_ip = ctx.client_ip();
-        
-        self.register_connection(client_ip);
-        
-        if let Some(security_response) = self.check_security(&ctx) {
-            self.unregister_connection(client_ip);
-            return security_response;
-        }
-        
-        let result = self.handle_file_request(request_path).await;
-        self.unregister_connection(client_ip);
-        result
+        self.serve_path(request_path, Some(&ctx.request)).await
     }
 
     pub async fn handle_method_request_with_context(&self, ctx: ServerContext) -> std::result::Result<GurtResponse, GurtError> {
-        let client_ip = ctx.client_ip();
-        let method = ctx.method();
-        
-        self.register_connection(client_ip);
-        
-        if let Some(security_response) = self.check_security(&ctx) {
-            self.unregister_connection(client_ip);
-            return security_response;
-        }
-        
-        let result = match method {
+        match ctx.method() {
             gurtlib::message::GurtMethod::GET => {
                 if ctx.path() == "/" {
-                    self.handle_root_request().await
+                    self.serve_root(Some(&ctx.request)).await
                 } else {
-                    self.handle_file_request(ctx.path()).await
+                    self.serve_path(ctx.path(), Some(&ctx.request)).await
                 }
             }
             gurtlib::message::GurtMethod::HEAD => {
                 let mut response = if ctx.path() == "/" {
-                    self.handle_root_request().await?
+                    self.serve_root(Some(&ctx.request)).await?
                 } else {
-                    self.handle_file_request(ctx.path()).await?
+                    self.serve_path(ctx.path(), Some(&ctx.request)).await?
                 };
                 response.body = Vec::new();
+                response.body_stream = None;
                 Ok(response)
             }
             gurtlib::message::GurtMethod::OPTIONS => {
@@ -276,13 +255,14 @@ impl RequestHandler {
                     .with_header("Content-Type", "text/html");
                 Ok(self.apply_global_headers(response))
             }
-        };
-        
-        self.unregister_connection(client_ip);
-        result
+        }
     }
 
     pub async fn handle_root_request(&self) -> std::result::Result<GurtResponse, GurtError> {
+        self.serve_root(None).await
+    }
+
+    async fn serve_root(&self, request: Option<&GurtRequest>) -> std::result::Result<GurtResponse, GurtError> {
         let index_path = self.base_directory.join("index.html");
 
         if index_path.exists() && index_path.is_file() {
@@ -290,13 +270,18 @@ impl RequestHandler {
                 return self.create_forbidden_response();
             }
 
+            let validators = std::fs::metadata(&index_path).ok().map(|metadata| Validators::from_metadata(&metadata));
+            if let Some(response) = self.not_modified_response(validators.as_ref(), request) {
+                return Ok(response);
+            }
+
             match self.file_handler.handle_file(&index_path) {
                 Ok(content) => {
                     let content_type = self.file_handler.get_content_type(&index_path);
                     let response = GurtResponse::ok()
                         .with_header("Content-Type", &content_type)
                         .with_body(content);
-                    return Ok(self.apply_global_headers(response));
+                    return Ok(self.apply_global_headers(with_validators(response, validators.as_ref())));
                 }
                 Err(_) => {
                     // fall
@@ -320,6 +305,12 @@ impl RequestHandler {
     }
 
     pub async fn handle_file_request(&self, request_path: &str) -> std::result::Result<GurtResponse, GurtError> {
+        self.serve_path(request_path, None).await
+    }
+
+    /// Serves the file or directory at `request_path`. When `request` is given, its
+    /// conditional headers are checked and an unchanged file is answered with 304.
+    async fn serve_path(&self, request_path: &str, request: Option<&GurtRequest>) -> std::result::Result<GurtResponse, GurtError> {
         let path_without_query = if let Some(query_start) = request_path.find('?') {
             &request_path[..query_start]
         } else {
@@ -365,9 +356,9 @@ impl RequestHandler {
                 }
                 
                 if canonical_path.is_file() {
-                    self.handle_file_response(&canonical_path).await
+                    self.handle_file_response(&canonical_path, request).await
                 } else if canonical_path.is_dir() {
-                    self.handle_directory_response(&canonical_path, request_path).await
+                    self.handle_directory_response(&canonical_path, request_path, request).await
                 } else {
                     self.handle_not_found_response().await
                 }
@@ -378,14 +369,44 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_file_response(&self, path: &Path) -> std::result::Result<GurtResponse, GurtError> {
+    async fn handle_file_response(&self, path: &Path, request: Option<&GurtRequest>) -> std::result::Result<GurtResponse, GurtError> {
+        let metadata = std::fs::metadata(path).ok();
+        let validators = metadata.as_ref().map(Validators::from_metadata);
+        if let Some(response) = self.not_modified_response(validators.as_ref(), request) {
+            return Ok(response);
+        }
+
+        if let (Some(metadata), Some(request)) = (&metadata, request) {
+            if let Some(response) = self.range_response(path, metadata.len(), validators.as_ref(), request) {
+                return Ok(self.apply_global_headers(with_validators(response, validators.as_ref())));
+            }
+        }
+
+        match self.file_handler.stream_file(path) {
+            Ok(Some((stream, length))) => {
+                let content_type = self.file_handler.get_content_type(path);
+                let response = GurtResponse::ok()
+                    .with_header("Content-Type", &content_type)
+                    .with_header("Accept-Ranges", "bytes")
+                    .with_stream_body(stream, Some(length));
+                return Ok(self.apply_global_headers(with_validators(response, validators.as_ref())));
+            }
+            Ok(None) => {}
+            Err(_) => {
+                let response = GurtResponse::internal_server_error()
+                    .with_header("Content-Type", "text/html");
+                return Ok(self.apply_global_headers(response));
+            }
+        }
+
         match self.file_handler.handle_file(path) {
             Ok(content) => {
                 let content_type = self.file_handler.get_content_type(path);
                 let response = GurtResponse::ok()
                     .with_header("Content-Type", &content_type)
+                    .with_header("Accept-Ranges", "bytes")
                     .with_body(content);
-                Ok(self.apply_global_headers(response))
+                Ok(self.apply_global_headers(with_validators(response, validators.as_ref())))
             }
             Err(_) => {
                 let response = GurtResponse::internal_server_error()
@@ -395,10 +416,37 @@ impl RequestHandler {
         }
     }
 
-    async fn handle_directory_response(&self, canonical_path: &Path, request_path: &str) -> std::result::Result<GurtResponse, GurtError> {
+    /// Answers a GET carrying a `Range` header with 206 or 416. Returns `None` when the
+    /// whole file should be served instead, e.g. because `If-Range` no longer matches or
+    /// the file handler cannot serve ranges.
+    fn range_response(&self, path: &Path, length: u64, validators: Option<&Validators>, request: &GurtRequest) -> Option<GurtResponse> {
+        if request.method != GurtMethod::GET || validators.is_some_and(|validators| !validators.allows_range(request)) {
+            return None;
+        }
+
+        match RangeRequest::parse(request.header("range").map(String::as_str), length) {
+            RangeRequest::Full => None,
+            RangeRequest::Unsatisfiable => Some(range::range_not_satisfiable(length)),
+            RangeRequest::Partial(ranges) => {
+                let mut parts = Vec::with_capacity(ranges.len());
+                for range in ranges {
+                    match self.file_handler.stream_range(path, range) {
+                        Ok(Some(stream)) => parts.push((range, stream)),
+                        Ok(None) => return None,
+                        Err(_) => return Some(GurtResponse::internal_server_error().with_header("Content-Type", "text/html")),
+                    }
+                }
+
+                let content_type = self.file_handler.get_content_type(path);
+                Some(range::partial_response(parts, &content_type, length))
+            }
+        }
+    }
+
+    async fn handle_directory_response(&self, canonical_path: &Path, request_path: &str, request: Option<&GurtRequest>) -> std::result::Result<GurtResponse, GurtError> {
         let index_path = canonical_path.join("index.html");
         if index_path.is_file() {
-            self.handle_file_response(&index_path).await
+            self.handle_file_response(&index_path, request).await
         } else {
             match self.directory_handler.handle_directory(canonical_path, request_path) {
                 Ok(listing) => {
@@ -416,6 +464,54 @@ impl RequestHandler {
         }
     }
 
+    fn not_modified_response(&self, validators: Option<&Validators>, request: Option<&GurtRequest>) -> Option<GurtResponse> {
+        let validators = validators?;
+        if request.is_some_and(|request| validators.is_fresh(request)) {
+            Some(self.apply_global_headers(validators.not_modified()))
+        } else {
+            None
+        }
+    }
+
+    /// Compresses text responses with the best coding the client accepts.
+    async fn compress_response(&self, request: &GurtRequest, response: GurtResponse) -> std::result::Result<GurtResponse, GurtError> {
+        if !self.is_compressible(request, &response) {
+            return Ok(response);
+        }
+
+        // Uncompressed copies depend on accept-encoding too, so caches must key on it
+        let response = compression::add_vary(response, "accept-encoding");
+        let encoding = request.header("accept-encoding")
+            .and_then(|accept| compression::negotiate(accept, &self.compression_encodings));
+
+        match encoding {
+            Some(encoding) => compression::compress_response(response, encoding).await,
+            None => Ok(response),
+        }
+    }
+
+    fn is_compressible(&self, request: &GurtRequest, response: &GurtResponse) -> bool {
+        if self.compression_encodings.is_empty()
+            || request.method == GurtMethod::HEAD
+            || response.header("content-encoding").is_some()
+            || response.status().is_informational()
+            || matches!(response.status(), GurtStatusCode::NoContent | GurtStatusCode::PartialContent | GurtStatusCode::NotModified)
+        {
+            return false;
+        }
+
+        if !response.header("content-type").is_some_and(|content_type| compression::is_compressible(content_type)) {
+            return false;
+        }
+
+        let length = if response.is_streaming() {
+            response.header("content-length").and_then(|length| length.parse().ok()).unwrap_or(u64::MAX)
+        } else {
+            response.body.len() as u64
+        };
+        length >= self.compression_min_size
+    }
+
     async fn handle_not_found_response(&self) -> std::result::Result<GurtResponse, GurtError> {
         let content = self.get_custom_error_page(404)
             .unwrap_or_else(|| crate::handlers::get_404_html().to_string());
@@ -427,13 +523,70 @@ impl RequestHandler {
     }
 }
 
+fn with_validators(response: GurtResponse, validators: Option<&Validators>) -> GurtResponse {
+    match validators {
+        Some(validators) => validators.apply(response),
+        None => response,
+    }
+}
+
+/// Tracks concurrent connections and enforces the configured security policy
+/// before any route handler runs.
+impl GurtMiddleware for RequestHandler {
+    fn before<'a>(&'a self, ctx: &'a mut ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::result::Result<Option<GurtResponse>, GurtError>> + Send + 'a>> {
+        Box::pin(async move {
+            self.register_connection(ctx.client_ip());
+            // Held by the context, so the slot is freed even if the request is dropped before `after`
+            if let Some(guard) = self.security.as_ref().and_then(|security| security.guard_connection(ctx.client_ip())) {
+                ctx.insert_extension(HeldConnection(Mutex::new(Some(guard))));
+            }
+            self.check_security(ctx).transpose()
+        })
+    }
+
+    fn after<'a>(&'a self, ctx: &'a ServerContext, response: GurtResponse) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::result::Result<GurtResponse, GurtError>> + Send + 'a>> {
+        Box::pin(async move {
+            let guard = ctx.extension::<HeldConnection>().and_then(|held| held.0.lock().ok()?.take());
+            let response = self.compress_response(&ctx.request, response).await?;
+            
+            // A streamed body is written after this returns, so the connection stays counted until it is done
+            match (guard, response.body_stream.clone()) {
+                (Some(guard), Some(stream)) => Ok(GurtResponse {
+                    body_stream: Some(GurtBodyStream::from_reader(GuardedReader { reader: stream.reader()?, _guard: guard })),
+                    ..response
+                }),
+                _ => Ok(response),
+            }
+        })
+    }
+}
+
+/// The connection slot taken in `before`, handed to the response body in `after`.
+struct HeldConnection(Mutex<Option<ConnectionGuard>>);
+
+/// Reads a streamed body while holding its client's connection slot.
+struct GuardedReader<R> {
+    reader: R,
+    _guard: ConnectionGuard,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for GuardedReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().reader).poll_read(cx, buf)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use gurtlib::GurtStatusCode;
+    use gurtlib::{GurtMethod, GurtStatusCode};
     use std::fs;
     use std::env;
 
+    fn context(request: GurtRequest) -> ServerContext {
+        ServerContext::new("127.0.0.1:4878".parse().unwrap(), request)
+    }
+
     fn create_test_handler() -> RequestHandler {
         let temp_dir = env::temp_dir().join("gurty_request_handler_test");
         let _ = fs::create_dir_all(&temp_dir);
@@ -563,4 +716,194 @@ mod tests {
         assert_eq!(modified_response.status_code, 200);
         assert_eq!(modified_response.body, b"Success".to_vec());
     }
+
+    #[tokio::test]
+    async fn test_middleware_enforces_security_policy() {
+        let temp_dir = env::temp_dir().join("gurty_request_handler_test_middleware");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let config = GurtConfig {
+            security: Some(crate::config::SecurityConfig {
+                deny_files: Vec::new(),
+                allowed_methods: vec!["GET".to_string()],
+                rate_limit_requests: 100,
+                rate_limit_connections: 10,
+            }),
+            ..Default::default()
+        };
+        let handler = RequestHandler::builder(&temp_dir)
+            .with_config(Arc::new(config))
+            .build();
+
+        let mut ctx = context(GurtRequest::new(GurtMethod::GET, "/".to_string()));
+        assert!(handler.before(&mut ctx).await.unwrap().is_none());
+        handler.after(&ctx, GurtResponse::ok()).await.unwrap();
+
+        ctx.request.method = GurtMethod::DELETE;
+        let rejected = handler.before(&mut ctx).await.unwrap().unwrap();
+        assert_eq!(rejected.status_code, 405);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_streamed_response_holds_connection_slot() {
+        let temp_dir = env::temp_dir().join("gurty_request_handler_test_streamed");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let config = GurtConfig {
+            security: Some(crate::config::SecurityConfig {
+                deny_files: Vec::new(),
+                allowed_methods: vec!["GET".to_string()],
+                rate_limit_requests: 100,
+                rate_limit_connections: 2,
+            }),
+            ..Default::default()
+        };
+        let handler = RequestHandler::builder(&temp_dir)
+            .with_config(Arc::new(config))
+            .build();
+
+        let mut ctx = context(GurtRequest::new(GurtMethod::GET, "/".to_string()));
+        assert!(handler.before(&mut ctx).await.unwrap().is_none());
+        let streamed = GurtResponse::ok().with_stream_body(GurtBodyStream::from_bytes("body"), Some(4));
+        let streamed = handler.after(&ctx, streamed).await.unwrap();
+
+        let rejected = handler.before(&mut ctx).await.unwrap().unwrap();
+        assert_eq!(rejected.status_code, 429);
+        handler.after(&ctx, rejected).await.unwrap();
+
+        assert_eq!(streamed.body_stream.unwrap().collect().await.unwrap(), b"body");
+        assert!(handler.before(&mut ctx).await.unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_releases_connection_slot() {
+        let temp_dir = env::temp_dir().join("gurty_request_handler_test_dropped");
+        let _ = fs::create_dir_all(&temp_dir);
+
+        let config = GurtConfig {
+            security: Some(crate::config::SecurityConfig {
+                deny_files: Vec::new(),
+                allowed_methods: vec!["GET".to_string()],
+                rate_limit_requests: 100,
+                rate_limit_connections: 2,
+            }),
+            ..Default::default()
+        };
+        let handler = RequestHandler::builder(&temp_dir)
+            .with_config(Arc::new(config))
+            .build();
+
+        // A request cancelled between `before` and `after` must not keep its slot
+        let mut cancelled = context(GurtRequest::new(GurtMethod::GET, "/".to_string()));
+        assert!(handler.before(&mut cancelled).await.unwrap().is_none());
+        drop(cancelled);
+
+        let mut ctx = context(GurtRequest::new(GurtMethod::GET, "/".to_string()));
+        assert!(handler.before(&mut ctx).await.unwrap().is_none());
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_file_request() {
+        let temp_dir = env::temp_dir().join("gurty_request_handler_test_conditional");
+        let _ = fs::create_dir_all(&temp_dir);
+        fs::write(temp_dir.join("style.css"), "body { color: red; }").unwrap();
+
+        let handler = RequestHandler::builder(&temp_dir).build();
+        let first = handler.handle_file_request_with_context("/style.css", context(GurtRequest::new(GurtMethod::GET, "/style.css".to_string())))
+            .await
+            .unwrap();
+        assert_eq!(first.status_code, 200);
+        let etag = first.header("etag").unwrap().clone();
+        let last_modified = first.header("last-modified").unwrap().clone();
+
+        let revalidated = GurtRequest::new(GurtMethod::GET, "/style.css".to_string())
+            .with_header("If-None-Match", &etag);
+        let response = handler.handle_file_request_with_context("/style.css", context(revalidated)).await.unwrap();
+        assert_eq!(response.status(), GurtStatusCode::NotModified);
+        assert!(response.body.is_empty() && response.body_stream.is_none());
+        assert_eq!(response.header("etag"), Some(&etag));
+
+        let by_date = GurtRequest::new(GurtMethod::GET, "/style.css".to_string())
+            .with_header("If-Modified-Since", &last_modified);
+        let response = handler.handle_file_request_with_context("/style.css", context(by_date)).await.unwrap();
+        assert_eq!(response.status_code, 304);
+
+        let stale = GurtRequest::new(GurtMethod::GET, "/style.css".to_string())
+            .with_header("If-None-Match", "\"stale\"");
+        let response = handler.handle_file_request_with_context("/style.css", context(stale)).await.unwrap();
+        assert_eq!(response.status_code, 200);
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_range_file_request() {
+        let temp_dir = env::temp_dir().join("gurty_request_handler_test_range");
+        let _ = fs::create_dir_all(&temp_dir);
+        fs::write(temp_dir.join("data.txt"), "0123456789").unwrap();
+
+        let handler = RequestHandler::builder(&temp_dir).build();
+        let ranged = |range: &str| context(GurtRequest::new(GurtMethod::GET, "/data.txt".to_string()).with_header("Range", range));
+
+        let response = handler.handle_file_request_with_context("/data.txt", ranged("bytes=2-5")).await.unwrap();
+        assert_eq!(response.status(), GurtStatusCode::PartialContent);
+        assert_eq!(response.header("content-range").unwrap(), "bytes 2-5/10");
+        assert_eq!(response.header("content-length").unwrap(), "4");
+        assert_eq!(response.body_stream.unwrap().collect().await.unwrap(), b"2345");
+
+        let response = handler.handle_file_request_with_context("/data.txt", ranged("bytes=0-0,-2")).await.unwrap();
+        assert_eq!(response.status_code, 206);
+        assert!(response.header("content-type").unwrap().starts_with("multipart/byteranges"));
+
+        let response = handler.handle_file_request_with_context("/data.txt", ranged("bytes=10-")).await.unwrap();
+        assert_eq!(response.status(), GurtStatusCode::RangeNotSatisfiable);
+        assert_eq!(response.header("content-range").unwrap(), "bytes */10");
+
+        let mut stale = ranged("bytes=2-5");
+        stale.request = stale.request.with_header("If-Range", "\"stale\"");
+        let response = handler.handle_file_request_with_context("/data.txt", stale).await.unwrap();
+        assert_eq!(response.status_code, 200);
+        assert_eq!(response.header("accept-ranges").unwrap(), "bytes");
+        assert_eq!(response.body, b"0123456789");
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_compresses_text_responses() {
+        let handler = create_test_handler();
+        let html = "<p>hello</p>".repeat(200);
+        let page = || GurtResponse::ok().with_header("Content-Type", "text/html").with_string_body(&html);
+        let ctx = |accept_encoding: Option<&str>| {
+            let mut request = GurtRequest::new(GurtMethod::GET, "/".to_string());
+            if let Some(accept_encoding) = accept_encoding {
+                request = request.with_header("Accept-Encoding", accept_encoding);
+            }
+            context(request)
+        };
+
+        let response = handler.after(&ctx(Some("gzip, br")), page()).await.unwrap();
+        assert_eq!(response.header("content-encoding").unwrap(), "br");
+        assert_eq!(response.header("vary").unwrap(), "accept-encoding");
+        let decoded = compression::decompress(ContentEncoding::Brotli, &response.body).await.unwrap();
+        assert_eq!(decoded, html.as_bytes());
+
+        let response = handler.after(&ctx(None), page()).await.unwrap();
+        assert!(response.header("content-encoding").is_none());
+        assert_eq!(response.header("vary").unwrap(), "accept-encoding");
+
+        let small = GurtResponse::ok().with_header("Content-Type", "text/html").with_string_body("<p>hi</p>");
+        let response = handler.after(&ctx(Some("gzip")), small).await.unwrap();
+        assert!(response.header("content-encoding").is_none());
+
+        let image = GurtResponse::ok().with_header("Content-Type", "image/png").with_body(vec![0u8; 4096]);
+        let response = handler.after(&ctx(Some("gzip")), image).await.unwrap();
+        assert!(response.header("content-encoding").is_none());
+    }
 }
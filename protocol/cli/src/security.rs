@@ -48,6 +48,27 @@ impl RateLimitData {
     }
 }
 
+fn release_connection(rate_limit_data: &Mutex<HashMap<IpAddr, RateLimitData>>, client_ip: IpAddr) {
+    let mut data = rate_limit_data.lock().unwrap();
+    if let Some(rate_data) = data.get_mut(&client_ip) {
+        rate_data.decrement_connections();
+        debug!("Connection unregistered for {}: {} concurrent connections remaining", 
+               client_ip, rate_data.connection_count());
+    }
+}
+
+/// Keeps a client's connection counted against its limit until dropped.
+pub struct ConnectionGuard {
+    rate_limit_data: Arc<Mutex<HashMap<IpAddr, RateLimitData>>>,
+    client_ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        release_connection(&self.rate_limit_data, self.client_ip);
+    }
+}
+
 pub struct SecurityMiddleware {
     config: Arc<GurtConfig>,
     rate_limit_data: Arc<Mutex<HashMap<IpAddr, RateLimitData>>>,
@@ -118,15 +139,19 @@ impl SecurityMiddleware {
 
     pub fn unregister_connection(&self, client_ip: IpAddr) {
         if self.config.security.is_some() {
-            let mut data = self.rate_limit_data.lock().unwrap();
-            if let Some(rate_data) = data.get_mut(&client_ip) {
-                rate_data.decrement_connections();
-                debug!("Connection unregistered for {}: {} concurrent connections remaining", 
-                       client_ip, rate_data.connection_count());
-            }
+            release_connection(&self.rate_limit_data, client_ip);
         }
     }
 
+    /// Takes over a connection registered with `register_connection`, unregistering it
+    /// when the returned guard is dropped.
+    pub fn guard_connection(&self, client_ip: IpAddr) -> Option<ConnectionGuard> {
+        self.config.security.as_ref().map(|_| ConnectionGuard {
+            rate_limit_data: self.rate_limit_data.clone(),
+            client_ip,
+        })
+    }
+
     pub fn create_method_not_allowed_response(&self) -> std::result::Result<GurtResponse, GurtError> {
         let response = GurtResponse::new(GurtStatusCode::MethodNotAllowed)
             .with_header("Content-Type", "text/html");
@@ -220,6 +245,12 @@ mod tests {
             let data = middleware.rate_limit_data.lock().unwrap();
             assert_eq!(data.get(&ip).unwrap().connection_count(), 0);
         }
+        
+        middleware.register_connection(ip);
+        let guard = middleware.guard_connection(ip).unwrap();
+        assert_eq!(middleware.rate_limit_data.lock().unwrap().get(&ip).unwrap().connection_count(), 1);
+        drop(guard);
+        assert_eq!(middleware.rate_limit_data.lock().unwrap().get(&ip).unwrap().connection_count(), 0);
     }
 
     #[test]
@@ -4,6 +4,7 @@ use crate::{
     request_handler::{RequestHandler, RequestHandlerBuilder},
 };
 use gurtlib::prelude::*;
+use gurtlib::Capabilities;
 use std::{path::PathBuf, sync::Arc};
 
 pub struct FileServerBuilder {
@@ -49,6 +50,18 @@ impl FileServerBuilder {
                         self.config.get_request_timeout(),
                         self.config.get_connection_timeout(),
                     )
+                    .with_handler_timeout(self.config.get_handler_timeout())
+                    .with_shutdown_timeout(self.config.get_shutdown_timeout())
+                    .with_max_connections(
+                        self.config.server.max_connections as usize,
+                        self.config.get_connection_limit_policy(),
+                    )
+                    .with_capabilities(Capabilities {
+                        compression: !self.config.get_compression_encodings().is_empty(),
+                        pipelining: true,
+                        streaming: true,
+                        direct_tls: true,
+                    })
                     .build()
             }
             None => {
@@ -71,6 +84,7 @@ impl FileServerBuilder {
         let request_handler = Arc::new(request_handler);
 
         let server = server
+            .with_middleware(request_handler.clone())
             .get("/", {
                 let handler = request_handler.clone();
                 move |ctx| {
@@ -228,6 +242,10 @@ pub struct GurtServerBuilder {
     handshake_timeout: Option<std::time::Duration>,
     request_timeout: Option<std::time::Duration>,
     connection_timeout: Option<std::time::Duration>,
+    handler_timeout: Option<std::time::Duration>,
+    shutdown_timeout: Option<std::time::Duration>,
+    max_connections: Option<(usize, ConnectionLimitPolicy)>,
+    capabilities: Option<Capabilities>,
 }
 
 impl GurtServerBuilder {
@@ -240,6 +258,10 @@ impl GurtServerBuilder {
             handshake_timeout: None,
             request_timeout: None,
             connection_timeout: None,
+            handler_timeout: None,
+            shutdown_timeout: None,
+            max_connections: None,
+            capabilities: None,
         }
     }
 
@@ -266,6 +288,26 @@ impl GurtServerBuilder {
         self
     }
 
+    pub fn with_handler_timeout(mut self, handler_timeout: std::time::Duration) -> Self {
+        self.handler_timeout = Some(handler_timeout);
+        self
+    }
+
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: std::time::Duration) -> Self {
+        self.shutdown_timeout = Some(shutdown_timeout);
+        self
+    }
+
+    pub fn with_max_connections(mut self, max_connections: usize, policy: ConnectionLimitPolicy) -> Self {
+        self.max_connections = Some((max_connections, policy));
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
     pub fn build(self) -> crate::Result<GurtServer> {
         match (self.cert_path, self.key_path) {
             (Some(cert), Some(key)) => {
@@ -283,6 +325,22 @@ impl GurtServerBuilder {
                     server = server.with_timeouts(handshake, request, connection);
                 }
                 
+                if let Some(handler) = self.handler_timeout {
+                    server = server.with_handler_timeout(handler);
+                }
+                
+                if let Some(shutdown) = self.shutdown_timeout {
+                    server = server.with_shutdown_timeout(shutdown);
+                }
+                
+                if let Some((max_connections, policy)) = self.max_connections {
+                    server = server.with_max_connections(max_connections, policy);
+                }
+                
+                if let Some(capabilities) = self.capabilities {
+                    server = server.with_capabilities(capabilities);
+                }
+                
                 Ok(server)
             }
             _ => {
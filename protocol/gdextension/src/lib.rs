@@ -1,8 +1,11 @@
 use godot::prelude::*;
 use gurtlib::prelude::*;
-use gurtlib::{GurtMethod, GurtClientConfig, GurtRequest, GurtResponseHead};
+use gurtlib::{GurtMethod, GurtClientConfig, GurtRequest, GurtResponseHead, CacheConfig, CookieJar};
+use gurtlib::multipart::{Multipart, Part};
+use gurtlib::range::parse_content_range;
+use gurtlib::{ChannelMessage, ChannelSender, Event};
 use std::cell::RefCell;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io::Write;
 use std::sync::Arc;
 use std::sync::Mutex;
@@ -23,7 +26,13 @@ struct GurtProtocolClient {
     runtime: Arc<RefCell<Option<Runtime>>>,
     ca_certificates: Arc<RefCell<Vec<String>>>,
     cancel_flags: Arc<Mutex<HashMap<String, bool>>>,
-    event_queue: Arc<Mutex<Vec<DownloadEvent>>>,
+    event_queue: Arc<Mutex<Vec<ClientEvent>>>,
+    // Outlives the client so cookies survive create_client being called again
+    cookie_jar: Arc<CookieJar>,
+    channels: Arc<Mutex<HashMap<String, ChannelSender>>>,
+    event_streams: Arc<Mutex<HashMap<String, tokio::task::AbortHandle>>>,
+    // The ETag or Last-Modified of each downloaded file, sent as If-Range when resuming
+    download_validators: Arc<Mutex<HashMap<String, String>>>,
 }
 
 #[derive(GodotClass)]
@@ -99,15 +108,20 @@ struct GurtProtocolServer {
     base: Base<RefCounted>,
 }
 
-struct DLState { file: Option<std::fs::File>, total_bytes: i64, downloaded: i64 }
+struct DLState { file: Option<std::fs::File>, total_bytes: i64, downloaded: i64, complete: bool, restart: bool }
 
 
 #[derive(Clone)]
-enum DownloadEvent {
+enum ClientEvent {
     Started(String, i64),
     Progress(String, i64, i64),
     Completed(String, String),
     Failed(String, String),
+    ChannelOpened(String),
+    ChannelMessage(String, ChannelMessage),
+    ChannelClosed(String, String),
+    EventReceived(String, Event),
+    EventsClosed(String, String),
 }
 
 #[godot_api]
@@ -120,6 +134,10 @@ impl GurtProtocolClient {
             ca_certificates: Arc::new(RefCell::new(Vec::new())),
             cancel_flags: Arc::new(Mutex::new(HashMap::new())),
             event_queue: Arc::new(Mutex::new(Vec::new())),
+            cookie_jar: Arc::new(CookieJar::new()),
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            event_streams: Arc::new(Mutex::new(HashMap::new())),
+            download_validators: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -138,6 +156,25 @@ impl GurtProtocolClient {
     #[signal]
     fn download_failed(download_id: GString, message: GString);
 
+    #[signal]
+    fn channel_opened(channel_id: GString);
+
+    /// `message` is a String for text messages and a PackedByteArray for binary ones.
+    #[signal]
+    fn channel_message(channel_id: GString, message: Variant);
+
+    /// `reason` is empty when the channel closed normally.
+    #[signal]
+    fn channel_closed(channel_id: GString, reason: GString);
+
+    /// `event` is the event type, `message` unless the server named one.
+    #[signal]
+    fn event_received(stream_id: GString, event: GString, data: GString, event_id: GString);
+
+    /// `reason` is empty when the server ended the stream or it was closed locally.
+    #[signal]
+    fn events_closed(stream_id: GString, reason: GString);
+
     #[func]
     fn create_client(&mut self, timeout_seconds: i32) -> bool {
         let runtime = match Runtime::new() {
@@ -153,6 +190,8 @@ impl GurtProtocolClient {
 
         // Add custom CA certificates
         config.custom_ca_certificates = self.ca_certificates.borrow().clone();
+        config.cache = Some(CacheConfig::default());
+        config.cookie_jar = Some(self.cookie_jar.clone());
 
         let client = GurtClient::with_config(config);
 
@@ -178,6 +217,8 @@ impl GurtProtocolClient {
         config.dns_server_port = dns_port as u16;
 
         config.custom_ca_certificates = self.ca_certificates.borrow().clone();
+        config.cache = Some(CacheConfig::default());
+        config.cookie_jar = Some(self.cookie_jar.clone());
 
         let client = GurtClient::with_config(config);
 
@@ -253,7 +294,7 @@ impl GurtProtocolClient {
             }
         };
 
-        let body = options.get("body").unwrap_or("".to_variant()).to::<String>();
+        let body = options.get("body").unwrap_or("".to_variant());
         let headers_dict = options.get("headers").unwrap_or(Dictionary::new().to_variant()).to::<Dictionary>();
 
         let mut request = GurtRequest::new(method, path.to_string())
@@ -267,8 +308,45 @@ impl GurtProtocolClient {
             }
         }
 
-        if !body.is_empty() {
-            request = request.with_string_body(&body);
+        // A `form` dictionary is sent url-encoded; a `multipart` array of field dictionaries
+        // ({name, value} or {name, filename, data, content_type}) is sent as multipart/form-data
+        if let Some(form) = options.get("form") {
+            let pairs: Vec<(String, String)> = form.to::<Dictionary>().iter_shared()
+                .map(|(key, value)| (key.to::<String>(), value.to::<String>()))
+                .collect();
+            request = match request.with_form_body(&pairs) {
+                Ok(request) => request,
+                Err(e) => {
+                    godot_print!("Invalid form: {}", e);
+                    return None;
+                }
+            };
+        } else if let Some(fields) = options.get("multipart") {
+            let mut form = Multipart::new();
+            for field in fields.to::<VariantArray>().iter_shared() {
+                let field = field.to::<Dictionary>();
+                let name = field.get("name").unwrap_or("".to_variant()).to::<String>();
+                let part = match field.get("filename") {
+                    Some(filename) => {
+                        let data = field.get("data").unwrap_or(PackedByteArray::new().to_variant()).to::<PackedByteArray>();
+                        let part = Part::file(name, filename.to::<String>(), data.to_vec());
+                        match field.get("content_type") {
+                            Some(content_type) => part.with_content_type(content_type.to::<String>()),
+                            None => part,
+                        }
+                    }
+                    None => Part::text(name, field.get("value").unwrap_or("".to_variant()).to::<String>()),
+                };
+                form = form.part(part);
+            }
+            request = request.with_multipart_body(&form);
+        } else if body.get_type() == VariantType::PACKED_BYTE_ARRAY {
+            request = request.with_body(body.to::<PackedByteArray>().to_vec());
+        } else {
+            let body = body.to::<String>();
+            if !body.is_empty() {
+                request = request.with_string_body(&body);
+            }
         }
 
         let response = match runtime.block_on(async {
@@ -286,6 +364,18 @@ impl GurtProtocolClient {
 
     #[func]
     fn start_download(&mut self, download_id: GString, url: GString, save_path: GString) -> bool {
+        self.spawn_download(download_id, url, save_path, false)
+    }
+
+    /// Continues a download into an existing partial file by requesting only the missing
+    /// bytes. Starts over if the file wasn't downloaded by this client, the resource has
+    /// changed, or the server ignores the range.
+    #[func]
+    fn resume_download(&mut self, download_id: GString, url: GString, save_path: GString) -> bool {
+        self.spawn_download(download_id, url, save_path, true)
+    }
+
+    fn spawn_download(&mut self, download_id: GString, url: GString, save_path: GString, resume: bool) -> bool {
         let runtime_handle = {
             let runtime_binding = self.runtime.borrow();
             match runtime_binding.as_ref() {
@@ -307,99 +397,310 @@ impl GurtProtocolClient {
         let download_id_string = download_id.to_string();
         let cancel_flags = self.cancel_flags.clone();
         let event_queue = self.event_queue.clone();
+        let download_validators = self.download_validators.clone();
+        // Without the validator of the earlier response there is no way to tell whether
+        // the partial file still matches the resource, so such downloads start over
+        let validator = if resume { download_validators.lock().ok().and_then(|map| map.get(&save_path_str).cloned()) } else { None };
+        let mut offset = match validator { Some(_) => std::fs::metadata(&save_path_str).map(|m| m.len()).unwrap_or(0), None => 0 };
 
         runtime_handle.spawn(async move {
             let event_queue_main = event_queue.clone();
             let parsed_url = match url::Url::parse(&url_str) { Ok(u) => u, Err(e) => {
-                if let Ok(mut q) = event_queue.lock() { q.push(DownloadEvent::Failed(download_id_string.clone(), format!("Invalid URL: {}", e))); }
+                if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::Failed(download_id_string.clone(), format!("Invalid URL: {}", e))); }
                 return;
             }};
             let host = match parsed_url.host_str() { Some(h) => h.to_string(), None => {
-                if let Ok(mut q) = event_queue.lock() { q.push(DownloadEvent::Failed(download_id_string.clone(), "URL must have a host".to_string())); }
+                if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::Failed(download_id_string.clone(), "URL must have a host".to_string())); }
                 return;
             }};
             let port = parsed_url.port().unwrap_or(4878);
             let path_with_query = if parsed_url.path().is_empty() { "/".to_string() } else { parsed_url.path().to_string() };
             let path = match parsed_url.query() { Some(query) => format!("{}?{}", path_with_query, query), None => path_with_query };
 
-        let state = Arc::new(Mutex::new(DLState { file: None, total_bytes: -1, downloaded: 0 }));
-
-            let request = GurtRequest::new(GurtMethod::GET, path).with_header("User-Agent", "GURT-Client/1.0.0");
+            loop {
+                let state = Arc::new(Mutex::new(DLState { file: None, total_bytes: -1, downloaded: 0, complete: false, restart: false }));
 
-            let state_head = state.clone();
-            let event_queue_head = event_queue.clone();
-            let id_for_head = download_id_string.clone();
-            let sp_for_head = save_path_str.clone();
-            let on_head = move |head: &GurtResponseHead| {
-                if head.status_code < 200 || head.status_code >= 300 {
-                    if let Ok(mut q) = event_queue_head.lock() { q.push(DownloadEvent::Failed(id_for_head.clone(), format!("{} {}", head.status_code, head.status_message))); }
-                    return;
+                let mut request = GurtRequest::new(GurtMethod::GET, path.clone()).with_header("User-Agent", "GURT-Client/1.0.0");
+                if offset > 0 {
+                    request = request.with_header("Range", format!("bytes={}-", offset));
+                    if let Some(validator) = &validator { request = request.with_header("If-Range", validator.as_str()); }
                 }
-                let mut total: i64 = -1;
-                if let Some(cl) = head.headers.get("content-length").or_else(|| head.headers.get("Content-Length")) {
-                    if let Ok(v) = cl.parse::<i64>() { total = v; }
+
+                let state_head = state.clone();
+                let event_queue_head = event_queue.clone();
+                let id_for_head = download_id_string.clone();
+                let sp_for_head = save_path_str.clone();
+                let validators_head = download_validators.clone();
+                let on_head = move |head: &GurtResponseHead| {
+                    let content_range = head.headers.get("content-range").map(|value| value.as_str());
+                    if offset > 0 && head.status_code == 416 {
+                        // The partial file already holds every byte of the resource
+                        let complete = content_range.and_then(|value| value.trim().strip_prefix("bytes */")).and_then(|total| total.trim().parse::<u64>().ok()) == Some(offset);
+                        if let Ok(mut st) = state_head.lock() {
+                            if complete { st.complete = true; st.total_bytes = offset as i64; st.downloaded = offset as i64; } else { st.restart = true; }
+                        }
+                        if complete { if let Ok(mut q) = event_queue_head.lock() { q.push(ClientEvent::Started(id_for_head.clone(), offset as i64)); } }
+                        return;
+                    }
+                    if head.status_code < 200 || head.status_code >= 300 {
+                        if let Ok(mut q) = event_queue_head.lock() { q.push(ClientEvent::Failed(id_for_head.clone(), format!("{} {}", head.status_code, head.status_message))); }
+                        return;
+                    }
+                    let mut total: i64 = -1;
+                    if let Some(cl) = head.headers.get("content-length").or_else(|| head.headers.get("Content-Length")) {
+                        if let Ok(v) = cl.parse::<i64>() { total = v; }
+                    }
+                    let resumed = offset > 0 && head.status_code == 206;
+                    if resumed {
+                        // Appending is only safe if the server sent exactly the missing bytes
+                        match content_range.and_then(parse_content_range) {
+                            Some((range, length)) if range.start == offset => {
+                                if let Some(length) = length { total = length as i64; } else if total >= 0 { total += offset as i64; }
+                            }
+                            _ => {
+                                if let Ok(mut st) = state_head.lock() { st.restart = true; }
+                                return;
+                            }
+                        }
+                    } else if let Ok(mut map) = validators_head.lock() {
+                        let etag = head.headers.get("etag").filter(|etag| !etag.starts_with("W/"));
+                        match etag.or_else(|| head.headers.get("last-modified")) {
+                            Some(validator) => { map.insert(sp_for_head.clone(), validator.clone()); }
+                            None => { map.remove(&sp_for_head); }
+                        }
+                    }
+                    let opened = if resumed { OpenOptions::new().append(true).open(&sp_for_head) } else { File::create(&sp_for_head) };
+                    match opened {
+                        Ok(f) => {
+                            if let Ok(mut st) = state_head.lock() { st.file = Some(f); st.total_bytes = total; st.downloaded = if resumed { offset as i64 } else { 0 }; }
+                        }
+                        Err(e) => { if let Ok(mut q) = event_queue_head.lock() { q.push(ClientEvent::Failed(id_for_head.clone(), format!("File error: {}", e))); } }
+                    }
+                    if let Ok(mut q) = event_queue_head.lock() { q.push(ClientEvent::Started(id_for_head.clone(), total)); }
+                };
+
+                let state_chunk = state.clone();
+                let event_queue_chunk = event_queue.clone();
+                let id_for_chunk = download_id_string.clone();
+                let cancel_flags_chunk = cancel_flags.clone();
+                let on_chunk = move |chunk: &[u8]| -> bool {
+                    if let Ok(map) = cancel_flags_chunk.lock() {
+                        if map.get(&id_for_chunk).copied().unwrap_or(false) { return false; }
+                    }
+                    let mut down = 0i64; let mut total = -1i64; let mut write_result: std::io::Result<()> = Ok(());
+                    if let Ok(mut st) = state_chunk.lock() {
+                        if st.restart { return false; }
+                        if st.complete { return true; }
+                        if let Some(f) = st.file.as_mut() { write_result = f.write_all(chunk); }
+                        st.downloaded += chunk.len() as i64; down = st.downloaded; total = st.total_bytes;
+                    }
+                    if let Err(e) = write_result { if let Ok(mut q) = event_queue_chunk.lock() { q.push(ClientEvent::Failed(id_for_chunk.clone(), format!("Write error: {}", e))); } return false; }
+                    if let Ok(mut q) = event_queue_chunk.lock() { q.push(ClientEvent::Progress(id_for_chunk.clone(), down, total)); }
+                    true
+                };
+
+                let result = client_instance.stream_request(host.as_str(), port, request, on_head, on_chunk).await;
+                if offset > 0 && state.lock().map(|st| st.restart).unwrap_or(false) {
+                    // The partial file doesn't line up with what the server has, so fetch it all again
+                    offset = 0;
+                    continue;
                 }
-                match File::create(&sp_for_head) {
-                    Ok(f) => {
-                        if let Ok(mut st) = state_head.lock() { st.file = Some(f); st.total_bytes = total; }
+                match result {
+                    Ok(()) => {
+                        if let Ok(mut st) = state.lock() { if let Some(f) = st.file.as_mut() { let _ = f.flush(); } }
+                        if let Ok(mut q) = event_queue_main.lock() { q.push(ClientEvent::Completed(download_id_string.clone(), save_path_str.clone())); }
+                    }
+                    Err(e) => {
+                        if let Ok(mut q) = event_queue_main.lock() { q.push(ClientEvent::Failed(download_id_string.clone(), format!("{}", e))); }
                     }
-                    Err(e) => { if let Ok(mut q) = event_queue_head.lock() { q.push(DownloadEvent::Failed(id_for_head.clone(), format!("File error: {}", e))); } }
                 }
-                if let Ok(mut q) = event_queue_head.lock() { q.push(DownloadEvent::Started(id_for_head.clone(), total)); }
-            };
+                break;
+            }
+        });
+        true
+    }
 
-            let state_chunk = state.clone();
-            let event_queue_chunk = event_queue.clone();
-            let id_for_chunk = download_id_string.clone();
-            let on_chunk = move |chunk: &[u8]| -> bool {
-                if let Ok(map) = cancel_flags.lock() {
-                    if map.get(&id_for_chunk).copied().unwrap_or(false) { return false; }
-                }
-                let mut down = 0i64; let mut total = -1i64; let mut write_result: std::io::Result<()> = Ok(());
-                if let Ok(mut st) = state_chunk.lock() {
-                    if let Some(f) = st.file.as_mut() { write_result = f.write_all(chunk); }
-                    st.downloaded += chunk.len() as i64; down = st.downloaded; total = st.total_bytes;
+    #[func]
+    fn cancel_download(&mut self, download_id: GString) {
+        if let Ok(mut map) = self.cancel_flags.lock() { map.insert(download_id.to_string(), true); }
+    }
+
+    /// Opens a long-lived channel to `url`. Its events are emitted from `poll_events`.
+    #[func]
+    fn open_channel(&mut self, channel_id: GString, url: GString) -> bool {
+        let runtime_handle = {
+            let runtime_binding = self.runtime.borrow();
+            match runtime_binding.as_ref() {
+                Some(rt) => rt.handle().clone(),
+                None => { godot_print!("No runtime available"); return false; }
+            }
+        };
+
+        let client_instance = {
+            let client_binding = self.client.borrow();
+            match client_binding.as_ref() {
+                Some(c) => c.clone(),
+                None => { godot_print!("No client available"); return false; }
+            }
+        };
+
+        let url_str = url.to_string();
+        let id = channel_id.to_string();
+        let channels = self.channels.clone();
+        let event_queue = self.event_queue.clone();
+
+        runtime_handle.spawn(async move {
+            let channel = match client_instance.connect_channel(&url_str).await {
+                Ok(channel) => channel,
+                Err(e) => {
+                    if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::ChannelClosed(id, e.to_string())); }
+                    return;
                 }
-                if let Err(e) = write_result { if let Ok(mut q) = event_queue_chunk.lock() { q.push(DownloadEvent::Failed(id_for_chunk.clone(), format!("Write error: {}", e))); } return false; }
-                if let Ok(mut q) = event_queue_chunk.lock() { q.push(DownloadEvent::Progress(id_for_chunk.clone(), down, total)); }
-                true
             };
 
-            let result = client_instance.stream_request(host.as_str(), port, request, on_head, on_chunk).await;
-            match result {
-                Ok(()) => {
-                    if let Ok(mut st) = state.lock() { if let Some(f) = st.file.as_mut() { let _ = f.flush(); } }
-                    if let Ok(mut q) = event_queue_main.lock() { q.push(DownloadEvent::Completed(download_id_string.clone(), save_path_str.clone())); }
-                }
-                Err(e) => {
-                    if let Ok(mut q) = event_queue_main.lock() { q.push(DownloadEvent::Failed(download_id_string.clone(), format!("{}", e))); }
+            let (sender, mut receiver) = channel.split();
+            if let Ok(mut map) = channels.lock() { map.insert(id.clone(), sender); }
+            if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::ChannelOpened(id.clone())); }
+
+            let reason = loop {
+                match receiver.recv().await {
+                    Ok(Some(message)) => {
+                        if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::ChannelMessage(id.clone(), message)); }
+                    }
+                    Ok(None) => break String::new(),
+                    Err(e) => break e.to_string(),
                 }
+            };
+
+            if let Ok(mut map) = channels.lock() { map.remove(&id); }
+            if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::ChannelClosed(id, reason)); }
+        });
+        true
+    }
+
+    /// Sends a String as a text message or a PackedByteArray as a binary one.
+    #[func]
+    fn channel_send(&self, channel_id: GString, message: Variant) -> bool {
+        let Some(sender) = self.channel_sender(&channel_id) else { return false; };
+        let message = if message.get_type() == VariantType::PACKED_BYTE_ARRAY {
+            ChannelMessage::Binary(message.to::<PackedByteArray>().to_vec())
+        } else {
+            ChannelMessage::Text(message.to::<String>())
+        };
+
+        let runtime_binding = self.runtime.borrow();
+        let Some(runtime) = runtime_binding.as_ref() else { return false; };
+        match runtime.block_on(sender.send(message)) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_print!("Failed to send on channel {}: {}", channel_id, e);
+                false
+            }
+        }
+    }
+
+    #[func]
+    fn close_channel(&self, channel_id: GString) {
+        let Some(sender) = self.channel_sender(&channel_id) else { return; };
+        if let Some(runtime) = self.runtime.borrow().as_ref() {
+            let _ = runtime.block_on(sender.close());
+        }
+    }
+
+    fn channel_sender(&self, channel_id: &GString) -> Option<ChannelSender> {
+        self.channels.lock().ok()?.get(&channel_id.to_string()).cloned()
+    }
+
+    /// Subscribes to the event stream at `url`. Its events are emitted from `poll_events`.
+    #[func]
+    fn open_events(&mut self, stream_id: GString, url: GString) -> bool {
+        let runtime_handle = {
+            let runtime_binding = self.runtime.borrow();
+            match runtime_binding.as_ref() {
+                Some(rt) => rt.handle().clone(),
+                None => { godot_print!("No runtime available"); return false; }
+            }
+        };
+
+        let client_instance = {
+            let client_binding = self.client.borrow();
+            match client_binding.as_ref() {
+                Some(c) => c.clone(),
+                None => { godot_print!("No client available"); return false; }
             }
+        };
+
+        let url_str = url.to_string();
+        let id = stream_id.to_string();
+        let event_streams = self.event_streams.clone();
+        let event_queue = self.event_queue.clone();
+
+        let task = runtime_handle.spawn(async move {
+            let reason = match client_instance.events(&url_str).await {
+                Ok(mut events) => loop {
+                    match events.next().await {
+                        Ok(Some(event)) => {
+                            if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::EventReceived(id.clone(), event)); }
+                        }
+                        Ok(None) => break String::new(),
+                        Err(e) => break e.to_string(),
+                    }
+                },
+                Err(e) => e.to_string(),
+            };
+
+            if let Ok(mut map) = event_streams.lock() { map.remove(&id); }
+            if let Ok(mut q) = event_queue.lock() { q.push(ClientEvent::EventsClosed(id, reason)); }
         });
+
+        if let Ok(mut map) = self.event_streams.lock() {
+            if let Some(previous) = map.insert(stream_id.to_string(), task.abort_handle()) { previous.abort(); }
+        }
         true
     }
 
     #[func]
-    fn cancel_download(&mut self, download_id: GString) {
-        if let Ok(mut map) = self.cancel_flags.lock() { map.insert(download_id.to_string(), true); }
+    fn close_events(&self, stream_id: GString) {
+        let Some(task) = self.event_streams.lock().ok().and_then(|mut map| map.remove(&stream_id.to_string())) else { return; };
+        task.abort();
+        if let Ok(mut q) = self.event_queue.lock() { q.push(ClientEvent::EventsClosed(stream_id.to_string(), String::new())); }
     }
 
     #[func]
     fn poll_events(&mut self) {
-        let mut drained: Vec<DownloadEvent> = Vec::new();
+        let mut drained: Vec<ClientEvent> = Vec::new();
         if let Ok(mut q) = self.event_queue.lock() { drained.append(&mut *q); }
         for ev in drained.into_iter() {
             match ev {
-                DownloadEvent::Started(id, total) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), (total as i64).to_variant()]; owner.emit_signal("download_started".into(), &args); }
-                DownloadEvent::Progress(id, down, total) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), (down as i64).to_variant(), (total as i64).to_variant()]; owner.emit_signal("download_progress".into(), &args); }
-                DownloadEvent::Completed(id, path) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(path).to_variant()]; owner.emit_signal("download_completed".into(), &args); }
-                DownloadEvent::Failed(id, msg) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(msg).to_variant()]; owner.emit_signal("download_failed".into(), &args); }
+                ClientEvent::Started(id, total) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), (total as i64).to_variant()]; owner.emit_signal("download_started".into(), &args); }
+                ClientEvent::Progress(id, down, total) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), (down as i64).to_variant(), (total as i64).to_variant()]; owner.emit_signal("download_progress".into(), &args); }
+                ClientEvent::Completed(id, path) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(path).to_variant()]; owner.emit_signal("download_completed".into(), &args); }
+                ClientEvent::Failed(id, msg) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(msg).to_variant()]; owner.emit_signal("download_failed".into(), &args); }
+                ClientEvent::ChannelOpened(id) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant()]; owner.emit_signal("channel_opened".into(), &args); }
+                ClientEvent::ChannelMessage(id, message) => {
+                    let message = match message {
+                        ChannelMessage::Text(text) => GString::from(text).to_variant(),
+                        ChannelMessage::Binary(data) => PackedByteArray::from(data.as_slice()).to_variant(),
+                    };
+                    let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), message]; owner.emit_signal("channel_message".into(), &args);
+                }
+                ClientEvent::ChannelClosed(id, reason) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(reason).to_variant()]; owner.emit_signal("channel_closed".into(), &args); }
+                ClientEvent::EventReceived(id, event) => {
+                    let mut owner = self.base.to_gd();
+                    let args = [GString::from(id).to_variant(), GString::from(event.name()).to_variant(), GString::from(event.data).to_variant(), GString::from(event.id.unwrap_or_default()).to_variant()];
+                    owner.emit_signal("event_received".into(), &args);
+                }
+                ClientEvent::EventsClosed(id, reason) => { let mut owner = self.base.to_gd(); let args = [GString::from(id).to_variant(), GString::from(reason).to_variant()]; owner.emit_signal("events_closed".into(), &args); }
             }
         }
     }
 
     #[func]
     fn disconnect(&mut self) {
+        if let Ok(mut map) = self.channels.lock() { map.clear(); }
+        if let Ok(mut map) = self.event_streams.lock() {
+            for (_, task) in map.drain() { task.abort(); }
+        }
         *self.client.borrow_mut() = None;
         *self.runtime.borrow_mut() = None;
     }
@@ -434,6 +735,37 @@ impl GurtProtocolClient {
         self.ca_certificates.borrow().len() as i32
     }
 
+    #[func]
+    fn save_cookies(&self, path: GString) -> bool {
+        match self.cookie_jar.save(path.to_string()) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_print!("Failed to save cookies: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Replaces the cookie jar with one saved by save_cookies. Takes effect for clients created afterwards.
+    #[func]
+    fn load_cookies(&mut self, path: GString) -> bool {
+        match CookieJar::load(path.to_string()) {
+            Ok(jar) => {
+                self.cookie_jar = Arc::new(jar);
+                true
+            }
+            Err(e) => {
+                godot_print!("Failed to load cookies: {}", e);
+                false
+            }
+        }
+    }
+
+    #[func]
+    fn clear_cookies(&self) {
+        self.cookie_jar.clear();
+    }
+
     fn convert_response(&self, response: GurtResponse) -> Gd<GurtGDResponse> {
         let mut gd_response = GurtGDResponse::new_gd();
 
@@ -443,7 +775,7 @@ impl GurtProtocolClient {
 
         let mut headers = Dictionary::new();
         for (key, value) in &response.headers {
-            headers.set(key.clone(), value.clone());
+            headers.set(key.to_lowercase(), value.clone());
         }
         gd_response.bind_mut().headers = headers;
 
@@ -0,0 +1,230 @@
+use crate::{GurtError, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::sync::mpsc;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, ready};
+
+/// Size of the reads performed when pulling data from a streamed body.
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+pub(crate) enum BodySource {
+    Reader(Pin<Box<dyn AsyncRead + Send>>),
+    Channel(mpsc::Receiver<Vec<u8>>),
+    Concat(VecDeque<BodySource>),
+}
+
+impl BodySource {
+    /// Returns the next piece of the body, or `None` once the source is exhausted.
+    pub(crate) async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        match self {
+            BodySource::Reader(reader) => {
+                let mut chunk = vec![0u8; STREAM_CHUNK_SIZE];
+                let bytes_read = reader.read(&mut chunk).await?;
+                if bytes_read == 0 {
+                    return Ok(None);
+                }
+                chunk.truncate(bytes_read);
+                Ok(Some(chunk))
+            }
+            BodySource::Channel(receiver) => Ok(receiver.recv().await),
+            BodySource::Concat(parts) => {
+                while let Some(part) = parts.front_mut() {
+                    if let Some(chunk) = Box::pin(part.next_chunk()).await? {
+                        return Ok(Some(chunk));
+                    }
+                    parts.pop_front();
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Reads into `buf`, keeping the part of a channel chunk that does not fit in
+    /// `overflow`. Leaving `buf` unfilled with `overflow` empty signals the end.
+    fn poll_read_into(&mut self, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>, overflow: &mut Vec<u8>) -> Poll<std::io::Result<()>> {
+        match self {
+            BodySource::Reader(reader) => reader.as_mut().poll_read(cx, buf),
+            BodySource::Channel(receiver) => loop {
+                match ready!(receiver.poll_recv(cx)) {
+                    Some(chunk) if chunk.is_empty() => continue,
+                    Some(chunk) => {
+                        let taken = chunk.len().min(buf.remaining());
+                        buf.put_slice(&chunk[..taken]);
+                        overflow.extend_from_slice(&chunk[taken..]);
+                        return Poll::Ready(Ok(()));
+                    }
+                    None => return Poll::Ready(Ok(())),
+                }
+            },
+            BodySource::Concat(parts) => {
+                while let Some(part) = parts.front_mut() {
+                    let filled = buf.filled().len();
+                    ready!(part.poll_read_into(cx, buf, overflow))?;
+                    if buf.filled().len() > filled || !overflow.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                    parts.pop_front();
+                }
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+/// Reads a [`GurtBodyStream`] through [`AsyncRead`], e.g. to pass it through an encoder.
+pub struct BodyReader {
+    source: BodySource,
+    overflow: Vec<u8>,
+    position: usize,
+}
+
+impl AsyncRead for BodyReader {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.position < this.overflow.len() {
+            let taken = (this.overflow.len() - this.position).min(buf.remaining());
+            buf.put_slice(&this.overflow[this.position..this.position + taken]);
+            this.position += taken;
+            if this.position == this.overflow.len() {
+                this.overflow.clear();
+                this.position = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+
+        this.source.poll_read_into(cx, buf, &mut this.overflow)
+    }
+}
+
+/// A response body produced incrementally instead of held in memory.
+///
+/// The underlying source can only be consumed once; clones share it, so whichever
+/// copy is written out first takes the data.
+#[derive(Clone)]
+pub struct GurtBodyStream {
+    source: Arc<Mutex<Option<BodySource>>>,
+}
+
+impl GurtBodyStream {
+    pub fn from_reader<R: AsyncRead + Send + 'static>(reader: R) -> Self {
+        Self::from_source(BodySource::Reader(Box::pin(reader)))
+    }
+
+    /// Creates a body fed by the returned sender. The body ends when the sender is dropped.
+    pub fn channel(capacity: usize) -> (mpsc::Sender<Vec<u8>>, Self) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        (sender, Self::from_source(BodySource::Channel(receiver)))
+    }
+
+    pub fn from_bytes<B: Into<Vec<u8>>>(bytes: B) -> Self {
+        Self::from_reader(std::io::Cursor::new(bytes.into()))
+    }
+
+    /// Joins several bodies into one that reads each in turn. Parts that were
+    /// already consumed contribute nothing.
+    pub fn concat<I: IntoIterator<Item = GurtBodyStream>>(parts: I) -> Self {
+        let sources = parts.into_iter().filter_map(|part| part.take()).collect();
+        Self::from_source(BodySource::Concat(sources))
+    }
+
+    fn from_source(source: BodySource) -> Self {
+        Self {
+            source: Arc::new(Mutex::new(Some(source))),
+        }
+    }
+
+    pub(crate) fn take(&self) -> Option<BodySource> {
+        self.source.lock().ok().and_then(|mut source| source.take())
+    }
+
+    /// Takes the stream's data as an [`AsyncRead`].
+    pub fn reader(&self) -> Result<BodyReader> {
+        let source = self.take()
+            .ok_or_else(|| GurtError::Protocol("Body stream already consumed".to_string()))?;
+        Ok(BodyReader { source, overflow: Vec::new(), position: 0 })
+    }
+
+    pub fn is_consumed(&self) -> bool {
+        self.source.lock().map(|source| source.is_none()).unwrap_or(true)
+    }
+
+    /// Reads the remainder of the stream into memory.
+    pub async fn collect(&self) -> Result<Vec<u8>> {
+        let mut source = self.take()
+            .ok_or_else(|| GurtError::Protocol("Body stream already consumed".to_string()))?;
+
+        let mut body = Vec::new();
+        while let Some(chunk) = source.next_chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+}
+
+impl fmt::Debug for GurtBodyStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GurtBodyStream")
+            .field("consumed", &self.is_consumed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reader_stream_collect() {
+        let data = vec![7u8; STREAM_CHUNK_SIZE + 10];
+        let stream = GurtBodyStream::from_reader(std::io::Cursor::new(data.clone()));
+        let shared = stream.clone();
+
+        assert_eq!(stream.collect().await.unwrap(), data);
+        assert!(shared.is_consumed());
+        assert!(shared.collect().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_stream() {
+        let (sender, stream) = GurtBodyStream::channel(4);
+
+        tokio::spawn(async move {
+            sender.send(b"hello ".to_vec()).await.unwrap();
+            sender.send(b"world".to_vec()).await.unwrap();
+        });
+
+        assert_eq!(stream.collect().await.unwrap(), b"hello world");
+    }
+
+    #[tokio::test]
+    async fn test_concat_stream_reader() {
+        let (sender, channel) = GurtBodyStream::channel(4);
+        tokio::spawn(async move {
+            sender.send(b"channel ".to_vec()).await.unwrap();
+        });
+
+        let stream = GurtBodyStream::concat([
+            GurtBodyStream::from_bytes("bytes "),
+            channel,
+            GurtBodyStream::from_bytes("end"),
+        ]);
+
+        let mut reader = stream.reader().unwrap();
+        let mut small = [0u8; 3];
+        let mut body = Vec::new();
+        loop {
+            let read = reader.read(&mut small).await.unwrap();
+            if read == 0 {
+                break;
+            }
+            body.extend_from_slice(&small[..read]);
+        }
+
+        assert_eq!(body, b"bytes channel end");
+        assert!(stream.reader().is_err());
+    }
+}
@@ -0,0 +1,527 @@
+use crate::{
+    GurtRequest, GurtResponse,
+    conditional::Validators,
+    message::GurtMethod,
+};
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::debug;
+
+/// Settings for the optional response cache in `GurtClient`.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    /// Upper bound on the total size of cached bodies held in memory.
+    pub max_bytes: usize,
+    pub max_entries: usize,
+    /// When set, cached responses are also written here and survive restarts. The
+    /// directory is kept within `max_bytes` and `max_entries` like the memory cache.
+    pub disk_directory: Option<PathBuf>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: 32 * 1024 * 1024,
+            max_entries: 1024,
+            disk_directory: None,
+        }
+    }
+}
+
+/// The `Cache-Control` directives the cache acts on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    pub max_age: Option<Duration>,
+    pub no_store: bool,
+    pub no_cache: bool,
+    /// Allows storing a response to a request that carried credentials.
+    pub public: bool,
+}
+
+impl CacheControl {
+    pub fn parse(value: &str) -> Self {
+        let mut control = Self::default();
+
+        for directive in value.split(',') {
+            let (name, argument) = match directive.split_once('=') {
+                Some((name, argument)) => (name.trim(), Some(argument.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "max-age" => control.max_age = argument.and_then(|seconds| seconds.parse().ok()).map(Duration::from_secs),
+                "no-store" => control.no_store = true,
+                "no-cache" => control.no_cache = true,
+                "public" => control.public = true,
+                _ => {}
+            }
+        }
+
+        control
+    }
+
+    fn of_request(request: &GurtRequest) -> Self {
+        request.header("cache-control").map(|value| Self::parse(value)).unwrap_or_default()
+    }
+
+    fn of_response(response: &GurtResponse) -> Self {
+        response.header("cache-control").map(|value| Self::parse(value)).unwrap_or_default()
+    }
+}
+
+pub(crate) enum CacheLookup {
+    /// A stored response that can be used without contacting the server.
+    Fresh(GurtResponse),
+    /// A stored response that must be revalidated with these validators first.
+    Stale(Validators),
+    Miss,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// The request header values named by the response's `Vary`, as sent when it was stored.
+    vary: Vec<(String, Option<String>)>,
+    stored_at: SystemTime,
+    max_age: Option<Duration>,
+    no_cache: bool,
+    #[serde(default)]
+    public: bool,
+    #[serde(with = "response_bytes")]
+    response: GurtResponse,
+    #[serde(skip)]
+    last_used: u64,
+}
+
+impl CacheEntry {
+    fn matches(&self, request: &GurtRequest) -> bool {
+        self.vary.iter().all(|(name, value)| request.header(name) == value.as_ref())
+    }
+
+    /// Whether this entry may answer `request`. Only responses marked `public` are
+    /// shared with requests that carry credentials.
+    fn serves(&self, request: &GurtRequest) -> bool {
+        self.matches(request) && (self.public || !has_credentials(request))
+    }
+
+    fn is_fresh(&self) -> bool {
+        let age = self.stored_at.elapsed().unwrap_or_default();
+        !self.no_cache && self.max_age.is_some_and(|max_age| age < max_age)
+    }
+
+    fn size(&self) -> usize {
+        self.response.body.len()
+    }
+}
+
+/// Requests with these headers may get responses meant only for their sender.
+fn has_credentials(request: &GurtRequest) -> bool {
+    request.header("authorization").is_some() || request.header("cookie").is_some()
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, Vec<CacheEntry>>,
+    total_bytes: usize,
+    entry_count: usize,
+    clock: u64,
+}
+
+/// Response cache keyed by URL and the request headers named in `Vary`. When a disk
+/// directory is configured, it holds exactly the entries kept in memory.
+pub(crate) struct ResponseCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+    /// Serializes cache file updates so writes for the same URL land in order.
+    disk_lock: tokio::sync::Mutex<()>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(config: CacheConfig) -> Self {
+        if let Some(directory) = &config.disk_directory {
+            if let Err(e) = std::fs::create_dir_all(directory) {
+                debug!("Failed to create cache directory {}: {}", directory.display(), e);
+            }
+        }
+
+        let cache = Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+            disk_lock: tokio::sync::Mutex::new(()),
+        };
+        cache.load_from_disk();
+        cache
+    }
+
+    /// Whether `request` may be answered from or stored in the cache.
+    pub(crate) fn is_cacheable_request(request: &GurtRequest) -> bool {
+        request.method == GurtMethod::GET
+            && !CacheControl::of_request(request).no_store
+            && request.header("if-none-match").is_none()
+            && request.header("if-modified-since").is_none()
+            && request.header("range").is_none()
+    }
+
+    pub(crate) fn lookup(&self, url: &str, request: &GurtRequest) -> CacheLookup {
+        let force_revalidate = CacheControl::of_request(request).no_cache;
+        let Ok(mut state) = self.state.lock() else {
+            return CacheLookup::Miss;
+        };
+
+        state.clock += 1;
+        let clock = state.clock;
+
+        let Some(entry) = state.entries.get_mut(url).and_then(|entries| entries.iter_mut().find(|entry| entry.serves(request))) else {
+            return CacheLookup::Miss;
+        };
+        entry.last_used = clock;
+
+        if entry.is_fresh() && !force_revalidate {
+            CacheLookup::Fresh(entry.response.clone())
+        } else {
+            CacheLookup::Stale(Validators::from_response(&entry.response))
+        }
+    }
+
+    /// Stores `response` to `request` if its headers allow it.
+    pub(crate) async fn store(&self, url: &str, request: &GurtRequest, response: &GurtResponse) {
+        let control = CacheControl::of_response(response);
+        let vary = response.header("vary").cloned().unwrap_or_default();
+        let validators = Validators::from_response(response);
+        let has_validators = validators.etag.is_some() || validators.last_modified.is_some();
+
+        let cacheable = response.status_code == 200
+            && !control.no_store
+            && vary.trim() != "*"
+            && (control.public || !has_credentials(request))
+            && (control.max_age.is_some() || has_validators)
+            && response.body.len() <= self.config.max_bytes;
+
+        let changed = {
+            let Ok(mut state) = self.state.lock() else {
+                return;
+            };
+            Self::remove_variant(&mut state, url, request);
+
+            let mut changed = vec![url.to_string()];
+            if cacheable {
+                let entry = CacheEntry {
+                    vary: vary.split(',')
+                        .map(|name| name.trim().to_lowercase())
+                        .filter(|name| !name.is_empty())
+                        .map(|name| {
+                            let value = request.header(&name).cloned();
+                            (name, value)
+                        })
+                        .collect(),
+                    stored_at: SystemTime::now(),
+                    max_age: control.max_age,
+                    no_cache: control.no_cache,
+                    public: control.public,
+                    response: response.clone(),
+                    last_used: 0,
+                };
+
+                Self::insert_entry(&mut state, url, entry);
+                changed.extend(self.evict(&mut state));
+            }
+            changed
+        };
+
+        self.write_to_disk(changed).await;
+    }
+
+    /// Handles a 304 for a stale entry: refreshes its headers and freshness, and
+    /// returns the stored response in place of the bodiless 304.
+    pub(crate) async fn refresh(&self, url: &str, request: &GurtRequest, not_modified: &GurtResponse) -> Option<GurtResponse> {
+        let control = CacheControl::of_response(not_modified);
+
+        let response = {
+            let mut state = self.state.lock().ok()?;
+            let entry = state.entries.get_mut(url)?.iter_mut().find(|entry| entry.serves(request))?;
+            for name in not_modified.headers.names() {
+                if name.eq_ignore_ascii_case("content-length") || name.eq_ignore_ascii_case("transfer-encoding") {
+                    continue;
+                }
+                entry.response.headers.remove(name);
+                for value in not_modified.headers.get_all(name) {
+                    entry.response.headers.append(name, value.as_str());
+                }
+            }
+            entry.stored_at = SystemTime::now();
+            if not_modified.header("cache-control").is_some() {
+                entry.max_age = control.max_age;
+                entry.no_cache = control.no_cache;
+            }
+            entry.response.clone()
+        };
+
+        self.write_to_disk(vec![url.to_string()]).await;
+        Some(response)
+    }
+
+    fn insert_entry(state: &mut CacheState, url: &str, mut entry: CacheEntry) {
+        state.clock += 1;
+        entry.last_used = state.clock;
+        state.total_bytes += entry.size();
+        state.entry_count += 1;
+        state.entries.entry(url.to_string()).or_default().push(entry);
+    }
+
+    fn remove_variant(state: &mut CacheState, url: &str, request: &GurtRequest) {
+        let Some(entries) = state.entries.get_mut(url) else {
+            return;
+        };
+
+        if let Some(index) = entries.iter().position(|entry| entry.matches(request)) {
+            let removed = entries.swap_remove(index);
+            state.total_bytes -= removed.size();
+            state.entry_count -= 1;
+        }
+        if entries.is_empty() {
+            state.entries.remove(url);
+        }
+    }
+
+    /// Drops least recently used entries until the cache is within its bounds, and
+    /// returns the URLs whose entries changed.
+    fn evict(&self, state: &mut CacheState) -> Vec<String> {
+        let mut changed = Vec::new();
+
+        while state.total_bytes > self.config.max_bytes || state.entry_count > self.config.max_entries {
+            let oldest = state.entries.iter()
+                .flat_map(|(url, entries)| entries.iter().enumerate().map(move |(index, entry)| (entry.last_used, url, index)))
+                .min_by_key(|(last_used, _, _)| *last_used)
+                .map(|(_, url, index)| (url.clone(), index));
+
+            let Some((url, index)) = oldest else {
+                break;
+            };
+
+            if let Some(entries) = state.entries.get_mut(&url) {
+                let removed = entries.swap_remove(index);
+                state.total_bytes -= removed.size();
+                state.entry_count -= 1;
+                if entries.is_empty() {
+                    state.entries.remove(&url);
+                }
+            }
+            if !changed.contains(&url) {
+                changed.push(url);
+            }
+        }
+
+        changed
+    }
+
+    fn disk_path(&self, url: &str) -> Option<PathBuf> {
+        let directory = self.config.disk_directory.as_ref()?;
+        let digest = Sha256::digest(url.as_bytes());
+        let name: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Some(directory.join(format!("{}.json", name)))
+    }
+
+    /// Loads the cache files left by an earlier run, up to the configured bounds.
+    /// Files that don't fit or can't be read are deleted.
+    fn load_from_disk(&self) {
+        let Some(directory) = &self.config.disk_directory else {
+            return;
+        };
+        let Ok(files) = std::fs::read_dir(directory) else {
+            return;
+        };
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        for path in files.flatten().map(|file| file.path()) {
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+
+            let loaded = std::fs::read(&path).ok()
+                .and_then(|data| serde_json::from_slice::<(String, Vec<CacheEntry>)>(&data).ok())
+                .filter(|(url, _)| self.disk_path(url).as_ref() == Some(&path));
+
+            let fits = loaded.as_ref().is_some_and(|(_, entries)| {
+                let bytes: usize = entries.iter().map(CacheEntry::size).sum();
+                state.total_bytes + bytes <= self.config.max_bytes && state.entry_count + entries.len() <= self.config.max_entries
+            });
+
+            match loaded {
+                Some((url, entries)) if fits => {
+                    for entry in entries {
+                        Self::insert_entry(&mut state, &url, entry);
+                    }
+                }
+                _ => {
+                    if let Err(e) = std::fs::remove_file(&path) {
+                        debug!("Failed to remove cache file {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rewrites the cache files for `urls` from memory, removing the files of URLs
+    /// that no longer have entries.
+    async fn write_to_disk(&self, urls: Vec<String>) {
+        if self.config.disk_directory.is_none() {
+            return;
+        }
+        let _writing = self.disk_lock.lock().await;
+
+        for url in urls {
+            let Some(path) = self.disk_path(&url) else {
+                continue;
+            };
+            let entries = match self.state.lock() {
+                Ok(state) => state.entries.get(&url).cloned(),
+                Err(_) => continue,
+            };
+
+            let result = match entries {
+                Some(entries) => match serde_json::to_vec(&(&url, &entries)) {
+                    Ok(data) => tokio::fs::write(&path, data).await,
+                    Err(e) => Err(std::io::Error::other(e)),
+                },
+                None => tokio::fs::remove_file(&path).await
+                    .or_else(|e| if e.kind() == std::io::ErrorKind::NotFound { Ok(()) } else { Err(e) }),
+            };
+
+            if let Err(e) = result {
+                debug!("Failed to update cache file {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+/// Stores a response on disk as its base64-encoded wire format.
+mod response_bytes {
+    use super::*;
+
+    pub fn serialize<S: serde::Serializer>(response: &GurtResponse, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&BASE64.encode(response.to_bytes()))
+    }
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<GurtResponse, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = BASE64.decode(encoded).map_err(serde::de::Error::custom)?;
+        GurtResponse::parse_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(path: &str) -> GurtRequest {
+        GurtRequest::new(GurtMethod::GET, path.to_string())
+    }
+
+    fn cacheable(body: &str, cache_control: &str) -> GurtResponse {
+        GurtResponse::ok()
+            .with_header("cache-control", cache_control)
+            .with_header("etag", "\"v1\"")
+            .with_string_body(body)
+    }
+
+    #[test]
+    fn test_cache_control_parsing() {
+        let control = CacheControl::parse("public, max-age=60, no-cache");
+        assert_eq!(control.max_age, Some(Duration::from_secs(60)));
+        assert!(control.no_cache);
+        assert!(control.public);
+        assert!(!control.no_store);
+        assert!(CacheControl::parse("no-store").no_store);
+    }
+
+    #[tokio::test]
+    async fn test_fresh_and_stale_entries() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let url = "gurt://example.web:4878/app.js";
+
+        cache.store(url, &get("/app.js"), &cacheable("fresh", "max-age=60")).await;
+        assert!(matches!(cache.lookup(url, &get("/app.js")), CacheLookup::Fresh(response) if response.text().unwrap() == "fresh"));
+
+        let forced = get("/app.js").with_header("cache-control", "no-cache");
+        assert!(matches!(cache.lookup(url, &forced), CacheLookup::Stale(_)));
+
+        cache.store(url, &get("/app.js"), &cacheable("stale", "no-cache")).await;
+        let CacheLookup::Stale(validators) = cache.lookup(url, &get("/app.js")) else {
+            panic!("expected a stale entry");
+        };
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+
+        let not_modified = GurtResponse::not_modified().with_header("cache-control", "max-age=60");
+        let refreshed = cache.refresh(url, &get("/app.js"), &not_modified).await.unwrap();
+        assert_eq!(refreshed.text().unwrap(), "stale");
+        assert!(matches!(cache.lookup(url, &get("/app.js")), CacheLookup::Fresh(_)));
+
+        cache.store(url, &get("/app.js"), &cacheable("private", "no-store")).await;
+        assert!(matches!(cache.lookup(url, &get("/app.js")), CacheLookup::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_vary_selects_variant() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let url = "gurt://example.web:4878/";
+        let english = get("/").with_header("accept-language", "en");
+        let french = get("/").with_header("accept-language", "fr");
+
+        cache.store(url, &english, &cacheable("hello", "max-age=60").with_header("vary", "Accept-Language")).await;
+        cache.store(url, &french, &cacheable("bonjour", "max-age=60").with_header("vary", "Accept-Language")).await;
+
+        assert!(matches!(cache.lookup(url, &english), CacheLookup::Fresh(response) if response.text().unwrap() == "hello"));
+        assert!(matches!(cache.lookup(url, &french), CacheLookup::Fresh(response) if response.text().unwrap() == "bonjour"));
+        assert!(matches!(cache.lookup(url, &get("/")), CacheLookup::Miss));
+    }
+
+    #[tokio::test]
+    async fn test_credentialed_requests() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let url = "gurt://example.web:4878/account";
+        let alice = get("/account").with_header("cookie", "session=alice");
+        let bob = get("/account").with_header("authorization", "Bearer bob");
+
+        cache.store(url, &alice, &cacheable("alice", "max-age=60")).await;
+        assert!(matches!(cache.lookup(url, &alice), CacheLookup::Miss));
+        assert!(matches!(cache.lookup(url, &get("/account")), CacheLookup::Miss));
+
+        cache.store(url, &get("/account"), &cacheable("anonymous", "max-age=60")).await;
+        assert!(matches!(cache.lookup(url, &bob), CacheLookup::Miss));
+
+        cache.store(url, &alice, &cacheable("shared", "public, max-age=60")).await;
+        assert!(matches!(cache.lookup(url, &bob), CacheLookup::Fresh(response) if response.text().unwrap() == "shared"));
+    }
+
+    #[tokio::test]
+    async fn test_eviction_and_disk_persistence() {
+        let directory = std::env::temp_dir().join("gurtlib_cache_test");
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let config = CacheConfig {
+            max_bytes: 10,
+            max_entries: 10,
+            disk_directory: Some(directory.clone()),
+        };
+        let cache = ResponseCache::new(config.clone());
+
+        cache.store("gurt://a.web:4878/", &get("/"), &cacheable("aaaaaa", "max-age=60")).await;
+        cache.store("gurt://b.web:4878/", &get("/"), &cacheable("bbbbbb", "max-age=60")).await;
+        assert_eq!(cache.state.lock().unwrap().entry_count, 1);
+
+        // The evicted entry's file is removed along with it
+        assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+        let restarted = ResponseCache::new(config);
+        assert!(matches!(restarted.lookup("gurt://a.web:4878/", &get("/")), CacheLookup::Miss));
+        assert!(matches!(restarted.lookup("gurt://b.web:4878/", &get("/")), CacheLookup::Fresh(response) if response.text().unwrap() == "bbbbbb"));
+
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
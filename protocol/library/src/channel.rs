@@ -0,0 +1,360 @@
+use crate::{GurtError, GurtRequest, GurtResponse, GurtStatusCode, Result};
+use crate::protocol::MAX_MESSAGE_SIZE;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::sync::Arc;
+
+/// The `upgrade` header value that asks to turn a connection into a [`GurtChannel`].
+pub const CHANNEL_PROTOCOL: &str = "gurt-channel";
+
+/// Upper bound on the payload of a single frame.
+pub const MAX_FRAME_SIZE: usize = MAX_MESSAGE_SIZE;
+
+const FRAME_HEADER_LEN: usize = 5;
+const READ_CHUNK_SIZE: usize = 8192;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// A message sent over a [`GurtChannel`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelMessage {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl ChannelMessage {
+    pub fn as_text(&self) -> Option<&str> {
+        match self {
+            ChannelMessage::Text(text) => Some(text),
+            ChannelMessage::Binary(_) => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ChannelMessage::Text(text) => text.as_bytes(),
+            ChannelMessage::Binary(data) => data,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            ChannelMessage::Text(text) => text.into_bytes(),
+            ChannelMessage::Binary(data) => data,
+        }
+    }
+}
+
+impl From<String> for ChannelMessage {
+    fn from(text: String) -> Self {
+        ChannelMessage::Text(text)
+    }
+}
+
+impl From<&str> for ChannelMessage {
+    fn from(text: &str) -> Self {
+        ChannelMessage::Text(text.to_string())
+    }
+}
+
+impl From<Vec<u8>> for ChannelMessage {
+    fn from(data: Vec<u8>) -> Self {
+        ChannelMessage::Binary(data)
+    }
+}
+
+/// Whether `request` asks to be upgraded to a channel.
+pub fn is_upgrade_request(request: &GurtRequest) -> bool {
+    request.header("upgrade").is_some_and(|value| value.trim().eq_ignore_ascii_case(CHANNEL_PROTOCOL))
+}
+
+/// The `101` response that accepts a channel upgrade.
+pub fn accept_response() -> GurtResponse {
+    GurtResponse::new(GurtStatusCode::SwitchingProtocols)
+        .with_header("upgrade", CHANNEL_PROTOCOL)
+        .with_header("connection", "upgrade")
+}
+
+/// The request that opens a channel to `path`.
+pub fn upgrade_request(path: &str) -> GurtRequest {
+    GurtRequest::new(crate::GurtMethod::GET, path.to_string())
+        .with_header("upgrade", CHANNEL_PROTOCOL)
+        .with_header("connection", "upgrade")
+}
+
+fn encode_frame(opcode: u8, payload: &[u8]) -> Result<Vec<u8>> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return Err(GurtError::invalid_message(format!("Channel message of {} bytes is too large", payload.len())));
+    }
+
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.push(opcode);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+    Ok(frame)
+}
+
+struct SenderState {
+    writer: Box<dyn AsyncWrite + Send + Sync + Unpin>,
+    closed: bool,
+}
+
+/// The sending half of a [`GurtChannel`]. Clones send over the same channel, so one
+/// can be handed to another task, e.g. to broadcast to every connected client.
+#[derive(Clone)]
+pub struct ChannelSender {
+    state: Arc<tokio::sync::Mutex<SenderState>>,
+}
+
+impl ChannelSender {
+    pub async fn send<M: Into<ChannelMessage>>(&self, message: M) -> Result<()> {
+        match message.into() {
+            ChannelMessage::Text(text) => self.send_frame(OPCODE_TEXT, text.as_bytes()).await,
+            ChannelMessage::Binary(data) => self.send_frame(OPCODE_BINARY, &data).await,
+        }
+    }
+
+    /// Sends a ping. The peer answers with a pong, which keeps idle connections open.
+    pub async fn ping(&self) -> Result<()> {
+        self.send_frame(OPCODE_PING, &[]).await
+    }
+
+    /// Starts closing the channel. Messages already on their way are still received
+    /// until the peer confirms, after which `recv` returns `None`.
+    pub async fn close(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        if state.closed {
+            return Ok(());
+        }
+
+        state.closed = true;
+        state.writer.write_all(&encode_frame(OPCODE_CLOSE, &[])?).await?;
+        state.writer.flush().await?;
+        Ok(())
+    }
+
+    pub async fn is_closed(&self) -> bool {
+        self.state.lock().await.closed
+    }
+
+    async fn send_frame(&self, opcode: u8, payload: &[u8]) -> Result<()> {
+        let frame = encode_frame(opcode, payload)?;
+
+        let mut state = self.state.lock().await;
+        if state.closed {
+            return Err(GurtError::connection("Channel is closed"));
+        }
+        state.writer.write_all(&frame).await?;
+        state.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn shutdown(&self) {
+        let mut state = self.state.lock().await;
+        state.closed = true;
+        let _ = state.writer.shutdown().await;
+    }
+}
+
+/// The receiving half of a [`GurtChannel`].
+pub struct ChannelReceiver {
+    reader: Box<dyn AsyncRead + Send + Sync + Unpin>,
+    buffer: Vec<u8>,
+    // Used to answer pings and confirm the peer's close
+    sender: ChannelSender,
+    finished: bool,
+}
+
+impl ChannelReceiver {
+    /// Waits for the next message. Returns `None` once the channel is closed.
+    pub async fn recv(&mut self) -> Result<Option<ChannelMessage>> {
+        while !self.finished {
+            let Some((opcode, payload)) = self.next_frame().await? else {
+                self.finished = true;
+                self.sender.shutdown().await;
+                break;
+            };
+
+            match opcode {
+                OPCODE_TEXT => {
+                    let text = String::from_utf8(payload)
+                        .map_err(|_| GurtError::invalid_message("Channel text message is not valid UTF-8"))?;
+                    return Ok(Some(ChannelMessage::Text(text)));
+                }
+                OPCODE_BINARY => return Ok(Some(ChannelMessage::Binary(payload))),
+                OPCODE_PING => {
+                    // A ping racing our own close has nobody left to answer it
+                    if !self.sender.is_closed().await {
+                        self.sender.send_frame(OPCODE_PONG, &payload).await?;
+                    }
+                }
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => {
+                    self.sender.close().await?;
+                    self.sender.shutdown().await;
+                    self.finished = true;
+                }
+                other => {
+                    return Err(GurtError::protocol(format!("Unknown channel frame type {:#x}", other)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    async fn next_frame(&mut self) -> Result<Option<(u8, Vec<u8>)>> {
+        loop {
+            if self.buffer.len() >= FRAME_HEADER_LEN {
+                let length = u32::from_be_bytes([self.buffer[1], self.buffer[2], self.buffer[3], self.buffer[4]]) as usize;
+                if length > MAX_FRAME_SIZE {
+                    return Err(GurtError::invalid_message(format!("Channel frame of {} bytes is too large", length)));
+                }
+
+                if self.buffer.len() >= FRAME_HEADER_LEN + length {
+                    let opcode = self.buffer[0];
+                    let payload = self.buffer[FRAME_HEADER_LEN..FRAME_HEADER_LEN + length].to_vec();
+                    self.buffer.drain(..FRAME_HEADER_LEN + length);
+                    return Ok(Some((opcode, payload)));
+                }
+            }
+
+            let mut chunk = [0u8; READ_CHUNK_SIZE];
+            let read = match self.reader.read(&mut chunk).await {
+                Ok(read) => read,
+                // TLS peers often drop the connection without close_notify
+                Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => 0,
+                Err(e) => return Err(e.into()),
+            };
+            if read == 0 {
+                if !self.buffer.is_empty() {
+                    return Err(GurtError::connection("Channel closed in the middle of a message"));
+                }
+                return Ok(None);
+            }
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// A long-lived, bidirectional message stream over an upgraded GURT connection.
+///
+/// Opened by a client with [`GurtClient::connect_channel`](crate::GurtClient::connect_channel)
+/// and accepted on the server by a route registered with
+/// [`GurtServer::channel`](crate::GurtServer::channel). Messages are framed, so each
+/// `recv` returns exactly one message as the peer sent it.
+pub struct GurtChannel {
+    sender: ChannelSender,
+    receiver: ChannelReceiver,
+}
+
+impl GurtChannel {
+    /// Wraps an upgraded connection. `buffered` holds bytes already read past the
+    /// upgrade response, which belong to the first frames.
+    pub fn new<S>(stream: S, buffered: Vec<u8>) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + Sync + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let sender = ChannelSender {
+            state: Arc::new(tokio::sync::Mutex::new(SenderState {
+                writer: Box::new(writer),
+                closed: false,
+            })),
+        };
+
+        Self {
+            receiver: ChannelReceiver {
+                reader: Box::new(reader),
+                buffer: buffered,
+                sender: sender.clone(),
+                finished: false,
+            },
+            sender,
+        }
+    }
+
+    pub async fn send<M: Into<ChannelMessage>>(&self, message: M) -> Result<()> {
+        self.sender.send(message).await
+    }
+
+    pub async fn recv(&mut self) -> Result<Option<ChannelMessage>> {
+        self.receiver.recv().await
+    }
+
+    pub async fn ping(&self) -> Result<()> {
+        self.sender.ping().await
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        self.sender.close().await
+    }
+
+    /// A handle for sending from another task.
+    pub fn sender(&self) -> ChannelSender {
+        self.sender.clone()
+    }
+
+    /// Splits the channel so sending and receiving can happen in separate tasks.
+    pub fn split(self) -> (ChannelSender, ChannelReceiver) {
+        (self.sender, self.receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_messages() {
+        let (client_io, server_io) = tokio::io::duplex(64);
+        let mut client = GurtChannel::new(client_io, Vec::new());
+        let mut server = GurtChannel::new(server_io, Vec::new());
+
+        let large = vec![7u8; 100_000];
+        let sending = {
+            let large = large.clone();
+            let sender = client.sender();
+            tokio::spawn(async move {
+                sender.send("hello").await.unwrap();
+                sender.ping().await.unwrap();
+                sender.send(large).await.unwrap();
+            })
+        };
+
+        assert_eq!(server.recv().await.unwrap(), Some(ChannelMessage::Text("hello".to_string())));
+        assert_eq!(server.recv().await.unwrap(), Some(ChannelMessage::Binary(large)));
+        sending.await.unwrap();
+
+        server.send("reply").await.unwrap();
+        assert_eq!(client.recv().await.unwrap().unwrap().as_text(), Some("reply"));
+
+        // Closing from one side ends both receivers
+        client.close().await.unwrap();
+        assert!(client.send("late").await.is_err());
+        assert_eq!(server.recv().await.unwrap(), None);
+        assert_eq!(client.recv().await.unwrap(), None);
+        assert!(server.send("late").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_channel_buffered_and_invalid_frames() {
+        let (client_io, server_io) = tokio::io::duplex(64);
+        let mut frames = encode_frame(OPCODE_TEXT, b"early").unwrap();
+        frames.extend(encode_frame(OPCODE_BINARY, &[1, 2]).unwrap());
+
+        let mut server = GurtChannel::new(server_io, frames);
+        assert_eq!(server.recv().await.unwrap(), Some(ChannelMessage::Text("early".to_string())));
+        assert_eq!(server.recv().await.unwrap(), Some(ChannelMessage::Binary(vec![1, 2])));
+
+        let (_reader, mut writer) = tokio::io::split(client_io);
+        writer.write_all(&[OPCODE_TEXT, 0xFF, 0xFF, 0xFF, 0xFF]).await.unwrap();
+        assert!(server.recv().await.is_err());
+
+        assert!(is_upgrade_request(&upgrade_request("/live")));
+        assert!(!is_upgrade_request(&GurtRequest::new(crate::GurtMethod::GET, "/live".to_string())));
+        assert_eq!(accept_response().status_code, 101);
+    }
+}
@@ -1,13 +1,21 @@
 use crate::{
-    GurtError, Result, GurtRequest, GurtResponse,
-    protocol::{DEFAULT_PORT, DEFAULT_CONNECTION_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, DEFAULT_HANDSHAKE_TIMEOUT, BODY_SEPARATOR},
-    message::GurtMethod,
-    crypto::GURT_ALPN,
+    GurtError, Result, GurtRequest, GurtResponse, GurtStatusCode,
+    protocol::{DEFAULT_PORT, DEFAULT_CONNECTION_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, DEFAULT_HANDSHAKE_TIMEOUT, BODY_SEPARATOR, MAX_MESSAGE_SIZE},
+    message::{GurtMethod, ChunkedDecoder, MessageFrame, frame_message},
+    crypto::{self, GURT_ALPN, TLS_SESSION_CACHE_SIZE},
+    pipeline::{PipelinedConnection, SendFailure},
+    cache::{CacheConfig, CacheLookup, ResponseCache},
+    compression::{self, ContentEncoding, StreamDecoder},
+    cookie::CookieJar,
+    multipart::Multipart,
+    channel::{self, GurtChannel},
+    events::{self, EventStream},
+    handshake::{self, Capabilities, Handshake},
 };
 use tokio::net::TcpStream;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::time::{timeout, Duration};
-use tokio_rustls::{TlsConnector, rustls::{ClientConfig as TlsClientConfig, RootCertStore, pki_types::ServerName}};
+use tokio_rustls::{TlsConnector, rustls::{ClientConfig as TlsClientConfig, RootCertStore, client::Resumption, pki_types::ServerName}};
 use std::sync::Arc;
 use std::collections::HashMap;
 use std::sync::Mutex;
@@ -23,10 +31,34 @@ pub struct GurtClientConfig {
     pub max_redirects: usize,
     pub enable_connection_pooling: bool,
     pub max_connections_per_host: usize,
+    /// How long an idle pooled connection is reused. Keep it below the server's idle
+    /// timeout (10 seconds for `GurtServer` by default), which closes idle connections.
+    pub pool_idle_timeout: Duration,
     pub custom_ca_certificates: Vec<String>,
     pub dns_server_ip: String,
     pub dns_server_port: u16,
     pub read_timeout: Duration,
+    /// Queue requests on pooled connections instead of waiting for each response.
+    /// Requires a server that reads pipelined requests, so it is off by default.
+    pub enable_pipelining: bool,
+    pub max_in_flight_per_connection: usize,
+    /// Cache GET responses according to their `Cache-Control` headers. Off when `None`.
+    pub cache: Option<CacheConfig>,
+    /// Send `Accept-Encoding` and transparently decode compressed response bodies.
+    pub decompress: bool,
+    /// Largest body a compressed response may decode to before the request fails.
+    pub max_decompressed_size: usize,
+    /// Send stored cookies with each request and store the ones responses set. Off when `None`;
+    /// share an `Arc` between clients to share their cookies.
+    pub cookie_jar: Option<Arc<CookieJar>>,
+    /// Start new connections with TLS right away, skipping the plaintext HANDSHAKE round
+    /// trip, to servers that advertised `direct-tls` in an earlier handshake.
+    pub direct_tls: bool,
+    /// PEM certificate chain presented to servers that ask for a client certificate.
+    /// Requires `client_key`.
+    pub client_certificate: Option<String>,
+    /// PEM private key matching `client_certificate`.
+    pub client_key: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -50,14 +82,32 @@ impl Default for GurtClientConfig {
             max_redirects: 5,
             enable_connection_pooling: true,
             max_connections_per_host: 4,
+            pool_idle_timeout: Duration::from_secs(5),
             custom_ca_certificates: Vec::new(),
             dns_server_ip: "135.125.163.131".to_string(),
             dns_server_port: 4878,
             read_timeout: Duration::from_secs(5),
+            enable_pipelining: false,
+            max_in_flight_per_connection: 8,
+            cache: None,
+            decompress: true,
+            max_decompressed_size: MAX_MESSAGE_SIZE,
+            cookie_jar: None,
+            direct_tls: true,
+            client_certificate: None,
+            client_key: None,
         }
     }
 }
 
+/// How long to go straight to HANDSHAKE with a server after direct TLS to it failed.
+const DIRECT_TLS_BACKOFF: Duration = Duration::from_secs(60);
+
+async fn write_request(tls_stream: &mut tokio_rustls::client::TlsStream<TcpStream>, request: &[u8]) -> Result<()> {
+    tls_stream.write_all(request).await
+        .map_err(|e| GurtError::connection(format!("Failed to write request: {}", e)))
+}
+
 #[derive(Debug)]
 enum Connection {
     Plain(TcpStream),
@@ -91,7 +141,19 @@ impl PooledConnection {
 pub struct GurtClient {
     config: GurtClientConfig,
     connection_pool: Arc<Mutex<HashMap<ConnectionKey, Vec<PooledTlsConnection>>>>,
+    pipelined_pool: Arc<Mutex<HashMap<ConnectionKey, Vec<PipelinedConnection>>>>,
+    /// Held while opening a pipelined connection to a host, so concurrent requests wait
+    /// for it instead of each opening their own.
+    pipelined_connects: Arc<Mutex<HashMap<ConnectionKey, Arc<tokio::sync::Mutex<()>>>>>,
     dns_cache: Arc<Mutex<HashMap<String, String>>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    /// The outcome of the latest handshake with each server.
+    handshakes: Arc<Mutex<HashMap<ConnectionKey, Handshake>>>,
+    /// When direct TLS last failed with each server, to skip it for `DIRECT_TLS_BACKOFF`.
+    direct_tls_failures: Arc<Mutex<HashMap<ConnectionKey, std::time::Instant>>>,
+    /// Built on first use and shared by clones, so its session store lets reconnects
+    /// resume earlier TLS sessions instead of running a full handshake.
+    tls_config: Arc<Mutex<Option<Arc<TlsClientConfig>>>>,
 }
 
 impl GurtClient {
@@ -99,21 +161,36 @@ impl GurtClient {
         Self {
             config: GurtClientConfig::default(),
             connection_pool: Arc::new(Mutex::new(HashMap::new())),
+            pipelined_pool: Arc::new(Mutex::new(HashMap::new())),
+            pipelined_connects: Arc::new(Mutex::new(HashMap::new())),
             dns_cache: Arc::new(Mutex::new(HashMap::new())),
+            response_cache: None,
+            handshakes: Arc::new(Mutex::new(HashMap::new())),
+            direct_tls_failures: Arc::new(Mutex::new(HashMap::new())),
+            tls_config: Arc::new(Mutex::new(None)),
         }
     }
     
     pub fn with_config(config: GurtClientConfig) -> Self {
+        let response_cache = config.cache.clone().map(|cache_config| Arc::new(ResponseCache::new(cache_config)));
+        
         Self {
             config,
             connection_pool: Arc::new(Mutex::new(HashMap::new())),
+            pipelined_pool: Arc::new(Mutex::new(HashMap::new())),
+            pipelined_connects: Arc::new(Mutex::new(HashMap::new())),
             dns_cache: Arc::new(Mutex::new(HashMap::new())),
+            response_cache,
+            handshakes: Arc::new(Mutex::new(HashMap::new())),
+            direct_tls_failures: Arc::new(Mutex::new(HashMap::new())),
+            tls_config: Arc::new(Mutex::new(None)),
         }
     }
     
-    async fn get_pooled_connection(&self, host: &str, port: u16, original_host: Option<&str>) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    /// Returns an idle pooled connection, or a new one. The flag tells whether it was reused.
+    async fn get_pooled_connection(&self, host: &str, port: u16, original_host: Option<&str>) -> Result<(tokio_rustls::client::TlsStream<TcpStream>, bool)> {
         if !self.config.enable_connection_pooling {
-            return self.perform_handshake(host, port, original_host).await;
+            return Ok((self.perform_handshake(host, port, original_host).await?, false));
         }
         
         let pool_host = original_host.unwrap_or(host);
@@ -124,17 +201,53 @@ impl GurtClient {
         
         if let Ok(mut pool) = self.connection_pool.lock() {
             if let Some(connections) = pool.get_mut(&key) {
-                connections.retain(|conn| conn.last_used.elapsed().as_secs() < 30);
+                connections.retain(|conn| conn.last_used.elapsed() < self.config.pool_idle_timeout);
                 
                 if let Some(pooled_conn) = connections.pop() {
                     debug!("Reusing pooled connection for {}:{}", host, port);
-                    return Ok(pooled_conn.connection);
+                    return Ok((pooled_conn.connection, true));
                 }
             }
         }
         
         debug!("Creating new connection for {}:{}", host, port);
-        self.perform_handshake(host, port, original_host).await
+        Ok((self.perform_handshake(host, port, original_host).await?, false))
+    }
+    
+    /// Writes `request` and reads the first bytes of the response into `buffer`. A pooled
+    /// connection that the server closed while it sat idle is replaced once by a new one
+    /// if writing the request failed, or if the request is idempotent and no byte of the
+    /// response arrived. Other requests may already have been processed, so they fail.
+    async fn start_exchange(&self, host: &str, port: u16, original_host: Option<&str>, request: &GurtRequest, buffer: &mut [u8]) -> Result<(tokio_rustls::client::TlsStream<TcpStream>, usize)> {
+        let request_bytes = request.to_bytes();
+        let (mut tls_stream, reused) = self.get_pooled_connection(host, port, original_host).await?;
+        
+        let failure = match write_request(&mut tls_stream, &request_bytes).await {
+            Err(e) => e,
+            Ok(()) => match self.await_response(&mut tls_stream, buffer).await {
+                Ok(read) => return Ok((tls_stream, read)),
+                Err(e) if request.method.is_idempotent() => e,
+                Err(e) => return Err(e),
+            },
+        };
+        if !reused || matches!(failure, GurtError::Timeout(_)) {
+            return Err(failure);
+        }
+        
+        debug!("Pooled connection to {}:{} failed before responding ({}), retrying on a new connection", host, port, failure);
+        let mut tls_stream = self.perform_handshake(host, port, original_host).await?;
+        write_request(&mut tls_stream, &request_bytes).await?;
+        let read = self.await_response(&mut tls_stream, buffer).await?;
+        Ok((tls_stream, read))
+    }
+    
+    async fn await_response(&self, tls_stream: &mut tokio_rustls::client::TlsStream<TcpStream>, buffer: &mut [u8]) -> Result<usize> {
+        match timeout(self.config.request_timeout, tls_stream.read(buffer)).await {
+            Ok(Ok(0)) => Err(GurtError::connection("Connection closed before the response arrived")),
+            Ok(Ok(read)) => Ok(read),
+            Ok(Err(e)) => Err(GurtError::connection(format!("Read error: {}", e))),
+            Err(_) => Err(GurtError::timeout("Request timeout")),
+        }
     }
     
     fn return_connection_to_pool(&self, host: &str, port: u16, connection: tokio_rustls::client::TlsStream<TcpStream>) {
@@ -160,6 +273,85 @@ impl GurtClient {
         }
     }
     
+    /// Picks the least busy pipelined connection to `key`, or `None` when a new one should be opened.
+    fn select_pipelined_connection(&self, key: &ConnectionKey) -> Option<PipelinedConnection> {
+        let mut pool = self.pipelined_pool.lock().ok()?;
+        let connections = pool.get_mut(key)?;
+        connections.retain(|conn| !conn.is_closed());
+        
+        let least_busy = connections.iter().max_by_key(|conn| conn.available_slots())?;
+        if least_busy.available_slots() > 0 || connections.len() >= self.config.max_connections_per_host {
+            Some(least_busy.clone())
+        } else {
+            None
+        }
+    }
+    
+    async fn send_pipelined(&self, host: &str, port: u16, request: GurtRequest, original_host: Option<&str>) -> Result<GurtResponse> {
+        let key = ConnectionKey {
+            host: original_host.unwrap_or(host).to_string(),
+            port,
+        };
+        
+        let connection = match self.select_pipelined_connection(&key) {
+            Some(connection) => {
+                debug!("Pipelining request on existing connection for {}:{}", host, port);
+                connection
+            }
+            None => self.open_pipelined_connection(host, port, original_host, key.clone()).await?,
+        };
+        
+        let exchange = async {
+            match connection.send(&request).await {
+                // The server may have closed the connection just as the request went out
+                Err(SendFailure::Unanswered(e)) if request.method.is_idempotent() => {
+                    debug!("Resending {} {} on a new connection: {}", request.method, request.path, e);
+                    let connection = self.connect_pipelined(host, port, original_host, key).await?;
+                    connection.send(&request).await.map_err(SendFailure::into_error)
+                }
+                result => result.map_err(SendFailure::into_error),
+            }
+        };
+        
+        timeout(self.config.request_timeout, exchange).await
+            .map_err(|_| GurtError::timeout("Request timeout"))?
+    }
+    
+    /// Opens a pipelined connection to `key`, one at a time per host. Requests that waited
+    /// while another connection was opening use that one if it has room.
+    async fn open_pipelined_connection(&self, host: &str, port: u16, original_host: Option<&str>, key: ConnectionKey) -> Result<PipelinedConnection> {
+        let connect_lock = self.pipelined_connects.lock()
+            .map_err(|_| GurtError::connection("Connection pool lock poisoned"))?
+            .entry(key.clone())
+            .or_default()
+            .clone();
+        let _connecting = connect_lock.lock().await;
+        
+        if let Some(connection) = self.select_pipelined_connection(&key) {
+            debug!("Pipelining request on connection opened meanwhile for {}:{}", host, port);
+            return Ok(connection);
+        }
+        
+        self.connect_pipelined(host, port, original_host, key).await
+    }
+    
+    async fn connect_pipelined(&self, host: &str, port: u16, original_host: Option<&str>, key: ConnectionKey) -> Result<PipelinedConnection> {
+        debug!("Creating new pipelined connection for {}:{}", host, port);
+        let tls_stream = self.perform_handshake(host, port, original_host).await?;
+        // Servers that did not agree to pipelining get one request at a time
+        let max_in_flight = match self.negotiated_handshake(&key.host, port) {
+            Some(negotiated) if !negotiated.capabilities.pipelining => 1,
+            _ => self.config.max_in_flight_per_connection,
+        };
+        let connection = PipelinedConnection::spawn(tls_stream, max_in_flight);
+        
+        if let Ok(mut pool) = self.pipelined_pool.lock() {
+            pool.entry(key).or_default().push(connection.clone());
+        }
+        
+        Ok(connection)
+    }
+    
     async fn create_connection(&self, host: &str, port: u16) -> Result<PooledConnection> {
         let addr = format!("{}:{}", host, port);
         let stream = timeout(
@@ -235,16 +427,29 @@ impl GurtClient {
     }
     
     async fn perform_handshake(&self, host: &str, port: u16, original_host: Option<&str>) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        // Use original_host for the Host header if available, otherwise fall back to host
+        let host_header = original_host.unwrap_or(host);
+        
+        if self.should_try_direct_tls(host_header, port) {
+            match self.connect_direct_tls(host, port, host_header).await {
+                Ok(tls_stream) => return Ok(tls_stream),
+                Err(e) => {
+                    debug!("Direct TLS with {}:{} failed, falling back to HANDSHAKE: {}", host, port, e);
+                    if let Ok(mut failures) = self.direct_tls_failures.lock() {
+                        failures.insert(ConnectionKey { host: host_header.to_string(), port }, std::time::Instant::now());
+                    }
+                }
+            }
+        }
+        
         debug!("Starting GURT handshake with {}:{}", host, port);
         
         let mut plain_conn = self.create_connection(host, port).await?;
         
-        // Use original_host for the Host header if available, otherwise fall back to host
-        let host_header = original_host.unwrap_or(host);
-        
-        let handshake_request = GurtRequest::new(GurtMethod::HANDSHAKE, "/".to_string())
+        let offered = self.offered_capabilities();
+        let handshake_request = handshake::offer(GurtRequest::new(GurtMethod::HANDSHAKE, "/".to_string())
             .with_header("Host", host_header)
-            .with_header("User-Agent", &self.config.user_agent);
+            .with_header("User-Agent", &self.config.user_agent), offered);
         
         let handshake_data = handshake_request.to_string();
         plain_conn.connection.write_all(handshake_data.as_bytes()).await?;
@@ -256,16 +461,14 @@ impl GurtClient {
             .map_err(|_| GurtError::Timeout("Handshake timeout".to_string()))??;
         
         let handshake_response = GurtResponse::parse_bytes(&handshake_response_bytes)?;
+        let negotiated = handshake::confirm(&handshake_response, offered)?;
+        debug!("Negotiated GURT/{} with {} (capabilities: {})", negotiated.version, host_header, negotiated.capabilities);
         
-        if handshake_response.status_code != 101 {
-            return Err(GurtError::Protocol(format!("Handshake failed: {} {}", 
-                handshake_response.status_code, 
-                handshake_response.status_message)));
+        if let Ok(mut handshakes) = self.handshakes.lock() {
+            handshakes.insert(ConnectionKey { host: host_header.to_string(), port }, negotiated);
         }
         
-        let tcp_stream = match plain_conn.connection {
-            Connection::Plain(stream) => stream,
-        };
+        let Connection::Plain(tcp_stream) = plain_conn.connection;
         
         // Use original_host for TLS SNI if available, otherwise fall back to host
         let tls_host = original_host.unwrap_or(host);
@@ -273,48 +476,70 @@ impl GurtClient {
         self.upgrade_to_tls(tcp_stream, tls_host).await
     }
     
-    async fn upgrade_to_tls(&self, stream: TcpStream, host: &str) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
-        debug!("Upgrading connection to TLS for {}", host);
-        
-        let mut root_store = RootCertStore::empty();
-        
-        let cert_result = rustls_native_certs::load_native_certs();
-        let mut added = 0;
-        for cert in cert_result.certs {
-            if root_store.add(cert).is_ok() {
-                added += 1;
-            }
+    /// Whether to skip HANDSHAKE with `host`: it advertised direct TLS in an earlier
+    /// handshake, and direct TLS has not failed with it within `DIRECT_TLS_BACKOFF`.
+    fn should_try_direct_tls(&self, host: &str, port: u16) -> bool {
+        if !self.config.direct_tls || !self.negotiated_handshake(host, port).is_some_and(|negotiated| negotiated.capabilities.direct_tls) {
+            return false;
         }
         
-        for ca_cert_pem in &self.config.custom_ca_certificates {
-            let mut pem_bytes = ca_cert_pem.as_bytes();
-            let cert_iter = rustls_pemfile::certs(&mut pem_bytes);
-            for cert_result in cert_iter {
-                match cert_result {
-                    Ok(cert) => {
-                        if root_store.add(cert).is_ok() {
-                            added += 1;
-                            debug!("Added custom CA certificate");
-                        }
-                    }
-                    Err(e) => {
-                        debug!("Failed to parse CA certificate: {}", e);
-                    }
-                }
+        let key = ConnectionKey { host: host.to_string(), port };
+        let Ok(mut failures) = self.direct_tls_failures.lock() else {
+            return true;
+        };
+        match failures.get(&key) {
+            Some(failed_at) if failed_at.elapsed() < DIRECT_TLS_BACKOFF => false,
+            Some(_) => {
+                failures.remove(&key);
+                true
             }
+            None => true,
         }
+    }
+    
+    /// Opens a connection that starts TLS immediately, identified as GURT by ALPN alone.
+    async fn connect_direct_tls(&self, host: &str, port: u16, tls_host: &str) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        debug!("Starting direct TLS with {}:{}", host, port);
         
-        if added == 0 {
-            return Err(GurtError::Crypto("No valid certificates found (system or custom)".to_string()));
+        let Connection::Plain(tcp_stream) = self.create_connection(host, port).await?.connection;
+        timeout(self.config.handshake_timeout, self.upgrade_to_tls(tcp_stream, tls_host)).await
+            .map_err(|_| GurtError::timeout("Direct TLS handshake timeout"))?
+    }
+    
+    /// The optional features this client asks for in its handshakes.
+    fn offered_capabilities(&self) -> Capabilities {
+        Capabilities {
+            compression: self.config.decompress,
+            pipelining: self.config.enable_pipelining,
+            streaming: true,
+            direct_tls: self.config.direct_tls,
         }
+    }
+    
+    /// The version and capabilities agreed on in the latest handshake with `host`, or
+    /// `None` before the first connection to it.
+    pub fn negotiated_handshake(&self, host: &str, port: u16) -> Option<Handshake> {
+        let key = ConnectionKey { host: host.to_string(), port };
+        self.handshakes.lock().ok()?.get(&key).copied()
+    }
+    
+    fn tls_client_config(&self) -> Result<Arc<TlsClientConfig>> {
+        let mut cached = self.tls_config.lock()
+            .map_err(|_| GurtError::Crypto("TLS config lock poisoned".to_string()))?;
         
-        let mut client_config = TlsClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+        if let Some(config) = cached.as_ref() {
+            return Ok(config.clone());
+        }
         
-        client_config.alpn_protocols = vec![GURT_ALPN.to_vec()];
+        let config = Arc::new(build_tls_client_config(&self.config)?);
+        *cached = Some(config.clone());
+        Ok(config)
+    }
+    
+    async fn upgrade_to_tls(&self, stream: TcpStream, host: &str) -> Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        debug!("Upgrading connection to TLS for {}", host);
         
-        let connector = TlsConnector::from(Arc::new(client_config));
+        let connector = TlsConnector::from(self.tls_client_config()?);
         
         let server_name = match host {
             "127.0.0.1" => "localhost",
@@ -336,64 +561,44 @@ impl GurtClient {
         let pool_host = original_host.unwrap_or(host);
         debug!("Sending {} {} to {}:{}", request.method, request.path, host, port);
         
-        let mut tls_stream = self.get_pooled_connection(host, port, original_host).await?;
-        
-        let request_data = request.to_string();
-        tls_stream.write_all(request_data.as_bytes()).await
-            .map_err(|e| GurtError::Connection(format!("Failed to write request: {}", e)))?;
+        if self.config.enable_pipelining && self.config.enable_connection_pooling {
+            return self.send_pipelined(host, port, request, original_host).await;
+        }
         
-        let mut buffer = Vec::new();
         let mut temp_buffer = [0u8; 8192];
-        
         let start_time = std::time::Instant::now();
-        let mut headers_parsed = false;
-        let mut expected_body_length: Option<usize> = None;
-        let mut headers_end_pos: Option<usize> = None;
         
-        loop {
+        let (mut tls_stream, first_read) = self.start_exchange(host, port, original_host, &request, &mut temp_buffer).await?;
+        let mut first_read = Some(first_read);
+        let mut buffer = Vec::new();
+        let has_body = request.method != GurtMethod::HEAD;
+        
+        let response_len = loop {
+            match frame_message(&buffer, has_body) {
+                MessageFrame::Complete(len) => break len,
+                MessageFrame::Incomplete => {}
+                MessageFrame::TooLarge => return Err(GurtError::invalid_message("Response exceeds maximum message size")),
+                MessageFrame::Invalid(reason) => return Err(GurtError::invalid_message(reason)),
+            }
+            
             if start_time.elapsed() > self.config.request_timeout {
                 return Err(GurtError::Timeout("Request timeout".to_string()));
             }
             
-            match timeout(Duration::from_millis(100), tls_stream.read(&mut temp_buffer)).await {
-                Ok(Ok(0)) => break,
-                Ok(Ok(n)) => {
-                    buffer.extend_from_slice(&temp_buffer[..n]);
-                    
-                    if !headers_parsed {
-                        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
-                            headers_end_pos = Some(pos + 4);
-                            headers_parsed = true;
-                            
-                            let headers_section = std::str::from_utf8(&buffer[..pos])
-                                .map_err(|e| GurtError::InvalidMessage(format!("Invalid UTF-8 in headers: {}", e)))?;
-                            
-                            for line in headers_section.lines().skip(1) {
-                                if line.to_lowercase().starts_with("content-length:") {
-                                    if let Some(length_str) = line.split(':').nth(1) {
-                                        expected_body_length = length_str.trim().parse().ok();
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    if headers_parsed {
-                        if let (Some(headers_end), Some(expected_len)) = (headers_end_pos, expected_body_length) {
-                            if buffer.len() >= headers_end + expected_len {
-                                break;
-                            }
-                        } else if expected_body_length.is_none() && headers_parsed {
-                            break;
-                        }
-                    }
-                },
+            let read = match first_read.take() {
+                Some(read) => Ok(Ok(read)),
+                None => timeout(Duration::from_millis(100), tls_stream.read(&mut temp_buffer)).await,
+            };
+            
+            match read {
+                Ok(Ok(0)) => return Err(GurtError::connection("Connection closed before the response was complete")),
+                Ok(Ok(n)) => buffer.extend_from_slice(&temp_buffer[..n]),
                 Ok(Err(e)) => return Err(GurtError::Connection(format!("Read error: {}", e))),
                 Err(_) => continue,
             }
-        }
+        };
         
-        let response = GurtResponse::parse_bytes(&buffer)?;
+        let response = GurtResponse::parse_bytes(&buffer[..response_len])?;
         
         self.return_connection_to_pool(pool_host, port, tls_stream);
         
@@ -410,6 +615,24 @@ impl GurtClient {
         self.send_request(&host, port, request).await
     }
     
+    /// Requests bytes `start..=end` of `url`, or everything from `start` onward when `end`
+    /// is `None`. Servers that support ranges answer 206 with a `content-range` header;
+    /// a 200 response means the whole resource was sent instead.
+    pub async fn get_range(&self, url: &str, start: u64, end: Option<u64>) -> Result<GurtResponse> {
+        let (host, port, path) = self.parse_gurt_url(url)?;
+        let range = match end {
+            Some(end) => format!("bytes={}-{}", start, end),
+            None => format!("bytes={}-", start),
+        };
+        
+        let request = GurtRequest::new(GurtMethod::GET, path.to_string())
+            .with_header("User-Agent", &self.config.user_agent)
+            .with_header("Accept", "*/*")
+            .with_header("Range", range);
+        
+        self.send_request(&host, port, request).await
+    }
+    
     pub async fn post(&self, url: &str, body: &str) -> Result<GurtResponse> {
         let (host, port, path) = self.parse_gurt_url(url)?;
         
@@ -433,6 +656,28 @@ impl GurtClient {
         self.send_request(&host, port, request).await
     }
 
+    /// Posts `data` as an `application/x-www-form-urlencoded` form.
+    pub async fn post_form<T: serde::Serialize>(&self, url: &str, data: &T) -> Result<GurtResponse> {
+        let (host, port, path) = self.parse_gurt_url(url)?;
+        
+        let request = GurtRequest::new(GurtMethod::POST, path.to_string())
+            .with_header("User-Agent", &self.config.user_agent)
+            .with_form_body(data)?;
+        
+        self.send_request(&host, port, request).await
+    }
+    
+    /// Posts a `multipart/form-data` form, e.g. one with file uploads.
+    pub async fn post_multipart(&self, url: &str, form: &Multipart) -> Result<GurtResponse> {
+        let (host, port, path) = self.parse_gurt_url(url)?;
+        
+        let request = GurtRequest::new(GurtMethod::POST, path.to_string())
+            .with_header("User-Agent", &self.config.user_agent)
+            .with_multipart_body(form);
+        
+        self.send_request(&host, port, request).await
+    }
+
     pub async fn put(&self, url: &str, body: &str) -> Result<GurtResponse> {
         let (host, port, path) = self.parse_gurt_url(url)?;
         
@@ -506,12 +751,117 @@ impl GurtClient {
         self.send_request(&host, port, request).await
     }
     
-    pub async fn send_request(&self, host: &str, port: u16, mut request: GurtRequest) -> Result<GurtResponse> {
+    /// Sends `request` and follows up to `max_redirects` redirects, returning the final response.
+    pub async fn send_request(&self, host: &str, port: u16, request: GurtRequest) -> Result<GurtResponse> {
+        let mut host = host.to_string();
+        let mut port = port;
+        let mut request = request;
+        let mut redirects = 0;
+        
+        loop {
+            let response = self.send_request_once(&host, port, request.clone()).await?;
+            
+            let Some(location) = response.header("location").filter(|_| is_redirect_status(response.status_code)) else {
+                return Ok(response);
+            };
+            
+            if redirects >= self.config.max_redirects {
+                return Err(GurtError::client(format!(
+                    "Too many redirects (max {}) while requesting {}{}", self.config.max_redirects, host, request.path
+                )));
+            }
+            redirects += 1;
+            
+            let current_url = format!("gurt://{}:{}{}", host, port, request.path);
+            let next_url = Url::parse(&current_url)
+                .and_then(|url| url.join(location))
+                .map_err(|e| GurtError::invalid_message(format!("Invalid redirect location '{}': {}", location, e)))?;
+            let (next_host, next_port, next_path) = self.parse_gurt_url(next_url.as_str())?;
+            
+            debug!("Following {} redirect to {}", response.status_code, next_url);
+            
+            // A different port may be a different service, so it counts as another origin too
+            let cross_origin = (next_host.as_str(), next_port) != (host.as_str(), port);
+            request = redirect_request(request, response.status_code, next_path, cross_origin);
+            host = next_host;
+            port = next_port;
+        }
+    }
+    
+    async fn send_request_once(&self, host: &str, port: u16, request: GurtRequest) -> Result<GurtResponse> {
+        // Cookies are part of the request the cache sees, so responses for one identity aren't reused for another
+        let request = self.with_cookies(host, request);
+        let Some(cache) = self.response_cache.as_ref().filter(|_| ResponseCache::is_cacheable_request(&request)) else {
+            return self.send_uncached(host, port, request).await;
+        };
+        
+        let url = format!("gurt://{}:{}{}", host, port, request.path);
+        
+        match cache.lookup(&url, &request) {
+            CacheLookup::Fresh(response) => {
+                debug!("Serving {} from cache", url);
+                Ok(response)
+            }
+            CacheLookup::Stale(validators) => {
+                let response = self.send_uncached(host, port, validators.condition(request.clone())).await?;
+                
+                if response.status_code != GurtStatusCode::NotModified.as_u16() {
+                    cache.store(&url, &request, &response).await;
+                    return Ok(response);
+                }
+                
+                match cache.refresh(&url, &request, &response).await {
+                    Some(cached) => {
+                        debug!("Revalidated cached {}", url);
+                        Ok(cached)
+                    }
+                    // The entry was evicted while revalidating, so fetch the body again
+                    None => {
+                        let response = self.send_uncached(host, port, request.clone()).await?;
+                        cache.store(&url, &request, &response).await;
+                        Ok(response)
+                    }
+                }
+            }
+            CacheLookup::Miss => {
+                let response = self.send_uncached(host, port, request.clone()).await?;
+                cache.store(&url, &request, &response).await;
+                Ok(response)
+            }
+        }
+    }
+    
+    async fn send_uncached(&self, host: &str, port: u16, mut request: GurtRequest) -> Result<GurtResponse> {
         let resolved_host = self.resolve_domain(host).await?;
         
-        request = request.with_header("Host", host);
+        request = self.with_accept_encoding(request.with_header("Host", host));
+        let request_path = request.path_without_query().to_string();
+        
+        let response = self.send_request_internal(&resolved_host, port, request, Some(host)).await?;
+        if let Some(jar) = &self.config.cookie_jar {
+            jar.store_set_cookies(host, &request_path, &response.headers);
+        }
         
-        self.send_request_internal(&resolved_host, port, request, Some(host)).await
+        if self.config.decompress {
+            compression::decompress_response(response, self.config.max_decompressed_size).await
+        } else {
+            Ok(response)
+        }
+    }
+    
+    fn with_accept_encoding(&self, request: GurtRequest) -> GurtRequest {
+        if self.config.decompress && request.header("accept-encoding").is_none() {
+            request.with_header("Accept-Encoding", compression::ACCEPT_ENCODING)
+        } else {
+            request
+        }
+    }
+    
+    fn with_cookies(&self, host: &str, request: GurtRequest) -> GurtRequest {
+        match &self.config.cookie_jar {
+            Some(jar) => jar.apply(host, request),
+            None => request,
+        }
     }
     
     fn parse_gurt_url(&self, url: &str) -> Result<(String, u16, String)> {
@@ -533,6 +883,22 @@ impl GurtClient {
     }
 
     pub async fn stream_request<HeadCb, ChunkCb>(&self,
+        host: &str,
+        port: u16,
+        request: GurtRequest,
+        on_head: HeadCb,
+        mut on_chunk: ChunkCb,
+    ) -> Result<()>
+    where
+        HeadCb: FnMut(&crate::message::GurtResponseHead) + Send,
+        ChunkCb: FnMut(&[u8]) -> bool + Send,
+    {
+        self.stream_response(host, port, request, on_head, |chunk| std::future::ready(on_chunk(&chunk))).await
+    }
+    
+    /// Like `stream_request`, but waits for `on_chunk` before reading on, so a slow
+    /// consumer holds back the server rather than buffering the body.
+    async fn stream_response<HeadCb, ChunkCb, ChunkFut>(&self,
         host: &str,
         port: u16,
         mut request: GurtRequest,
@@ -541,32 +907,41 @@ impl GurtClient {
     ) -> Result<()>
     where
         HeadCb: FnMut(&crate::message::GurtResponseHead) + Send,
-        ChunkCb: FnMut(&[u8]) -> bool + Send,
+        ChunkCb: FnMut(Vec<u8>) -> ChunkFut + Send,
+        ChunkFut: std::future::Future<Output = bool> + Send,
     {
         let resolved_host = self.resolve_domain(host).await?;
-        request = request.with_header("Host", host);
-
-        let mut tls_stream = self.get_pooled_connection(&resolved_host, port, Some(host)).await?;
+        request = self.with_cookies(host, self.with_accept_encoding(request.with_header("Host", host)));
+        let request_path = request.path_without_query().to_string();
 
-        let request_data = request.to_string();
-        tls_stream.write_all(request_data.as_bytes()).await
-            .map_err(|e| GurtError::connection(format!("Failed to write request: {}", e)))?;
-
-        let mut buffer: Vec<u8> = Vec::new();
         let mut temp_buffer = [0u8; 8192];
         let start_time = std::time::Instant::now();
+
+        let (mut tls_stream, first_read) = self.start_exchange(&resolved_host, port, Some(host), &request, &mut temp_buffer).await?;
+        let mut first_read = Some(first_read);
+        let mut buffer: Vec<u8> = Vec::new();
         let mut headers_parsed = false;
         let mut expected_body_length: Option<usize> = None;
         let mut headers_end_pos: Option<usize> = None;
         let mut head_emitted = false;
+        let mut chunked_decoder: Option<ChunkedDecoder> = None;
+        let mut content_decoder: Option<StreamDecoder> = None;
         let mut delivered: usize = 0;
+        let mut body_complete = false;
+        // Event streams stay open indefinitely, so only their head is subject to the request timeout
+        let mut event_stream = false;
 
         loop {
-            if start_time.elapsed() > self.config.request_timeout {
+            if !event_stream && start_time.elapsed() > self.config.request_timeout {
                 return Err(GurtError::timeout("Request timeout"));
             }
 
-            match timeout(self.config.read_timeout, tls_stream.read(&mut temp_buffer)).await {
+            let read = match first_read.take() {
+                Some(read) => Ok(Ok(read)),
+                None => timeout(self.config.read_timeout, tls_stream.read(&mut temp_buffer)).await,
+            };
+
+            match read {
                 Ok(Ok(0)) => {
                     if headers_parsed && !head_emitted {
                         return Err(GurtError::connection("Connection closed before response headers were fully received"));
@@ -596,16 +971,27 @@ impl GurtClient {
                                 if parts.len() > 2 { status_message = parts[2].to_string(); }
                             }
 
-                            let mut headers = std::collections::HashMap::new();
+                            let mut headers = crate::GurtHeaders::new();
                             for line in lines {
                                 if line.is_empty() { break; }
                                 if let Some(colon) = line.find(':') {
-                                    let key = line[..colon].trim().to_lowercase();
-                                    let value = line[colon+1..].trim().to_string();
-                                    if key == "content-length" { expected_body_length = value.parse().ok(); }
-                                    headers.insert(key, value);
+                                    headers.append(line[..colon].trim(), line[colon+1..].trim());
                                 }
                             }
+                            expected_body_length = headers.get("content-length").and_then(|value| value.parse().ok());
+                            if let Some(jar) = &self.config.cookie_jar {
+                                jar.store_set_cookies(host, &request_path, &headers);
+                            }
+
+                            let encoding = headers.get("content-encoding")
+                                .and_then(|value| ContentEncoding::parse(value))
+                                .filter(|_| self.config.decompress);
+                            if let Some(encoding) = encoding {
+                                // The decoded length is unknown until the body has been read
+                                headers.remove("content-encoding");
+                                headers.remove("content-length");
+                                content_decoder = Some(StreamDecoder::with_limit(encoding, self.config.max_decompressed_size));
+                            }
 
                             let head = crate::message::GurtResponseHead {
                                 version,
@@ -613,35 +999,41 @@ impl GurtClient {
                                 status_message,
                                 headers,
                             };
+                            if head.is_chunked() {
+                                chunked_decoder = Some(ChunkedDecoder::new());
+                            }
+                            event_stream = head.headers.get("content-type").is_some_and(|value| events::is_event_stream(value));
                             on_head(&head);
                             head_emitted = true;
-
-                            if let Some(end) = headers_end_pos {
-                                if buffer.len() > end {
-                                    let body_slice = &buffer[end..];
-                                    if !on_chunk(body_slice) {
-                                        return Err(GurtError::Cancelled);
-                                    }
-                                    delivered = body_slice.len();
-                                }
-                            }
                         }
-                    } else {
-                        if let Some(end) = headers_end_pos {
-                            let available = buffer.len().saturating_sub(end + delivered);
-                            if available > 0 {
-                                let start = end + delivered;
-                                let end_pos = end + delivered + available;
-                                if !on_chunk(&buffer[start..end_pos]) {
-                                    return Err(GurtError::Cancelled);
-                                }
-                                delivered += available;
-                            }
+                    }
+
+                    if let Some(end) = headers_end_pos {
+                        // Only the head stays buffered; body bytes are handed on as they arrive
+                        let fresh = buffer.split_off(end);
+                        if !fresh.is_empty() {
+                            let raw = match chunked_decoder.as_mut() {
+                                Some(decoder) => decoder.decode(&fresh)?,
+                                None => fresh,
+                            };
+                            delivered += raw.len();
+
+                            let body = match content_decoder.as_mut() {
+                                Some(decoder) if !raw.is_empty() => decoder.decode(&raw).await?,
+                                _ => raw,
+                            };
 
-                            if let Some(expected_len) = expected_body_length {
-                                if delivered >= expected_len { break; }
+                            if !body.is_empty() && !on_chunk(body).await {
+                                return Err(GurtError::Cancelled);
                             }
                         }
+
+                        body_complete = match (&chunked_decoder, expected_body_length) {
+                            (Some(decoder), _) => decoder.is_complete(),
+                            (None, Some(expected_len)) => delivered >= expected_len,
+                            (None, None) => false,
+                        };
+                        if body_complete { break; }
                     }
                 }
                 Ok(Err(e)) => return Err(GurtError::connection(format!("Read error: {}", e))),
@@ -649,15 +1041,142 @@ impl GurtClient {
             }
         }
 
-        if let (Some(end), Some(expected_len)) = (headers_end_pos, expected_body_length) {
-            if delivered >= expected_len {
-                self.return_connection_to_pool(&resolved_host, port, tls_stream);
+        if let Some(decoder) = content_decoder {
+            let rest = decoder.finish().await?;
+            if !rest.is_empty() && !on_chunk(rest).await {
+                return Err(GurtError::Cancelled);
             }
         }
 
+        if body_complete {
+            self.return_connection_to_pool(&resolved_host, port, tls_stream);
+        }
+
         Ok(())
     }
     
+    /// Requests an event stream from `url` and yields its events as they arrive.
+    pub async fn events(&self, url: &str) -> Result<EventStream> {
+        let (host, port, path) = self.parse_gurt_url(url)?;
+        
+        let request = GurtRequest::new(GurtMethod::GET, path)
+            .with_header("User-Agent", &self.config.user_agent);
+        
+        self.open_events(&host, port, request).await
+    }
+    
+    /// Sends `request` and returns its event stream once the response head arrives,
+    /// e.g. to add an `authorization` or `last-event-id` header. Fails if the server
+    /// answers with anything other than a successful event stream.
+    pub async fn open_events(&self, host: &str, port: u16, request: GurtRequest) -> Result<EventStream> {
+        let request = request.with_header("accept", events::EVENT_STREAM_CONTENT_TYPE);
+        let (head_sender, head_receiver) = tokio::sync::oneshot::channel();
+        let (chunk_sender, chunk_receiver) = tokio::sync::mpsc::channel(events::CLIENT_BUFFERED_CHUNKS);
+        
+        let client = self.clone();
+        let host_owned = host.to_string();
+        let task = tokio::spawn(async move {
+            let mut head_sender = Some(head_sender);
+            client.stream_response(&host_owned, port, request,
+                |head| {
+                    if let Some(sender) = head_sender.take() {
+                        let _ = sender.send(head.clone());
+                    }
+                },
+                |chunk| {
+                    let chunk_sender = chunk_sender.clone();
+                    async move { chunk_sender.send(chunk).await.is_ok() }
+                },
+            ).await
+        });
+        
+        let head = match head_receiver.await {
+            Ok(head) => head,
+            Err(_) => {
+                // The request failed before a response head arrived
+                task.await.map_err(|e| GurtError::connection(format!("Event stream task failed: {}", e)))??;
+                return Err(GurtError::connection("Connection closed before response headers were received"));
+            }
+        };
+        
+        let stream = EventStream::new(head, chunk_receiver, task);
+        let head = stream.head();
+        if !(200..300).contains(&head.status_code) {
+            return Err(GurtError::client(format!(
+                "Event stream request to {} failed: {} {}", host, head.status_code, head.status_message
+            )));
+        }
+        if !head.headers.get("content-type").is_some_and(|value| events::is_event_stream(value)) {
+            return Err(GurtError::client(format!("Response from {} is not an event stream", host)));
+        }
+        
+        debug!("Opened event stream from {}:{}", host, port);
+        Ok(stream)
+    }
+    
+    /// Opens a [`GurtChannel`] to a route registered with `GurtServer::channel`.
+    pub async fn connect_channel(&self, url: &str) -> Result<GurtChannel> {
+        let (host, port, path) = self.parse_gurt_url(url)?;
+        
+        let request = channel::upgrade_request(&path)
+            .with_header("User-Agent", &self.config.user_agent);
+        
+        self.open_channel(&host, port, request).await
+    }
+    
+    /// Sends `request` as a channel upgrade, e.g. to add an `authorization` header,
+    /// and returns the channel once the server accepts it. Channels get a connection
+    /// of their own, which is never returned to the pool.
+    pub async fn open_channel(&self, host: &str, port: u16, request: GurtRequest) -> Result<GurtChannel> {
+        let resolved_host = self.resolve_domain(host).await?;
+        let request = self.with_cookies(host, request
+            .with_header("Host", host)
+            .with_header("upgrade", channel::CHANNEL_PROTOCOL)
+            .with_header("connection", "upgrade"));
+        let request_path = request.path_without_query().to_string();
+        
+        let mut tls_stream = self.perform_handshake(&resolved_host, port, Some(host)).await?;
+        tls_stream.write_all(&request.to_bytes()).await
+            .map_err(|e| GurtError::connection(format!("Failed to write request: {}", e)))?;
+        
+        let mut buffer = Vec::new();
+        let mut temp_buffer = [0u8; 8192];
+        let deadline = tokio::time::Instant::now() + self.config.request_timeout;
+        
+        let response_len = loop {
+            match frame_message(&buffer, true) {
+                MessageFrame::Complete(len) => break len,
+                MessageFrame::Incomplete => {}
+                MessageFrame::TooLarge => return Err(GurtError::invalid_message("Upgrade response too large")),
+                MessageFrame::Invalid(reason) => return Err(GurtError::invalid_message(reason)),
+            }
+            
+            let read = tokio::time::timeout_at(deadline, tls_stream.read(&mut temp_buffer)).await
+                .map_err(|_| GurtError::timeout("Timed out waiting for channel upgrade"))?
+                .map_err(|e| GurtError::connection(format!("Read error: {}", e)))?;
+            if read == 0 {
+                return Err(GurtError::connection("Connection closed before the upgrade response"));
+            }
+            buffer.extend_from_slice(&temp_buffer[..read]);
+        };
+        
+        let response = GurtResponse::parse_bytes(&buffer[..response_len])?;
+        if let Some(jar) = &self.config.cookie_jar {
+            jar.store_set_cookies(host, &request_path, &response.headers);
+        }
+        
+        let accepted = response.status_code == GurtStatusCode::SwitchingProtocols.as_u16()
+            && response.header("upgrade").is_some_and(|value| value.eq_ignore_ascii_case(channel::CHANNEL_PROTOCOL));
+        if !accepted {
+            return Err(GurtError::client(format!(
+                "Channel upgrade to {}{} refused: {} {}", host, request.path, response.status_code, response.status_message
+            )));
+        }
+        
+        debug!("Opened channel to {}:{}{}", host, port, request.path);
+        Ok(GurtChannel::new(tls_stream, buffer.split_off(response_len)))
+    }
+    
     async fn resolve_domain(&self, domain: &str) -> Result<String> {
         match self.dns_cache.lock() {
             Ok(cache) => {
@@ -754,14 +1273,112 @@ impl Clone for GurtClient {
         Self {
             config: self.config.clone(),
             connection_pool: self.connection_pool.clone(),
+            pipelined_pool: self.pipelined_pool.clone(),
+            pipelined_connects: self.pipelined_connects.clone(),
             dns_cache: self.dns_cache.clone(),
+            response_cache: self.response_cache.clone(),
+            handshakes: self.handshakes.clone(),
+            direct_tls_failures: self.direct_tls_failures.clone(),
+            tls_config: self.tls_config.clone(),
+        }
+    }
+}
+
+fn build_tls_client_config(config: &GurtClientConfig) -> Result<TlsClientConfig> {
+    let mut root_store = RootCertStore::empty();
+    
+    let cert_result = rustls_native_certs::load_native_certs();
+    let mut added = 0;
+    for cert in cert_result.certs {
+        if root_store.add(cert).is_ok() {
+            added += 1;
+        }
+    }
+    
+    for ca_cert_pem in &config.custom_ca_certificates {
+        let mut pem_bytes = ca_cert_pem.as_bytes();
+        let cert_iter = rustls_pemfile::certs(&mut pem_bytes);
+        for cert_result in cert_iter {
+            match cert_result {
+                Ok(cert) => {
+                    if root_store.add(cert).is_ok() {
+                        added += 1;
+                        debug!("Added custom CA certificate");
+                    }
+                }
+                Err(e) => {
+                    debug!("Failed to parse CA certificate: {}", e);
+                }
+            }
+        }
+    }
+    
+    if added == 0 {
+        return Err(GurtError::Crypto("No valid certificates found (system or custom)".to_string()));
+    }
+    
+    let builder = TlsClientConfig::builder().with_root_certificates(root_store);
+    let mut client_config = match (&config.client_certificate, &config.client_key) {
+        (Some(certificate), Some(key)) => {
+            let certs = crypto::parse_certificates(certificate.as_bytes())?;
+            let key = crypto::parse_private_key(key.as_bytes())?;
+            builder.with_client_auth_cert(certs, key)
+                .map_err(|e| GurtError::Crypto(format!("Invalid client certificate: {}", e)))?
+        }
+        (None, None) => builder.with_no_client_auth(),
+        _ => return Err(GurtError::Crypto("A client certificate requires both client_certificate and client_key".to_string())),
+    };
+    
+    client_config.alpn_protocols = vec![GURT_ALPN.to_vec()];
+    client_config.resumption = Resumption::in_memory_sessions(TLS_SESSION_CACHE_SIZE);
+    
+    Ok(client_config)
+}
+
+fn is_redirect_status(status_code: u16) -> bool {
+    matches!(
+        GurtStatusCode::from_u16(status_code),
+        GurtStatusCode::MovedPermanently
+            | GurtStatusCode::Found
+            | GurtStatusCode::SeeOther
+            | GurtStatusCode::TemporaryRedirect
+            | GurtStatusCode::PermanentRedirect
+    )
+}
+
+/// Builds the request to send to a redirect target.
+///
+/// 303 turns any method but HEAD into a body-less GET, and so do 301 and 302 for POST.
+/// 307 and 308 resend the request unchanged. Credentials are dropped when the host or
+/// port changes.
+fn redirect_request(mut request: GurtRequest, status_code: u16, path: String, cross_origin: bool) -> GurtRequest {
+    let becomes_get = match GurtStatusCode::from_u16(status_code) {
+        GurtStatusCode::SeeOther => request.method != GurtMethod::HEAD,
+        GurtStatusCode::MovedPermanently | GurtStatusCode::Found => request.method == GurtMethod::POST,
+        _ => false,
+    };
+    
+    if becomes_get {
+        request.method = GurtMethod::GET;
+        request.body.clear();
+        for header in ["content-type", "content-length", "transfer-encoding"] {
+            request.headers.remove(header);
         }
     }
+    
+    if cross_origin {
+        request.headers.remove("authorization");
+        request.headers.remove("cookie");
+    }
+    
+    request.path = path;
+    request
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{spawn_server, TestPki};
     
     #[tokio::test]
     async fn test_url_parsing() {
@@ -778,6 +1395,36 @@ mod tests {
         assert_eq!(path, "/api/v1");
     }
     
+    #[test]
+    fn test_redirect_request_rewrites() {
+        let post = GurtRequest::new(GurtMethod::POST, "/form".to_string())
+            .with_header("Content-Type", "text/plain")
+            .with_header("Authorization", "Bearer token")
+            .with_string_body("data");
+        
+        let see_other = redirect_request(post.clone(), 303, "/done".to_string(), false);
+        assert_eq!(see_other.method, GurtMethod::GET);
+        assert_eq!(see_other.path, "/done");
+        assert!(see_other.body.is_empty());
+        assert!(see_other.header("content-type").is_none());
+        assert!(see_other.header("authorization").is_some());
+        
+        let temporary = redirect_request(post.clone(), 307, "/form2".to_string(), true);
+        assert_eq!(temporary.method, GurtMethod::POST);
+        assert_eq!(temporary.body, b"data");
+        assert!(temporary.header("authorization").is_none());
+        
+        let head = GurtRequest::new(GurtMethod::HEAD, "/".to_string());
+        assert_eq!(redirect_request(head, 303, "/".to_string(), false).method, GurtMethod::HEAD);
+        
+        let put = GurtRequest::new(GurtMethod::PUT, "/".to_string());
+        assert_eq!(redirect_request(put, 301, "/".to_string(), false).method, GurtMethod::PUT);
+        
+        assert!(is_redirect_status(308));
+        assert!(!is_redirect_status(304));
+        assert!(!is_redirect_status(200));
+    }
+    
     #[test]
     fn test_connection_pooling_config() {
         let config = GurtClientConfig {
@@ -791,6 +1438,224 @@ mod tests {
         assert_eq!(client.config.max_connections_per_host, 8);
     }
     
+    #[tokio::test]
+    async fn test_pipelined_burst_shares_one_connection() {
+        let pki = TestPki::new();
+        let server = pki.server()
+            .get("/", |_ctx| async {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok(GurtResponse::ok())
+            });
+        let addr = spawn_server(server).await;
+        
+        let client = GurtClient::with_config(GurtClientConfig {
+            enable_pipelining: true,
+            max_in_flight_per_connection: 8,
+            ..pki.client_config()
+        });
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        let mut burst = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let (client, url) = (client.clone(), url.clone());
+            burst.spawn(async move { client.get(&url).await });
+        }
+        while let Some(response) = burst.join_next().await {
+            assert_eq!(response.unwrap().unwrap().status_code, 200);
+        }
+        
+        let key = ConnectionKey { host: "localhost".to_string(), port: addr.port() };
+        assert_eq!(client.pipelined_pool.lock().unwrap().get(&key).map(Vec::len), Some(1));
+    }
+    
+    #[tokio::test]
+    async fn test_request_after_server_idle_timeout() {
+        let pki = TestPki::new();
+        let server = pki.server()
+            .with_timeouts(Duration::from_secs(5), Duration::from_secs(5), Duration::from_millis(200))
+            .get("/", |_ctx| async { Ok(GurtResponse::ok().with_string_body("hello")) });
+        let addr = spawn_server(server).await;
+        
+        // Keep pooled connections for longer than the server keeps them open
+        let client = GurtClient::with_config(GurtClientConfig {
+            pool_idle_timeout: Duration::from_secs(30),
+            ..pki.client_config()
+        });
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        assert_eq!(client.get(&url).await.unwrap().text().unwrap(), "hello");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(client.get(&url).await.unwrap().text().unwrap(), "hello");
+    }
+    
+    #[tokio::test]
+    async fn test_non_idempotent_request_not_resent() {
+        let pki = TestPki::new();
+        let server = pki.server()
+            .with_timeouts(Duration::from_secs(5), Duration::from_secs(5), Duration::from_millis(200))
+            .get("/", |_ctx| async { Ok(GurtResponse::ok()) })
+            .post("/", |_ctx| async { Ok(GurtResponse::ok()) });
+        let addr = spawn_server(server).await;
+        
+        let client = GurtClient::with_config(GurtClientConfig {
+            pool_idle_timeout: Duration::from_secs(30),
+            ..pki.client_config()
+        });
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        assert!(client.get(&url).await.is_ok());
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(client.post(&url, "once").await.is_err());
+        assert!(client.post(&url, "once").await.is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_head_response_with_content_length() {
+        let pki = TestPki::new();
+        let server = pki.server()
+            .head("/", |_ctx| async { Ok(GurtResponse::ok().with_header("content-length", "512")) });
+        let addr = spawn_server(server).await;
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        let client = GurtClient::with_config(pki.client_config());
+        let response = timeout(Duration::from_secs(2), client.head(&url)).await.unwrap().unwrap();
+        assert_eq!(response.status_code, 200);
+        assert!(response.body.is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_redirect_to_other_port_drops_credentials() {
+        let pki = TestPki::new();
+        let target = pki.server()
+            .get("/", |ctx| {
+                let authorized = ctx.header("authorization").is_some();
+                async move { Ok(GurtResponse::ok().with_string_body(authorized.to_string())) }
+            });
+        let target = spawn_server(target).await;
+        
+        let location = format!("gurt://localhost:{}/", target.port());
+        let origin = pki.server()
+            .get("/", move |_ctx| {
+                let location = location.clone();
+                async move { Ok(GurtResponse::new(GurtStatusCode::Found).with_header("location", location)) }
+            });
+        let origin = spawn_server(origin).await;
+        
+        let client = GurtClient::with_config(pki.client_config());
+        let request = GurtRequest::new(GurtMethod::GET, "/".to_string()).with_header("authorization", "Bearer secret");
+        let response = client.send_request("localhost", origin.port(), request).await.unwrap();
+        assert_eq!(response.text().unwrap(), "false");
+    }
+    
+    #[tokio::test]
+    async fn test_decompressed_size_limit() {
+        let bomb = compression::compress(ContentEncoding::Gzip, &vec![0u8; 4 * 1024 * 1024]).await.unwrap();
+        let pki = TestPki::new();
+        let server = pki.server()
+            .get("/", move |_ctx| {
+                let bomb = bomb.clone();
+                async move { Ok(GurtResponse::ok().with_header("content-encoding", "gzip").with_body(bomb)) }
+            });
+        let addr = spawn_server(server).await;
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        let client = GurtClient::with_config(GurtClientConfig {
+            max_decompressed_size: 1024 * 1024,
+            ..pki.client_config()
+        });
+        assert!(client.get(&url).await.is_err());
+        
+        let streamed = client.stream_request("localhost", addr.port(), GurtRequest::new(GurtMethod::GET, "/".to_string()), |_| {}, |_| true).await;
+        assert!(streamed.is_err());
+        
+        let client = GurtClient::with_config(pki.client_config());
+        assert_eq!(client.get(&url).await.unwrap().body.len(), 4 * 1024 * 1024);
+    }
+    
+    #[tokio::test]
+    async fn test_cache_not_shared_across_cookies() {
+        let pki = TestPki::new();
+        let server = pki.server()
+            .get("/", |ctx| {
+                let cookie = ctx.header("cookie").cloned().unwrap_or_default();
+                async move {
+                    Ok(GurtResponse::ok()
+                        .with_header("cache-control", "max-age=60")
+                        .with_string_body(cookie))
+                }
+            });
+        let addr = spawn_server(server).await;
+        
+        let jar = Arc::new(CookieJar::new());
+        let client = GurtClient::with_config(GurtClientConfig {
+            cache: Some(CacheConfig::default()),
+            cookie_jar: Some(jar.clone()),
+            ..pki.client_config()
+        });
+        let url = format!("gurt://localhost:{}/", addr.port());
+        
+        assert_eq!(client.get(&url).await.unwrap().text().unwrap(), "");
+        jar.store("localhost", "/", crate::cookie::Cookie::new("session", "alice"));
+        assert_eq!(client.get(&url).await.unwrap().text().unwrap(), "session=alice");
+        jar.store("localhost", "/", crate::cookie::Cookie::new("session", "bob"));
+        assert_eq!(client.get(&url).await.unwrap().text().unwrap(), "session=bob");
+    }
+    
+    #[test]
+    fn test_direct_tls_backoff() {
+        let client = GurtClient::new();
+        let key = ConnectionKey { host: "example.web".to_string(), port: 4878 };
+        assert!(!client.should_try_direct_tls("example.web", 4878));
+        
+        let mut handshake = Handshake::default();
+        handshake.capabilities.direct_tls = true;
+        client.handshakes.lock().unwrap().insert(key.clone(), handshake);
+        assert!(client.should_try_direct_tls("example.web", 4878));
+        
+        client.direct_tls_failures.lock().unwrap().insert(key.clone(), std::time::Instant::now());
+        assert!(!client.should_try_direct_tls("example.web", 4878));
+        assert!(client.negotiated_handshake("example.web", 4878).unwrap().capabilities.direct_tls);
+        
+        let expired = std::time::Instant::now() - DIRECT_TLS_BACKOFF - Duration::from_secs(1);
+        client.direct_tls_failures.lock().unwrap().insert(key, expired);
+        assert!(client.should_try_direct_tls("example.web", 4878));
+    }
+    
+    #[test]
+    fn test_tls_config_shared_between_clones() {
+        let client = GurtClient::new();
+        let config = crate::crypto::TlsConfig::new_client().unwrap().client_config.unwrap();
+        *client.tls_config.lock().unwrap() = Some(config.clone());
+    
+        let clone = client.clone();
+        assert!(Arc::ptr_eq(&clone.tls_client_config().unwrap(), &config));
+        assert!(Arc::ptr_eq(&client.tls_client_config().unwrap(), &config));
+    }
+    
+    #[tokio::test]
+    async fn test_pipelined_connection_selection() {
+        let config = GurtClientConfig {
+            enable_pipelining: true,
+            max_connections_per_host: 2,
+            max_in_flight_per_connection: 1,
+            ..Default::default()
+        };
+        let client = GurtClient::with_config(config);
+        let key = ConnectionKey {
+            host: "example.com".to_string(),
+            port: 4878,
+        };
+        
+        assert!(client.select_pipelined_connection(&key).is_none());
+        
+        let (stream, _server_side) = tokio::io::duplex(1024);
+        let connection = PipelinedConnection::spawn(stream, 1);
+        client.pipelined_pool.lock().unwrap().insert(key.clone(), vec![connection]);
+        
+        let selected = client.select_pipelined_connection(&key).unwrap();
+        assert_eq!(selected.available_slots(), 1);
+    }
+    
     #[test]
     fn test_connection_key() {
         let key1 = ConnectionKey {
@@ -816,8 +1681,10 @@ mod tests {
     async fn test_host_header_preserved_with_dns_resolution() {
         use crate::message::{GurtMethod, GurtRequest};
         
-        let mut config = GurtClientConfig::default();
-        config.enable_connection_pooling = false;
+        let config = GurtClientConfig {
+            enable_connection_pooling: false,
+            ..Default::default()
+        };
         let client = GurtClient::with_config(config);
         
         {
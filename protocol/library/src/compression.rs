@@ -0,0 +1,343 @@
+use crate::{GurtError, GurtResponse, GurtBodyStream, Result, protocol::MAX_MESSAGE_SIZE};
+use async_compression::tokio::{bufread, write};
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+/// The `accept-encoding` value sent by `GurtClient`, listing every supported coding.
+pub const ACCEPT_ENCODING: &str = "zstd, br, gzip";
+
+/// A content coding applied to a message body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+    Gzip,
+    Brotli,
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Every supported coding, most preferred first.
+    pub const ALL: [ContentEncoding; 3] = [ContentEncoding::Zstd, ContentEncoding::Brotli, ContentEncoding::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentEncoding::Gzip => "gzip",
+            ContentEncoding::Brotli => "br",
+            ContentEncoding::Zstd => "zstd",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(ContentEncoding::Gzip),
+            "br" => Some(ContentEncoding::Brotli),
+            "zstd" => Some(ContentEncoding::Zstd),
+            _ => None,
+        }
+    }
+}
+
+/// Picks the coding to answer an `accept-encoding` header with, from `supported` in order
+/// of preference. Codings with a higher `q` value win; `*` covers codings not listed.
+pub fn negotiate(accept_encoding: &str, supported: &[ContentEncoding]) -> Option<ContentEncoding> {
+    let mut wildcard = None;
+    let mut listed = Vec::new();
+
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let name = params.next().unwrap_or_default().trim();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name == "*" {
+            wildcard = Some(quality);
+        } else if let Some(encoding) = ContentEncoding::parse(name) {
+            listed.push((encoding, quality));
+        }
+    }
+
+    let quality_of = |encoding: ContentEncoding| {
+        listed.iter()
+            .find(|(listed, _)| *listed == encoding)
+            .map(|(_, quality)| *quality)
+            .or(wildcard)
+            .unwrap_or(0.0)
+    };
+
+    let mut best: Option<(ContentEncoding, f32)> = None;
+    for &encoding in supported {
+        let quality = quality_of(encoding);
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// Whether bodies of `content_type` are text-like and worth compressing. Event streams
+/// are not, as the encoder would hold events back until it has enough input.
+pub fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+
+    (mime.starts_with("text/") && mime != crate::events::EVENT_STREAM_CONTENT_TYPE)
+        || mime.ends_with("+json")
+        || mime.ends_with("+xml")
+        || matches!(
+            mime.as_str(),
+            "application/json" | "application/javascript" | "application/xml"
+                | "application/lua" | "application/x-lua" | "image/svg+xml"
+        )
+}
+
+pub async fn compress(encoding: ContentEncoding, data: &[u8]) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    encoder(encoding, data).read_to_end(&mut output).await?;
+    Ok(output)
+}
+
+/// Decompresses `data`, failing if it decodes to more than `MAX_MESSAGE_SIZE` bytes.
+pub async fn decompress(encoding: ContentEncoding, data: &[u8]) -> Result<Vec<u8>> {
+    decompress_limited(encoding, data, MAX_MESSAGE_SIZE).await
+}
+
+/// Decompresses `data`, failing if it decodes to more than `max_size` bytes.
+pub async fn decompress_limited(encoding: ContentEncoding, data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    let mut decoder = StreamDecoder::with_limit(encoding, max_size);
+    let mut output = decoder.decode(data).await?;
+    output.extend(decoder.finish().await?);
+    Ok(output)
+}
+
+/// Compresses `stream` as it is read.
+pub fn compress_stream(encoding: ContentEncoding, stream: GurtBodyStream) -> Result<GurtBodyStream> {
+    Ok(GurtBodyStream::from_reader(encoder(encoding, BufReader::new(stream.reader()?))))
+}
+
+fn encoder<'a, R>(encoding: ContentEncoding, reader: R) -> Box<dyn AsyncRead + Send + Unpin + 'a>
+where
+    R: AsyncBufRead + Send + Unpin + 'a,
+{
+    match encoding {
+        ContentEncoding::Gzip => Box::new(bufread::GzipEncoder::new(reader)),
+        ContentEncoding::Brotli => Box::new(bufread::BrotliEncoder::new(reader)),
+        ContentEncoding::Zstd => Box::new(bufread::ZstdEncoder::new(reader)),
+    }
+}
+
+/// Decodes a compressed body piece by piece as it arrives. Decoding fails once the
+/// total output passes its limit, which is `MAX_MESSAGE_SIZE` for `new`.
+pub struct StreamDecoder {
+    inner: Box<dyn AsyncWrite + Send + Unpin>,
+    output: Arc<Mutex<Vec<u8>>>,
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: ContentEncoding) -> Self {
+        Self::with_limit(encoding, MAX_MESSAGE_SIZE)
+    }
+
+    /// A decoder whose output may total at most `max_size` bytes.
+    pub fn with_limit(encoding: ContentEncoding, max_size: usize) -> Self {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let sink = SharedSink { output: output.clone(), written: 0, limit: max_size };
+        let inner: Box<dyn AsyncWrite + Send + Unpin> = match encoding {
+            ContentEncoding::Gzip => Box::new(write::GzipDecoder::new(sink)),
+            ContentEncoding::Brotli => Box::new(write::BrotliDecoder::new(sink)),
+            ContentEncoding::Zstd => Box::new(write::ZstdDecoder::new(sink)),
+        };
+        Self { inner, output }
+    }
+
+    /// Feeds `chunk` in and returns whatever could be decoded so far.
+    pub async fn decode(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        self.inner.write_all(chunk).await.map_err(decode_error)?;
+        self.inner.flush().await.map_err(decode_error)?;
+        Ok(self.take_output())
+    }
+
+    /// Ends the input, failing if the compressed data was truncated.
+    pub async fn finish(mut self) -> Result<Vec<u8>> {
+        self.inner.shutdown().await.map_err(decode_error)?;
+        Ok(self.take_output())
+    }
+
+    fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.output.lock().unwrap())
+    }
+}
+
+fn decode_error(error: std::io::Error) -> GurtError {
+    GurtError::invalid_message(format!("Failed to decode body: {}", error))
+}
+
+struct SharedSink {
+    output: Arc<Mutex<Vec<u8>>>,
+    written: usize,
+    limit: usize,
+}
+
+impl AsyncWrite for SharedSink {
+    fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let sink = self.get_mut();
+        if sink.written + buf.len() > sink.limit {
+            return Poll::Ready(Err(std::io::Error::other(format!("decoded body is larger than {} bytes", sink.limit))));
+        }
+
+        sink.written += buf.len();
+        sink.output.lock().unwrap().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Compresses the body of `response` with `encoding` and sets `content-encoding`.
+/// Streamed bodies stay streamed and are sent chunked, since their compressed size
+/// is not known up front. A strong `etag` is made weak, as the compressed bytes differ
+/// from those it was computed for.
+pub async fn compress_response(mut response: GurtResponse, encoding: ContentEncoding) -> Result<GurtResponse> {
+    response = match response.body_stream.take() {
+        Some(stream) => response.with_stream_body(compress_stream(encoding, stream)?, None),
+        None => {
+            let body = compress(encoding, &response.body).await?;
+            response.headers.remove("content-length");
+            response.with_body(body)
+        }
+    };
+
+    if let Some(etag) = response.header("etag").filter(|etag| !etag.starts_with("W/")) {
+        let weak = format!("W/{}", etag);
+        response.headers.insert("etag".to_string(), weak);
+    }
+
+    Ok(add_vary(response, "accept-encoding").with_header("content-encoding", encoding.as_str()))
+}
+
+/// Undoes the `content-encoding` of an in-memory response body, failing if it decodes to
+/// more than `max_size` bytes. Responses with no coding, one that is not supported, or
+/// no body (e.g. to HEAD) are returned unchanged.
+pub async fn decompress_response(mut response: GurtResponse, max_size: usize) -> Result<GurtResponse> {
+    let encoding = response.header("content-encoding").and_then(|value| ContentEncoding::parse(value));
+    let Some(encoding) = encoding.filter(|_| !response.body.is_empty()) else {
+        return Ok(response);
+    };
+
+    response.body = decompress_limited(encoding, &response.body, max_size).await?;
+    response.headers.remove("content-encoding");
+    if response.header("content-length").is_some() {
+        response.headers.insert("content-length".to_string(), response.body.len().to_string());
+    }
+    Ok(response)
+}
+
+/// Adds `header` to the `vary` list of `response` unless it is already there.
+pub fn add_vary(response: GurtResponse, header: &str) -> GurtResponse {
+    let vary = match response.header("vary") {
+        Some(vary) if vary.split(',').any(|name| name.trim().eq_ignore_ascii_case(header) || name.trim() == "*") => return response,
+        Some(vary) => format!("{}, {}", vary, header),
+        None => header.to_string(),
+    };
+    response.with_header("vary", vary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate() {
+        let all = &ContentEncoding::ALL;
+
+        assert_eq!(negotiate("gzip, br", all), Some(ContentEncoding::Brotli));
+        assert_eq!(negotiate("gzip;q=1.0, br;q=0.5", all), Some(ContentEncoding::Gzip));
+        assert_eq!(negotiate("*", all), Some(ContentEncoding::Zstd));
+        assert_eq!(negotiate("*, zstd;q=0", all), Some(ContentEncoding::Brotli));
+        assert_eq!(negotiate("identity", all), None);
+        assert_eq!(negotiate("gzip;q=0", all), None);
+        assert_eq!(negotiate("zstd", &[ContentEncoding::Gzip]), None);
+
+        assert!(is_compressible("text/html; charset=utf-8"));
+        assert!(is_compressible("application/lua"));
+        assert!(!is_compressible("image/png"));
+        assert!(!is_compressible("text/event-stream"));
+    }
+
+    #[tokio::test]
+    async fn test_compression_round_trip() {
+        let data = "<p>hello</p>".repeat(200).into_bytes();
+
+        for encoding in ContentEncoding::ALL {
+            let compressed = compress(encoding, &data).await.unwrap();
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress(encoding, &compressed).await.unwrap(), data);
+
+            let mut decoder = StreamDecoder::new(encoding);
+            let mut decoded = Vec::new();
+            for chunk in compressed.chunks(7) {
+                decoded.extend(decoder.decode(chunk).await.unwrap());
+            }
+            decoded.extend(decoder.finish().await.unwrap());
+            assert_eq!(decoded, data);
+
+            let stream = compress_stream(encoding, GurtBodyStream::from_bytes(data.clone())).unwrap();
+            let streamed = stream.collect().await.unwrap();
+            assert_eq!(decompress(encoding, &streamed).await.unwrap(), data);
+        }
+
+        let truncated = compress(ContentEncoding::Gzip, &data).await.unwrap();
+        assert!(decompress(ContentEncoding::Gzip, &truncated[..truncated.len() / 2]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decompression_limit() {
+        let bomb = vec![0u8; 4 * 1024 * 1024];
+
+        for encoding in ContentEncoding::ALL {
+            let compressed = compress(encoding, &bomb).await.unwrap();
+            assert!(compressed.len() < 64 * 1024);
+            assert_eq!(decompress_limited(encoding, &compressed, bomb.len()).await.unwrap().len(), bomb.len());
+            assert!(decompress_limited(encoding, &compressed, 1024 * 1024).await.is_err());
+
+            let mut decoder = StreamDecoder::with_limit(encoding, 1024 * 1024);
+            let mut decoded = 0;
+            let mut failed = false;
+            for chunk in compressed.chunks(1024) {
+                match decoder.decode(chunk).await {
+                    Ok(output) => decoded += output.len(),
+                    Err(_) => {
+                        failed = true;
+                        break;
+                    }
+                }
+            }
+            assert!(failed);
+            assert!(decoded <= 1024 * 1024);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compress_response() {
+        let response = GurtResponse::ok()
+            .with_header("content-type", "text/html")
+            .with_header("etag", "\"abc\"")
+            .with_string_body("<p>hello</p>".repeat(50));
+
+        let compressed = compress_response(response, ContentEncoding::Gzip).await.unwrap();
+        assert_eq!(compressed.header("content-encoding").unwrap(), "gzip");
+        assert_eq!(compressed.header("vary").unwrap(), "accept-encoding");
+        assert_eq!(compressed.header("etag").unwrap(), "W/\"abc\"");
+
+        let decompressed = decompress_response(compressed, MAX_MESSAGE_SIZE).await.unwrap();
+        assert!(decompressed.header("content-encoding").is_none());
+        assert_eq!(decompressed.text().unwrap(), "<p>hello</p>".repeat(50));
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{GurtRequest, GurtResponse, message::GurtMethod};
+use chrono::{DateTime, Utc};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Formats a time as an HTTP date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn format_http_date(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).format(HTTP_DATE_FORMAT).to_string()
+}
+
+pub fn parse_http_date(value: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc2822(value.trim()).ok().map(SystemTime::from)
+}
+
+/// Checks an `If-None-Match` header value against `etag` using weak comparison.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+
+    if_none_match.split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// The validators of a resource version: an entity tag and a modification time.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Derives validators from a file's size and modification time, so they change
+    /// whenever the file does.
+    pub fn from_metadata(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        let modified_nanos = modified
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|since_epoch| since_epoch.as_nanos())
+            .unwrap_or_default();
+
+        Self {
+            etag: Some(format!("\"{:x}-{:x}\"", metadata.len(), modified_nanos)),
+            last_modified: modified,
+        }
+    }
+
+    /// Reads the validators a server sent with `response`.
+    pub fn from_response(response: &GurtResponse) -> Self {
+        Self {
+            etag: response.header("etag").cloned(),
+            last_modified: response.header("last-modified").and_then(|value| parse_http_date(value)),
+        }
+    }
+
+    /// Makes `request` conditional on these validators, so that an unchanged resource
+    /// comes back as 304 instead of a full body.
+    pub fn condition(&self, mut request: GurtRequest) -> GurtRequest {
+        if let Some(etag) = &self.etag {
+            request = request.with_header("if-none-match", etag.as_str());
+        }
+        if let Some(modified) = self.last_modified {
+            request = request.with_header("if-modified-since", format_http_date(modified));
+        }
+        request
+    }
+
+    /// Whether the client's cached copy is current, meaning `request` can be answered with 304.
+    ///
+    /// `If-None-Match` takes precedence; `If-Modified-Since` is only consulted without it,
+    /// and only for GET and HEAD.
+    pub fn is_fresh(&self, request: &GurtRequest) -> bool {
+        if let Some(if_none_match) = request.header("if-none-match") {
+            return self.etag.as_deref().is_some_and(|etag| etag_matches(if_none_match, etag));
+        }
+
+        if !matches!(request.method, GurtMethod::GET | GurtMethod::HEAD) {
+            return false;
+        }
+
+        match (request.header("if-modified-since").and_then(|value| parse_http_date(value)), self.last_modified) {
+            // HTTP dates have one-second precision
+            (Some(since), Some(modified)) => truncate_to_seconds(modified) <= since,
+            _ => false,
+        }
+    }
+
+    /// Whether a `Range` in `request` may be honoured. Without `If-Range` it always may;
+    /// with it, the range only applies if the named version is still current. Entity tags
+    /// use strong comparison here, so weak tags never match.
+    pub fn allows_range(&self, request: &GurtRequest) -> bool {
+        let Some(if_range) = request.header("if-range").map(|value| value.trim()) else {
+            return true;
+        };
+
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return !if_range.starts_with("W/")
+                && self.etag.as_deref().is_some_and(|etag| etag == if_range);
+        }
+
+        match (parse_http_date(if_range), self.last_modified) {
+            (Some(date), Some(modified)) => truncate_to_seconds(modified) == date,
+            _ => false,
+        }
+    }
+
+    /// Adds `etag` and `last-modified` headers to `response`.
+    pub fn apply(&self, mut response: GurtResponse) -> GurtResponse {
+        if let Some(etag) = &self.etag {
+            response = response.with_header("etag", etag.as_str());
+        }
+        if let Some(modified) = self.last_modified {
+            response = response.with_header("last-modified", format_http_date(modified));
+        }
+        response
+    }
+
+    /// A bodiless 304 response carrying these validators.
+    pub fn not_modified(&self) -> GurtResponse {
+        self.apply(GurtResponse::not_modified())
+    }
+}
+
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()),
+        Err(_) => time,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_date_round_trip() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        assert_eq!(format_http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_validators_freshness() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some(UNIX_EPOCH + Duration::from_millis(784_111_777_500)),
+        };
+        let request = |header: &str, value: &str| {
+            GurtRequest::new(GurtMethod::GET, "/".to_string()).with_header(header, value)
+        };
+
+        assert!(validators.is_fresh(&request("If-None-Match", "\"abc\"")));
+        assert!(validators.is_fresh(&request("If-None-Match", "\"x\", W/\"abc\"")));
+        assert!(validators.is_fresh(&request("If-None-Match", "*")));
+        assert!(!validators.is_fresh(&request("If-None-Match", "\"other\"")));
+
+        assert!(validators.is_fresh(&request("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")));
+        assert!(!validators.is_fresh(&request("If-Modified-Since", "Sun, 06 Nov 1994 08:49:36 GMT")));
+        assert!(!validators.is_fresh(&GurtRequest::new(GurtMethod::GET, "/".to_string())));
+
+        assert!(validators.allows_range(&GurtRequest::new(GurtMethod::GET, "/".to_string())));
+        assert!(validators.allows_range(&request("If-Range", "\"abc\"")));
+        assert!(validators.allows_range(&request("If-Range", "Sun, 06 Nov 1994 08:49:37 GMT")));
+        assert!(!validators.allows_range(&request("If-Range", "W/\"abc\"")));
+        assert!(!validators.allows_range(&request("If-Range", "Sun, 06 Nov 1994 08:49:36 GMT")));
+
+        let response = validators.not_modified();
+        assert_eq!(response.status_code, 304);
+        assert_eq!(response.header("etag").unwrap(), "\"abc\"");
+        assert_eq!(response.header("last-modified").unwrap(), "Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+}
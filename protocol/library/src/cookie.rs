@@ -0,0 +1,428 @@
+use crate::{GurtHeaders, GurtRequest, Result};
+use crate::conditional::{format_http_date, parse_http_date};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A cookie as sent in a `set-cookie` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    pub expires: Option<SystemTime>,
+    /// Takes precedence over `expires`. Zero deletes the cookie.
+    pub max_age: Option<Duration>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    pub fn new<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            domain: None,
+            path: None,
+            expires: None,
+            max_age: None,
+            secure: false,
+            http_only: false,
+        }
+    }
+
+    /// A cookie that makes clients delete any stored cookie called `name`.
+    /// Its path and domain must match the cookie being removed.
+    pub fn removal<N: Into<String>>(name: N) -> Self {
+        Self::new(name, "")
+            .with_max_age(Duration::ZERO)
+            .with_expires(UNIX_EPOCH)
+    }
+
+    pub fn with_domain<S: Into<String>>(mut self, domain: S) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    pub fn with_path<S: Into<String>>(mut self, path: S) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn with_expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn with_secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    /// Marks the cookie as only for requests, hiding it from page scripts.
+    pub fn with_http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    /// Parses a `set-cookie` header value. Unknown attributes are ignored; `None` is
+    /// returned when there is no `name=value` pair.
+    pub fn parse(set_cookie: &str) -> Option<Self> {
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie::new(name, value.trim().trim_matches('"'));
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+
+            match key.to_ascii_lowercase().as_str() {
+                "expires" => cookie.expires = parse_http_date(value).or(cookie.expires),
+                "max-age" => {
+                    if let Ok(seconds) = value.parse::<i64>() {
+                        cookie.max_age = Some(Duration::from_secs(seconds.max(0) as u64));
+                    }
+                }
+                "domain" => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    cookie.domain = (!domain.is_empty()).then_some(domain);
+                }
+                "path" => cookie.path = value.starts_with('/').then(|| value.to_string()),
+                "secure" => cookie.secure = true,
+                "httponly" => cookie.http_only = true,
+                _ => {}
+            }
+        }
+        Some(cookie)
+    }
+}
+
+/// Formats the cookie as a `set-cookie` header value.
+impl fmt::Display for Cookie {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.name, self.value)?;
+        if let Some(domain) = &self.domain {
+            write!(f, "; Domain={}", domain)?;
+        }
+        if let Some(path) = &self.path {
+            write!(f, "; Path={}", path)?;
+        }
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", format_http_date(expires))?;
+        }
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.as_secs())?;
+        }
+        if self.secure {
+            write!(f, "; Secure")?;
+        }
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a `cookie` request header into its name/value pairs.
+pub fn parse_cookie_header(value: &str) -> Vec<(String, String)> {
+    value.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().trim_matches('"').to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredCookie {
+    name: String,
+    value: String,
+    domain: String,
+    /// Set when the cookie had no `Domain` attribute, so it is only sent to the exact host.
+    host_only: bool,
+    path: String,
+    /// `None` for session cookies, which are not persisted.
+    expires: Option<SystemTime>,
+    secure: bool,
+    http_only: bool,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, host: &str, path: &str) -> bool {
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(host, &self.domain)
+        };
+        domain_matches && path_matches(path, &self.path)
+    }
+
+    fn to_cookie(&self) -> Cookie {
+        Cookie {
+            name: self.name.clone(),
+            value: self.value.clone(),
+            domain: (!self.host_only).then(|| self.domain.clone()),
+            path: Some(self.path.clone()),
+            expires: self.expires,
+            max_age: None,
+            secure: self.secure,
+            http_only: self.http_only,
+        }
+    }
+}
+
+/// Stores cookies set by servers and picks the ones to send with each request.
+///
+/// Domain and path matching follow RFC 6265. There is no public suffix list, so a
+/// `Domain` attribute must contain a dot unless it names the responding host itself.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    // Kept in creation order, which breaks ties when ordering the `cookie` header
+    cookies: Mutex<Vec<StoredCookie>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `cookie` as set by `host` in response to a request for `request_path`.
+    /// Returns `false` if the cookie was rejected because its domain does not match.
+    /// A cookie that is already expired deletes the stored cookie it replaces.
+    pub fn store(&self, host: &str, request_path: &str, cookie: Cookie) -> bool {
+        let host = host.to_ascii_lowercase();
+        let now = SystemTime::now();
+
+        let (domain, host_only) = match cookie.domain {
+            Some(domain) if domain == host => (domain, false),
+            Some(domain) if domain.contains('.') && domain_matches(&host, &domain) => (domain, false),
+            Some(_) => return false,
+            None => (host, true),
+        };
+
+        let stored = StoredCookie {
+            expires: match cookie.max_age {
+                Some(max_age) => Some(now.checked_add(max_age).unwrap_or(now + Duration::from_secs(u32::MAX as u64))),
+                None => cookie.expires,
+            },
+            name: cookie.name,
+            value: cookie.value,
+            domain,
+            host_only,
+            path: cookie.path.unwrap_or_else(|| default_path(request_path)),
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+        };
+
+        let mut cookies = self.cookies.lock().unwrap();
+        let existing = cookies.iter().position(|existing| {
+            existing.name == stored.name && existing.domain == stored.domain && existing.path == stored.path
+        });
+
+        match (existing, stored.is_expired(now)) {
+            (Some(index), true) => {
+                cookies.remove(index);
+            }
+            (Some(index), false) => cookies[index] = stored,
+            (None, true) => {}
+            (None, false) => cookies.push(stored),
+        }
+        true
+    }
+
+    /// Stores every cookie in the `set-cookie` headers of a response from `host`.
+    pub fn store_set_cookies(&self, host: &str, request_path: &str, headers: &GurtHeaders) {
+        for set_cookie in headers.get_all("set-cookie") {
+            if let Some(cookie) = Cookie::parse(set_cookie) {
+                self.store(host, request_path, cookie);
+            }
+        }
+    }
+
+    /// The cookies to send to `host` for `path`, longest path first.
+    pub fn cookies_for(&self, host: &str, path: &str) -> Vec<Cookie> {
+        let host = host.to_ascii_lowercase();
+        let now = SystemTime::now();
+
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<&StoredCookie> = cookies.iter()
+            .filter(|cookie| cookie.matches(&host, path))
+            .collect();
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        matching.into_iter().map(StoredCookie::to_cookie).collect()
+    }
+
+    /// The `cookie` header value for a request to `host` for `path`, if any cookies match.
+    pub fn cookie_header(&self, host: &str, path: &str) -> Option<String> {
+        let pairs: Vec<String> = self.cookies_for(host, path).into_iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        (!pairs.is_empty()).then(|| pairs.join("; "))
+    }
+
+    /// Adds the matching cookies to `request`, after any cookies it already carries.
+    pub fn apply(&self, host: &str, request: GurtRequest) -> GurtRequest {
+        let Some(cookies) = self.cookie_header(host, request.path_without_query()) else {
+            return request;
+        };
+
+        let value = match request.header("cookie") {
+            Some(existing) => format!("{}; {}", existing, cookies),
+            None => cookies,
+        };
+        request.with_header("cookie", value)
+    }
+
+    /// All stored cookies that have not expired.
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = SystemTime::now();
+        self.cookies.lock().unwrap().iter()
+            .filter(|cookie| !cookie.is_expired(now))
+            .map(StoredCookie::to_cookie)
+            .collect()
+    }
+
+    /// Removes the cookies called `name` that would be sent to `host`.
+    pub fn remove(&self, host: &str, name: &str) {
+        let host = host.to_ascii_lowercase();
+        self.cookies.lock().unwrap().retain(|cookie| !(cookie.name == name && cookie.matches(&host, &cookie.path)));
+    }
+
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Writes the persistent cookies to `path` as JSON. Session cookies, which have
+    /// no expiry, are left out.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let now = SystemTime::now();
+        let persistent: Vec<StoredCookie> = self.cookies.lock().unwrap().iter()
+            .filter(|cookie| cookie.expires.is_some() && !cookie.is_expired(now))
+            .cloned()
+            .collect();
+
+        std::fs::write(path, serde_json::to_vec(&persistent)?)?;
+        Ok(())
+    }
+
+    /// Reads a jar written by [`save`](Self::save), dropping cookies that have since expired.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let now = SystemTime::now();
+        let mut cookies: Vec<StoredCookie> = serde_json::from_slice(&std::fs::read(path)?)?;
+        cookies.retain(|cookie| !cookie.is_expired(now));
+
+        Ok(Self { cookies: Mutex::new(cookies) })
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.len() > domain.len()
+            && host.ends_with(domain)
+            && host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            && host.parse::<IpAddr>().is_err())
+}
+
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The directory of `request_path`, used as the path of cookies set without one.
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => request_path[..index].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cookie_parsing() {
+        let cookie = Cookie::parse("token=abc; Path=/api; Domain=.Example.web; Max-Age=60; Secure; HttpOnly").unwrap();
+        assert_eq!(cookie.name, "token");
+        assert_eq!(cookie.value, "abc");
+        assert_eq!(cookie.domain.as_deref(), Some("example.web"));
+        assert_eq!(cookie.path.as_deref(), Some("/api"));
+        assert_eq!(cookie.max_age, Some(Duration::from_secs(60)));
+        assert!(cookie.secure && cookie.http_only);
+
+        let cookie = Cookie::parse("id=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT; Path=relative").unwrap();
+        assert_eq!(cookie.expires, Some(UNIX_EPOCH + Duration::from_secs(784_111_777)));
+        assert_eq!(cookie.path, None);
+
+        assert!(Cookie::parse("no-equals-sign").is_none());
+        assert!(Cookie::parse("=value").is_none());
+
+        let formatted = Cookie::new("theme", "dark").with_path("/").with_max_age(Duration::from_secs(10)).with_http_only(true).to_string();
+        assert_eq!(formatted, "theme=dark; Path=/; Max-Age=10; HttpOnly");
+        assert_eq!(Cookie::parse(&formatted).unwrap().max_age, Some(Duration::from_secs(10)));
+
+        assert_eq!(parse_cookie_header("a=1; b=\"2\""), vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+    }
+
+    #[test]
+    fn test_cookie_jar_matching() {
+        let jar = CookieJar::new();
+        assert!(jar.store("example.web", "/account/login", Cookie::new("session", "1")));
+        assert!(jar.store("example.web", "/", Cookie::new("wide", "2").with_domain("example.web").with_path("/")));
+        assert!(jar.store("example.web", "/", Cookie::new("api", "3").with_path("/api")));
+        assert!(!jar.store("example.web", "/", Cookie::new("evil", "4").with_domain("other.web")));
+        assert!(!jar.store("example.web", "/", Cookie::new("tld", "5").with_domain("web")));
+
+        assert_eq!(jar.cookie_header("example.web", "/account/settings").unwrap(), "session=1; wide=2");
+        assert_eq!(jar.cookie_header("example.web", "/api/users").unwrap(), "api=3; wide=2");
+        assert_eq!(jar.cookie_header("EXAMPLE.web", "/apiary").unwrap(), "wide=2");
+        assert_eq!(jar.cookie_header("sub.example.web", "/account").unwrap(), "wide=2");
+        assert_eq!(jar.cookie_header("other.web", "/"), None);
+
+        jar.store("example.web", "/", Cookie::removal("wide").with_domain("example.web").with_path("/"));
+        assert_eq!(jar.cookie_header("example.web", "/"), None);
+
+        let mut headers = GurtHeaders::new();
+        headers.append("Set-Cookie", "a=1; Path=/");
+        headers.append("Set-Cookie", "b=2; Path=/");
+        jar.store_set_cookies("example.web", "/", &headers);
+        let request = jar.apply("example.web", GurtRequest::new(crate::GurtMethod::GET, "/?q=1".to_string()).with_header("Cookie", "own=0"));
+        assert_eq!(request.header("cookie").unwrap(), "own=0; a=1; b=2");
+    }
+
+    #[test]
+    fn test_cookie_jar_persistence() {
+        let path = std::env::temp_dir().join(format!("gurt_cookie_jar_test_{}.json", std::process::id()));
+
+        let jar = CookieJar::new();
+        jar.store("example.web", "/", Cookie::new("kept", "1").with_path("/").with_max_age(Duration::from_secs(3600)));
+        jar.store("example.web", "/", Cookie::new("session", "2").with_path("/"));
+        jar.save(&path).unwrap();
+
+        let loaded = CookieJar::load(&path).unwrap();
+        assert_eq!(loaded.cookie_header("example.web", "/").unwrap(), "kept=1");
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,11 +1,72 @@
 use crate::{GurtError, Result};
-use rustls::{ClientConfig, ServerConfig};
+use rustls::{ClientConfig, RootCertStore, ServerConfig};
+use rustls::client::Resumption;
+use rustls::crypto::aws_lc_rs::Ticketer;
+use rustls::server::{ServerSessionMemoryCache, WebPkiClientVerifier};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio_rustls::{TlsConnector, TlsAcceptor};
 use std::sync::Arc;
 
 pub const TLS_VERSION: &str = "TLS/1.3";
 pub const GURT_ALPN: &[u8] = b"GURT/1.0";
+/// How many TLS sessions a client remembers, and a server keeps state for, to resume later.
+pub const TLS_SESSION_CACHE_SIZE: usize = 256;
+
+/// Whether a server asks connecting clients for a certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientAuthMode {
+    /// Verify a certificate when the client presents one, but also accept clients without.
+    Request,
+    /// Refuse clients that do not present a valid certificate.
+    Require,
+}
+
+/// The verified certificate a client presented during the TLS handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerIdentity {
+    /// DNS names from the certificate's subject alternative names.
+    pub dns_names: Vec<String>,
+    /// The certificate chain the client sent, leaf first.
+    pub certificates: Vec<CertificateDer<'static>>,
+}
+
+impl PeerIdentity {
+    /// Reads the identity from a chain the TLS handshake has already verified.
+    pub fn from_certificates(certificates: &[CertificateDer<'_>]) -> Option<Self> {
+        let leaf = webpki::EndEntityCert::try_from(certificates.first()?).ok()?;
+        let dns_names = leaf.valid_dns_names().map(str::to_string).collect();
+        
+        Some(Self {
+            dns_names,
+            certificates: certificates.iter().map(|cert| cert.clone().into_owned()).collect(),
+        })
+    }
+    
+    /// The first DNS name of the certificate, usually the name of the calling service.
+    pub fn name(&self) -> Option<&str> {
+        self.dns_names.first().map(|name| name.as_str())
+    }
+    
+    pub fn has_name(&self, name: &str) -> bool {
+        self.dns_names.iter().any(|dns_name| dns_name.eq_ignore_ascii_case(name))
+    }
+}
+
+/// Parses every certificate in a PEM bundle.
+pub fn parse_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+    let mut cursor = std::io::Cursor::new(pem);
+    rustls_pemfile::certs(&mut cursor)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| GurtError::Crypto(format!("Failed to parse certificates: {}", e)))
+}
+
+/// Parses the first private key in a PEM file.
+pub fn parse_private_key(pem: &[u8]) -> Result<PrivateKeyDer<'static>> {
+    let mut cursor = std::io::Cursor::new(pem);
+    rustls_pemfile::private_key(&mut cursor)
+        .map_err(|e| GurtError::Crypto(format!("Failed to parse private key: {}", e)))?
+        .ok_or_else(|| GurtError::Crypto("No private key found".to_string()))
+}
 
 #[derive(Debug, Clone)]
 pub struct TlsConfig {
@@ -20,6 +81,7 @@ impl TlsConfig {
             .with_no_client_auth();
         
         config.alpn_protocols = vec![GURT_ALPN.to_vec()];
+        config.resumption = Resumption::in_memory_sessions(TLS_SESSION_CACHE_SIZE);
         
         Ok(Self {
             client_config: Some(Arc::new(config)),
@@ -28,12 +90,37 @@ impl TlsConfig {
     }
     
     pub fn new_server(cert_chain: Vec<CertificateDer<'static>>, private_key: PrivateKeyDer<'static>) -> Result<Self> {
-        let mut config = ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(cert_chain, private_key)
+        let builder = ServerConfig::builder().with_no_client_auth();
+        Self::finish_server(builder.with_single_cert(cert_chain, private_key))
+    }
+    
+    /// Like [`TlsConfig::new_server`], but also verifies client certificates against `client_roots`.
+    pub fn new_server_with_client_auth(
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+        client_roots: RootCertStore,
+        mode: ClientAuthMode,
+    ) -> Result<Self> {
+        let verifier = WebPkiClientVerifier::builder(Arc::new(client_roots));
+        let verifier = match mode {
+            ClientAuthMode::Request => verifier.allow_unauthenticated(),
+            ClientAuthMode::Require => verifier,
+        };
+        let verifier = verifier.build()
+            .map_err(|e| GurtError::Crypto(format!("TLS client verifier error: {}", e)))?;
+        
+        let builder = ServerConfig::builder().with_client_cert_verifier(verifier);
+        Self::finish_server(builder.with_single_cert(cert_chain, private_key))
+    }
+    
+    fn finish_server(config: std::result::Result<ServerConfig, rustls::Error>) -> Result<Self> {
+        let mut config = config
             .map_err(|e| GurtError::Crypto(format!("TLS server config error: {}", e)))?;
         
         config.alpn_protocols = vec![GURT_ALPN.to_vec()];
+        config.session_storage = ServerSessionMemoryCache::new(TLS_SESSION_CACHE_SIZE);
+        config.ticketer = Ticketer::new()
+            .map_err(|e| GurtError::Crypto(format!("TLS ticketer error: {}", e)))?;
         
         Ok(Self {
             client_config: None,
@@ -115,6 +202,15 @@ mod tests {
         assert!(config.server_config.is_none());
     }
     
+    #[test]
+    fn test_peer_identity_requires_certificate() {
+        assert!(PeerIdentity::from_certificates(&[]).is_none());
+        assert!(PeerIdentity::from_certificates(&[CertificateDer::from(vec![0u8; 16])]).is_none());
+        
+        assert!(parse_certificates(b"").unwrap().is_empty());
+        assert!(parse_private_key(b"").is_err());
+    }
+    
     #[test]
     fn test_crypto_manager() {
         let crypto = CryptoManager::new();
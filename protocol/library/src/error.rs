@@ -36,4 +36,38 @@ pub enum GurtError {
     Cancelled,
 }
 
-pub type Result<T> = std::result::Result<T, GurtError>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, GurtError>;
+
+impl GurtError {
+    pub fn crypto<T: Into<String>>(message: T) -> Self {
+        GurtError::Crypto(message.into())
+    }
+    
+    pub fn protocol<T: Into<String>>(message: T) -> Self {
+        GurtError::Protocol(message.into())
+    }
+    
+    pub fn invalid_message<T: Into<String>>(message: T) -> Self {
+        GurtError::InvalidMessage(message.into())
+    }
+    
+    pub fn connection<T: Into<String>>(message: T) -> Self {
+        GurtError::Connection(message.into())
+    }
+    
+    pub fn handshake<T: Into<String>>(message: T) -> Self {
+        GurtError::Handshake(message.into())
+    }
+    
+    pub fn timeout<T: Into<String>>(message: T) -> Self {
+        GurtError::Timeout(message.into())
+    }
+    
+    pub fn server(status: u16, message: String) -> Self {
+        GurtError::Server { status, message }
+    }
+    
+    pub fn client<T: Into<String>>(message: T) -> Self {
+        GurtError::Client(message.into())
+    }
+}
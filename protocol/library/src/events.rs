@@ -0,0 +1,348 @@
+use crate::{GurtError, GurtResponseHead, Result};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The content type of a response that pushes [`Event`]s.
+pub const EVENT_STREAM_CONTENT_TYPE: &str = "text/event-stream";
+
+/// Largest event, or single line of one, that `EventStream` accepts.
+pub const MAX_EVENT_SIZE: usize = 1024 * 1024;
+
+/// How many received chunks `EventStream` holds before the connection stops being read.
+pub(crate) const CLIENT_BUFFERED_CHUNKS: usize = 16;
+
+/// Whether a `content-type` value names an event stream.
+pub fn is_event_stream(content_type: &str) -> bool {
+    content_type.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(EVENT_STREAM_CONTENT_TYPE)
+}
+
+/// A single record of an event stream, written as `event:`/`data:` lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Event {
+    pub event: Option<String>,
+    pub data: String,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+impl Event {
+    pub fn new<S: Into<String>>(data: S) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn json<T: serde::Serialize>(data: &T) -> Result<Self> {
+        Ok(Self::new(serde_json::to_string(data)?))
+    }
+
+    pub fn with_event<S: Into<String>>(mut self, event: S) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Asks clients to wait `millis` before reconnecting after the stream ends.
+    pub fn with_retry(mut self, millis: u64) -> Self {
+        self.retry = Some(millis);
+        self
+    }
+
+    /// The event type, `message` when none was set.
+    pub fn name(&self) -> &str {
+        self.event.as_deref().unwrap_or("message")
+    }
+
+    pub fn parse_json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.data).map_err(GurtError::from)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut record = String::new();
+
+        if let Some(event) = &self.event {
+            record.push_str(&format!("event: {}\n", single_line(event)));
+        }
+        if let Some(id) = &self.id {
+            record.push_str(&format!("id: {}\n", single_line(id)));
+        }
+        if let Some(retry) = self.retry {
+            record.push_str(&format!("retry: {}\n", retry));
+        }
+        for line in self.data.split('\n') {
+            record.push_str(&format!("data: {}\n", line.strip_suffix('\r').unwrap_or(line)));
+        }
+
+        record.push('\n');
+        record.into_bytes()
+    }
+}
+
+fn single_line(value: &str) -> &str {
+    value.split(['\r', '\n']).next().unwrap_or_default()
+}
+
+/// Pushes events into the response returned by `GurtResponse::event_stream`.
+/// The stream ends once every clone has been dropped.
+#[derive(Clone, Debug)]
+pub struct EventSender {
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl EventSender {
+    pub(crate) fn new(sender: mpsc::Sender<Vec<u8>>) -> Self {
+        Self { sender }
+    }
+
+    /// Waits for room in the stream's buffer, failing once the client has gone away.
+    pub async fn send(&self, event: Event) -> Result<()> {
+        self.sender.send(event.to_bytes()).await
+            .map_err(|_| GurtError::connection("Event stream closed"))
+    }
+
+    /// Sends a comment line, which clients ignore. Useful to keep idle streams open.
+    pub async fn comment(&self, text: &str) -> Result<()> {
+        self.sender.send(format!(": {}\n\n", single_line(text)).into_bytes()).await
+            .map_err(|_| GurtError::connection("Event stream closed"))
+    }
+
+    /// Whether the client has gone away, e.g. to stop producing events for it.
+    pub fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+
+    /// Resolves once the client has gone away.
+    pub async fn closed(&self) {
+        self.sender.closed().await
+    }
+}
+
+/// Incrementally splits event stream text into [`Event`]s.
+#[derive(Debug, Default)]
+pub(crate) struct EventParser {
+    buffer: Vec<u8>,
+    /// Start of the first line not yet parsed.
+    start: usize,
+    /// How far past `start` has already been searched for a line break.
+    scanned: usize,
+    current: Event,
+    has_data: bool,
+}
+
+impl EventParser {
+    pub(crate) fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete event, if one has been fed in full. Fails once a line
+    /// or event grows past `MAX_EVENT_SIZE`.
+    pub(crate) fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            let Some(offset) = self.buffer[self.scanned..].iter().position(|&byte| byte == b'\n') else {
+                self.buffer.drain(..self.start);
+                self.start = 0;
+                self.scanned = self.buffer.len();
+
+                if self.buffer.len() > MAX_EVENT_SIZE {
+                    return Err(GurtError::invalid_message(format!("Event stream line is longer than {} bytes", MAX_EVENT_SIZE)));
+                }
+                return Ok(None);
+            };
+
+            let end = self.scanned + offset;
+            let line = String::from_utf8_lossy(&self.buffer[self.start..end]);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
+            self.start = end + 1;
+            self.scanned = self.start;
+
+            if line.is_empty() {
+                let event = std::mem::take(&mut self.current);
+                if std::mem::take(&mut self.has_data) {
+                    return Ok(Some(event));
+                }
+                continue;
+            }
+            if line.starts_with(':') {
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+
+            match field {
+                "event" => self.current.event = Some(value.to_string()),
+                "data" => {
+                    if self.has_data {
+                        self.current.data.push('\n');
+                    }
+                    self.current.data.push_str(value);
+                    self.has_data = true;
+
+                    if self.current.data.len() > MAX_EVENT_SIZE {
+                        return Err(GurtError::invalid_message(format!("Event is larger than {} bytes", MAX_EVENT_SIZE)));
+                    }
+                }
+                "id" if !value.contains('\0') => self.current.id = Some(value.to_string()),
+                "retry" => {
+                    if let Ok(retry) = value.parse() {
+                        self.current.retry = Some(retry);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The client side of an event stream, as returned by `GurtClient::events`.
+///
+/// Dropping it closes the underlying connection.
+pub struct EventStream {
+    head: GurtResponseHead,
+    chunks: mpsc::Receiver<Vec<u8>>,
+    parser: EventParser,
+    task: Option<JoinHandle<Result<()>>>,
+    last_event_id: Option<String>,
+}
+
+impl EventStream {
+    pub(crate) fn new(head: GurtResponseHead, chunks: mpsc::Receiver<Vec<u8>>, task: JoinHandle<Result<()>>) -> Self {
+        Self {
+            head,
+            chunks,
+            parser: EventParser::default(),
+            task: Some(task),
+            last_event_id: None,
+        }
+    }
+
+    /// The head of the response that opened the stream.
+    pub fn head(&self) -> &GurtResponseHead {
+        &self.head
+    }
+
+    /// The id of the latest event that carried one. Send it back in a `last-event-id`
+    /// header when reconnecting to resume where the stream left off.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Waits for the next event. Returns `None` once the server ends the stream.
+    pub async fn next(&mut self) -> Result<Option<Event>> {
+        loop {
+            if let Some(event) = self.parser.next_event()? {
+                if event.id.is_some() {
+                    self.last_event_id = event.id.clone();
+                }
+                return Ok(Some(event));
+            }
+
+            match self.chunks.recv().await {
+                Some(chunk) => self.parser.feed(&chunk),
+                None => {
+                    return match self.task.take() {
+                        Some(task) => task.await
+                            .map_err(|e| GurtError::connection(format!("Event stream task failed: {}", e)))?
+                            .map(|_| None),
+                        None => Ok(None),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        if let Some(task) = &self.task {
+            task.abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventStream")
+            .field("status_code", &self.head.status_code)
+            .field("last_event_id", &self.last_event_id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GurtResponse;
+
+    #[test]
+    fn test_event_round_trip() {
+        let event = Event::new("first line\nsecond line")
+            .with_event("status")
+            .with_id("7")
+            .with_retry(3000);
+        let bytes = event.to_bytes();
+        assert_eq!(
+            String::from_utf8_lossy(&bytes),
+            "event: status\nid: 7\nretry: 3000\ndata: first line\ndata: second line\n\n"
+        );
+
+        let mut parser = EventParser::default();
+        let (first, second) = bytes.split_at(10);
+        parser.feed(b": keep-alive\n\n");
+        parser.feed(first);
+        assert_eq!(parser.next_event().unwrap(), None);
+        parser.feed(second);
+        assert_eq!(parser.next_event().unwrap(), Some(event));
+        assert_eq!(parser.next_event().unwrap(), None);
+
+        parser.feed(b"data:no space\r\n\r\nevent: ignored\n\n");
+        let event = parser.next_event().unwrap().unwrap();
+        assert_eq!(event.data, "no space");
+        assert_eq!(event.name(), "message");
+        assert_eq!(parser.next_event().unwrap(), None);
+    }
+
+    #[test]
+    fn test_event_size_limits() {
+        let mut parser = EventParser::default();
+        let line = vec![b'x'; 64 * 1024];
+        for _ in 0..MAX_EVENT_SIZE / line.len() {
+            parser.feed(&line);
+            assert_eq!(parser.next_event().unwrap(), None);
+        }
+        parser.feed(&line);
+        assert!(parser.next_event().is_err());
+
+        let mut parser = EventParser::default();
+        let line = format!("data: {}\n", "x".repeat(64 * 1024));
+        for _ in 0..MAX_EVENT_SIZE / line.len() {
+            parser.feed(line.as_bytes());
+            assert_eq!(parser.next_event().unwrap(), None);
+        }
+        parser.feed(line.as_bytes());
+        parser.feed(line.as_bytes());
+        assert!(parser.next_event().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_response() {
+        let (events, response) = GurtResponse::event_stream(4);
+        assert!(is_event_stream(response.header("content-type").unwrap()));
+        assert!(response.is_chunked());
+
+        tokio::spawn(async move {
+            events.send(Event::new("one")).await.unwrap();
+            events.send(Event::new("two").with_event("update")).await.unwrap();
+        });
+
+        let body = response.body_stream.unwrap().collect().await.unwrap();
+        assert_eq!(body, b"data: one\n\nevent: update\ndata: two\n\n");
+    }
+}
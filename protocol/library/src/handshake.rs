@@ -0,0 +1,314 @@
+use crate::{GurtError, GurtRequest, GurtResponse, GurtStatusCode, Result, GURT_VERSION};
+use crate::crypto::{GURT_ALPN, TLS_VERSION};
+use std::fmt;
+
+/// Carries each side's protocol version in the HANDSHAKE exchange.
+pub const VERSION_HEADER: &str = "GURT-Version";
+/// Lists the optional features each side supports in the HANDSHAKE exchange.
+pub const CAPABILITIES_HEADER: &str = "Capabilities";
+
+/// The first byte of a TLS handshake record, which opens a direct TLS connection.
+/// A plaintext HANDSHAKE starts with `H` instead.
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+/// A `major.minor.patch` protocol version. Peers can talk to each other when their
+/// major versions match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl ProtocolVersion {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self { major, minor, patch }
+    }
+
+    /// The version implemented by this library, [`GURT_VERSION`].
+    pub fn current() -> Self {
+        Self::parse(GURT_VERSION).unwrap_or(Self::new(1, 0, 0))
+    }
+
+    /// Parses `1`, `1.2` or `1.2.3`, with or without a leading `GURT/`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let value = value.strip_prefix("GURT/").unwrap_or(value);
+
+        let mut parts = value.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+        let patch = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self::new(major, minor, patch))
+    }
+
+    pub fn is_compatible_with(&self, other: &ProtocolVersion) -> bool {
+        self.major == other.major
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Optional protocol features a peer advertises during the handshake. A feature is
+/// only in use on a connection when both sides advertised it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capabilities {
+    /// `content-encoding` compressed bodies.
+    pub compression: bool,
+    /// Several requests in flight on one connection.
+    pub pipelining: bool,
+    /// Chunked bodies, event streams and channels.
+    pub streaming: bool,
+    /// New connections may start TLS right away instead of with a plaintext HANDSHAKE.
+    pub direct_tls: bool,
+}
+
+impl Capabilities {
+    pub const fn all() -> Self {
+        Self { compression: true, pipelining: true, streaming: true, direct_tls: true }
+    }
+
+    pub const fn none() -> Self {
+        Self { compression: false, pipelining: false, streaming: false, direct_tls: false }
+    }
+
+    /// Parses a comma-separated list, ignoring features this version does not know.
+    pub fn parse(value: &str) -> Self {
+        let mut capabilities = Self::none();
+        for name in value.split(',').map(|name| name.trim().to_ascii_lowercase()) {
+            match name.as_str() {
+                "compression" => capabilities.compression = true,
+                "pipelining" => capabilities.pipelining = true,
+                "streaming" => capabilities.streaming = true,
+                "direct-tls" => capabilities.direct_tls = true,
+                _ => {}
+            }
+        }
+        capabilities
+    }
+
+    pub fn intersection(&self, other: &Capabilities) -> Self {
+        Self {
+            compression: self.compression && other.compression,
+            pipelining: self.pipelining && other.pipelining,
+            streaming: self.streaming && other.streaming,
+            direct_tls: self.direct_tls && other.direct_tls,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::none()
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = [
+            (self.compression, "compression"),
+            (self.pipelining, "pipelining"),
+            (self.streaming, "streaming"),
+            (self.direct_tls, "direct-tls"),
+        ]
+            .into_iter()
+            .filter_map(|(enabled, name)| enabled.then_some(name))
+            .collect();
+
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// What the two sides of a connection agreed on in the HANDSHAKE exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handshake {
+    pub version: ProtocolVersion,
+    pub capabilities: Capabilities,
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self {
+            version: ProtocolVersion::current(),
+            capabilities: Capabilities::none(),
+        }
+    }
+}
+
+/// Whether a connection that starts with `first_byte` skips the plaintext HANDSHAKE
+/// and opens with a TLS ClientHello.
+pub fn is_direct_tls(first_byte: u8) -> bool {
+    first_byte == TLS_HANDSHAKE_RECORD
+}
+
+/// The handshake for a direct TLS connection, where the ALPN protocol (`GURT/1.0`)
+/// stands in for the version exchange. No capabilities are exchanged on such
+/// connections, so none are recorded.
+pub fn from_alpn(protocol: Option<&[u8]>) -> Result<Handshake> {
+    let version = protocol
+        .and_then(|protocol| std::str::from_utf8(protocol).ok())
+        .filter(|protocol| protocol.starts_with("GURT/"))
+        .and_then(ProtocolVersion::parse)
+        .ok_or_else(|| GurtError::handshake("Direct TLS connection did not negotiate the GURT ALPN protocol"))?;
+
+    if !version.is_compatible_with(&ProtocolVersion::current()) {
+        return Err(GurtError::handshake(format!(
+            "Direct TLS connection negotiated GURT/{}, which is incompatible with GURT/{}", version, ProtocolVersion::current()
+        )));
+    }
+
+    Ok(Handshake {
+        version: version.min(ProtocolVersion::current()),
+        capabilities: Capabilities::none(),
+    })
+}
+
+/// Reads the peer's version header. Peers that predate negotiation send none and
+/// are treated as `1.0.0`.
+fn peer_version(version: Option<&String>) -> Result<ProtocolVersion> {
+    match version {
+        Some(value) => ProtocolVersion::parse(value)
+            .ok_or_else(|| GurtError::handshake(format!("Invalid {} '{}'", VERSION_HEADER, value))),
+        None => Ok(ProtocolVersion::new(1, 0, 0)),
+    }
+}
+
+/// Adds the version and capability headers a client offers in its HANDSHAKE request.
+pub fn offer(request: GurtRequest, capabilities: Capabilities) -> GurtRequest {
+    request
+        .with_header(VERSION_HEADER, ProtocolVersion::current().to_string())
+        .with_header(CAPABILITIES_HEADER, capabilities.to_string())
+}
+
+/// Negotiates with a client's HANDSHAKE request, settling on the lower of the two
+/// versions and the capabilities both sides support. Fails if the major versions differ.
+pub fn negotiate(request: &GurtRequest, capabilities: Capabilities) -> Result<Handshake> {
+    let client_version = peer_version(request.header(VERSION_HEADER))?;
+    let server_version = ProtocolVersion::current();
+    if !server_version.is_compatible_with(&client_version) {
+        return Err(GurtError::handshake(format!(
+            "Client speaks GURT/{}, but this server only supports GURT/{}.x", client_version, server_version.major
+        )));
+    }
+
+    let offered = request.header(CAPABILITIES_HEADER)
+        .map(|value| Capabilities::parse(value))
+        .unwrap_or_default();
+
+    Ok(Handshake {
+        version: client_version.min(server_version),
+        capabilities: offered.intersection(&capabilities),
+    })
+}
+
+/// The `101` response that completes a negotiated handshake.
+pub fn accept_response(handshake: &Handshake) -> GurtResponse {
+    GurtResponse::new(GurtStatusCode::SwitchingProtocols)
+        .with_header(VERSION_HEADER, handshake.version.to_string())
+        .with_header("Encryption", TLS_VERSION)
+        .with_header("ALPN", std::str::from_utf8(GURT_ALPN).unwrap_or("GURT/1.0"))
+        .with_header(CAPABILITIES_HEADER, handshake.capabilities.to_string())
+}
+
+/// The `505` response for a client whose version the server cannot speak.
+pub fn reject_response(reason: &str) -> GurtResponse {
+    GurtResponse::new(GurtStatusCode::VersionNotSupported)
+        .with_header(VERSION_HEADER, ProtocolVersion::current().to_string())
+        .with_string_body(reason)
+}
+
+/// Checks the server's answer to a HANDSHAKE request that offered `capabilities`.
+pub fn confirm(response: &GurtResponse, capabilities: Capabilities) -> Result<Handshake> {
+    if response.status_code == GurtStatusCode::VersionNotSupported.as_u16() {
+        let server_version = response.header(VERSION_HEADER).map(String::as_str).unwrap_or("unknown");
+        return Err(GurtError::handshake(format!(
+            "Server does not support GURT/{} (server version {})", ProtocolVersion::current(), server_version
+        )));
+    }
+    if response.status_code != GurtStatusCode::SwitchingProtocols.as_u16() {
+        return Err(GurtError::handshake(format!("{} {}", response.status_code, response.status_message)));
+    }
+
+    let version = peer_version(response.header(VERSION_HEADER))?;
+    if !version.is_compatible_with(&ProtocolVersion::current()) {
+        return Err(GurtError::handshake(format!(
+            "Server answered with GURT/{}, which is incompatible with GURT/{}", version, ProtocolVersion::current()
+        )));
+    }
+
+    let advertised = response.header(CAPABILITIES_HEADER)
+        .map(|value| Capabilities::parse(value))
+        .unwrap_or_default();
+
+    Ok(Handshake {
+        version: version.min(ProtocolVersion::current()),
+        capabilities: advertised.intersection(&capabilities),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GurtMethod;
+
+    #[test]
+    fn test_version_parsing() {
+        assert_eq!(ProtocolVersion::parse("1.2.3"), Some(ProtocolVersion::new(1, 2, 3)));
+        assert_eq!(ProtocolVersion::parse("GURT/2"), Some(ProtocolVersion::new(2, 0, 0)));
+        assert_eq!(ProtocolVersion::parse("1.x"), None);
+        assert_eq!(ProtocolVersion::parse("1.2.3.4"), None);
+        assert!(ProtocolVersion::new(1, 4, 0).is_compatible_with(&ProtocolVersion::new(1, 0, 2)));
+        assert!(!ProtocolVersion::new(2, 0, 0).is_compatible_with(&ProtocolVersion::new(1, 0, 0)));
+
+        let capabilities = Capabilities::parse("Streaming, compression, teleportation, direct-tls");
+        assert_eq!(capabilities, Capabilities { compression: true, pipelining: false, streaming: true, direct_tls: true });
+        assert_eq!(capabilities.to_string(), "compression, streaming, direct-tls");
+        assert!(Capabilities::parse("").is_empty());
+    }
+
+    #[test]
+    fn test_negotiation() {
+        let hello = || GurtRequest::new(GurtMethod::HANDSHAKE, "/".to_string());
+        let server_capabilities = Capabilities { compression: false, pipelining: true, streaming: true, direct_tls: true };
+
+        let request = offer(hello(), Capabilities::all());
+        let handshake = negotiate(&request, server_capabilities).unwrap();
+        assert_eq!(handshake.version, ProtocolVersion::current());
+        assert_eq!(handshake.capabilities, server_capabilities);
+
+        let response = GurtResponse::parse_bytes(&accept_response(&handshake).to_bytes()).unwrap();
+        assert_eq!(confirm(&response, Capabilities::all()).unwrap(), handshake);
+
+        // Clients from before negotiation get no optional features
+        let legacy = negotiate(&hello(), server_capabilities).unwrap();
+        assert_eq!(legacy.version, ProtocolVersion::new(1, 0, 0));
+        assert!(legacy.capabilities.is_empty());
+
+        let future = hello().with_header(VERSION_HEADER, "2.0.0");
+        let error = negotiate(&future, server_capabilities).unwrap_err();
+        assert!(matches!(error, GurtError::Handshake(_)));
+
+        let rejected = reject_response(&error.to_string());
+        assert!(matches!(confirm(&rejected, Capabilities::all()), Err(GurtError::Handshake(_))));
+    }
+
+    #[test]
+    fn test_direct_tls_detection() {
+        assert!(is_direct_tls(0x16));
+        assert!(!is_direct_tls(b'H'));
+
+        let direct = from_alpn(Some(GURT_ALPN)).unwrap();
+        assert!(direct.version.is_compatible_with(&ProtocolVersion::current()));
+        assert!(direct.capabilities.is_empty());
+
+        assert!(from_alpn(None).is_err());
+        assert!(from_alpn(Some(b"h2")).is_err());
+        assert!(from_alpn(Some(b"GURT/2.0")).is_err());
+    }
+}
@@ -0,0 +1,253 @@
+use crate::{GurtError, Result};
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use tracing::warn;
+
+/// Whether `name` is a valid header field name: a non-empty token without
+/// whitespace, separators or control characters.
+pub fn is_valid_header_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|byte| {
+        byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+    })
+}
+
+/// Whether `value` can be sent as a header value. CR, LF and NUL are rejected so a
+/// value cannot end the header line and inject headers or a body of its own.
+pub fn is_valid_header_value(value: &str) -> bool {
+    !value.bytes().any(|byte| matches!(byte, b'\r' | b'\n' | b'\0'))
+}
+
+fn validate(name: &str, value: &str) -> Result<()> {
+    if !is_valid_header_name(name) {
+        return Err(GurtError::invalid_message(format!("Invalid header name: {:?}", name)));
+    }
+    if !is_valid_header_value(value) {
+        return Err(GurtError::invalid_message(format!("Invalid value for header {}", name)));
+    }
+    Ok(())
+}
+
+/// The header fields of a message.
+///
+/// Names are matched case-insensitively but sent with the case they were added
+/// with. Fields keep their insertion order, and a name may carry several values,
+/// as `set-cookie` often does.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(String, String)>", into = "Vec<(String, String)>")]
+pub struct GurtHeaders {
+    entries: Vec<(String, String)>,
+}
+
+impl GurtHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The first value of `name`.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Every value of `name`, in the order they were added.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.entries.iter()
+            .filter(move |(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to a single value, replacing any existing values in place and
+    /// returning the first of them.
+    ///
+    /// Invalid names or values are dropped with a warning; use
+    /// [`try_insert`](Self::try_insert) to handle them as errors instead.
+    pub fn insert<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) -> Option<String> {
+        let (name, value) = (name.into(), value.into());
+        match self.try_insert(name.as_str(), value) {
+            Ok(previous) => previous,
+            Err(e) => {
+                warn!("Dropping header {:?}: {}", name, e);
+                None
+            }
+        }
+    }
+
+    pub fn try_insert<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) -> Result<Option<String>> {
+        let (name, value) = (name.into(), value.into());
+        validate(&name, &value)?;
+
+        let Some(position) = self.entries.iter().position(|(key, _)| key.eq_ignore_ascii_case(&name)) else {
+            self.entries.push((name, value));
+            return Ok(None);
+        };
+
+        let previous = std::mem::replace(&mut self.entries[position], (name.clone(), value)).1;
+        let mut index = 0;
+        self.entries.retain(|(key, _)| {
+            let keep = index == position || !key.eq_ignore_ascii_case(&name);
+            index += 1;
+            keep
+        });
+        Ok(Some(previous))
+    }
+
+    /// Adds a value for `name`, keeping any it already has.
+    ///
+    /// Invalid names or values are dropped with a warning; use
+    /// [`try_append`](Self::try_append) to handle them as errors instead.
+    pub fn append<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        let (name, value) = (name.into(), value.into());
+        if let Err(e) = self.try_append(name.as_str(), value) {
+            warn!("Dropping header {:?}: {}", name, e);
+        }
+    }
+
+    pub fn try_append<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) -> Result<()> {
+        let (name, value) = (name.into(), value.into());
+        validate(&name, &value)?;
+        self.entries.push((name, value));
+        Ok(())
+    }
+
+    /// Removes every value of `name`, returning the first.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut removed = None;
+        self.entries.retain_mut(|(key, value)| {
+            if !key.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if removed.is_none() {
+                removed = Some(std::mem::take(value));
+            }
+            false
+        });
+        removed
+    }
+
+    /// Iterates over all fields in order. A name with several values appears once per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.into_iter()
+    }
+
+    /// The distinct header names, in the order they first appear.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in &self.entries {
+            if !names.iter().any(|seen| seen.eq_ignore_ascii_case(name)) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// The number of fields, counting each value of a repeated name.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Debug for GurtHeaders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a GurtHeaders {
+    type Item = (&'a String, &'a String);
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (String, String)>, fn(&'a (String, String)) -> (&'a String, &'a String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        fn as_refs((name, value): &(String, String)) -> (&String, &String) {
+            (name, value)
+        }
+        self.entries.iter().map(as_refs as fn(&'a (String, String)) -> (&'a String, &'a String))
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> Extend<(K, V)> for GurtHeaders {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, fields: I) {
+        for (name, value) in fields {
+            self.append(name, value);
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for GurtHeaders {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(fields: I) -> Self {
+        let mut headers = Self::new();
+        headers.extend(fields);
+        headers
+    }
+}
+
+impl TryFrom<Vec<(String, String)>> for GurtHeaders {
+    type Error = GurtError;
+
+    fn try_from(fields: Vec<(String, String)>) -> Result<Self> {
+        let mut headers = Self::new();
+        for (name, value) in fields {
+            headers.try_append(name, value)?;
+        }
+        Ok(headers)
+    }
+}
+
+impl From<GurtHeaders> for Vec<(String, String)> {
+    fn from(headers: GurtHeaders) -> Self {
+        headers.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_map() {
+        let mut headers = GurtHeaders::new();
+        headers.insert("Content-Type", "text/html");
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+        headers.insert("X-Trace", "1");
+
+        assert_eq!(headers.get("content-type").unwrap(), "text/html");
+        assert_eq!(headers.get_all("SET-COOKIE").collect::<Vec<_>>(), ["a=1", "b=2"]);
+        assert_eq!(headers.names(), ["Content-Type", "Set-Cookie", "X-Trace"]);
+        assert_eq!(headers.len(), 4);
+
+        assert_eq!(headers.insert("set-cookie", "c=3").unwrap(), "a=1");
+        assert_eq!(headers.get_all("set-cookie").collect::<Vec<_>>(), ["c=3"]);
+        let order: Vec<&str> = headers.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(order, ["Content-Type", "set-cookie", "X-Trace"]);
+
+        assert_eq!(headers.remove("CONTENT-type").unwrap(), "text/html");
+        assert!(!headers.contains_key("content-type"));
+    }
+
+    #[test]
+    fn test_header_validation() {
+        let mut headers = GurtHeaders::new();
+
+        assert!(headers.try_insert("Location", "/next\r\nSet-Cookie: evil=1").is_err());
+        assert!(headers.try_append("Bad Name", "value").is_err());
+        assert!(headers.try_append("", "value").is_err());
+        assert!(headers.try_append("X-Ok", "tab\tand spaces").is_ok());
+
+        headers.insert("X-Split", "a\nb");
+        assert!(!headers.contains_key("x-split"));
+
+        let invalid: std::result::Result<GurtHeaders, _> = serde_json::from_str(r#"[["X-Evil", "a\r\nb"]]"#);
+        assert!(invalid.is_err());
+        let round_trip: GurtHeaders = serde_json::from_str(&serde_json::to_string(&headers).unwrap()).unwrap();
+        assert_eq!(round_trip, headers);
+    }
+}
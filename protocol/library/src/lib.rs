@@ -4,21 +4,48 @@ pub mod server;
 pub mod client;
 pub mod error;
 pub mod message;
+pub mod headers;
+pub mod body;
+pub mod query;
+pub mod conditional;
+pub mod cache;
+pub mod range;
+pub mod compression;
+pub mod cookie;
+pub mod multipart;
+pub mod channel;
+pub mod events;
+pub mod handshake;
+mod pipeline;
+#[cfg(test)]
+mod test_support;
 
 pub use error::{GurtError, Result};
-pub use message::{GurtMessage, GurtRequest, GurtResponse, GurtResponseHead, GurtMethod};
+pub use message::{GurtMessage, GurtRequest, GurtResponse, GurtResponseHead, GurtMethod, ChunkedEncoder, ChunkedDecoder};
+pub use headers::GurtHeaders;
+pub use body::GurtBodyStream;
+pub use query::QueryParams;
+pub use conditional::Validators;
 pub use protocol::{GurtStatusCode, GURT_VERSION, DEFAULT_PORT};
-pub use crypto::{CryptoManager, TlsConfig, GURT_ALPN, TLS_VERSION};
-pub use server::{GurtServer, GurtHandler, ServerContext, Route};
+pub use crypto::{ClientAuthMode, CryptoManager, PeerIdentity, TlsConfig, GURT_ALPN, TLS_VERSION};
+pub use server::{GurtServer, ConnectionLimitPolicy, GurtHandler, GurtMiddleware, Extensions, ServerContext, Route};
 pub use client::{GurtClient, GurtClientConfig};
+pub use cache::{CacheConfig, CacheControl};
+pub use range::{ByteRange, RangeRequest};
+pub use compression::ContentEncoding;
+pub use cookie::{Cookie, CookieJar};
+pub use multipart::{Multipart, MultipartReader};
+pub use channel::{GurtChannel, ChannelMessage, ChannelSender, ChannelReceiver};
+pub use events::{Event, EventSender, EventStream};
+pub use handshake::{Capabilities, Handshake, ProtocolVersion};
 
 pub mod prelude {
     pub use crate::{
         GurtError, Result,
-        GurtMessage, GurtRequest, GurtResponse, GurtResponseHead,
+        GurtMessage, GurtRequest, GurtResponse, GurtResponseHead, GurtHeaders, GurtBodyStream, QueryParams,
         GURT_VERSION, DEFAULT_PORT,
-        CryptoManager, TlsConfig, GURT_ALPN, TLS_VERSION,
-        GurtServer, GurtHandler, ServerContext, Route,
+        CryptoManager, TlsConfig, ClientAuthMode, PeerIdentity, GURT_ALPN, TLS_VERSION,
+        GurtServer, ConnectionLimitPolicy, GurtHandler, GurtMiddleware, ServerContext, Route,
         GurtClient, GurtClientConfig,
     };
 }
\ No newline at end of file
@@ -1,9 +1,14 @@
 use crate::{GurtError, Result, GURT_VERSION};
-use crate::protocol::{GurtStatusCode, PROTOCOL_PREFIX, HEADER_SEPARATOR, BODY_SEPARATOR};
-use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use crate::body::GurtBodyStream;
+use crate::cookie::Cookie;
+use crate::events::{EventSender, EVENT_STREAM_CONTENT_TYPE};
+use crate::multipart::{Multipart, MultipartReader};
+use crate::protocol::{GurtStatusCode, PROTOCOL_PREFIX, HEADER_SEPARATOR, BODY_SEPARATOR, MAX_MESSAGE_SIZE};
+use crate::query::{QueryParams, deserialize_query, split_path_and_query};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use std::fmt;
 use chrono::Utc;
+use tokio::io::AsyncRead;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GurtMethod {
@@ -31,6 +36,12 @@ impl GurtMethod {
             _ => Err(GurtError::InvalidMessage(format!("Unsupported method: {}", s))),
         }
     }
+    
+    /// Whether sending a request with this method twice has the same effect as sending it once,
+    /// so that it can be retried after a connection fails.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, Self::GET | Self::HEAD | Self::OPTIONS | Self::PUT | Self::DELETE)
+    }
 }
 
 impl fmt::Display for GurtMethod {
@@ -49,7 +60,327 @@ impl fmt::Display for GurtMethod {
     }
 }
 
-pub type GurtHeaders = HashMap<String, String>;
+pub use crate::headers::GurtHeaders;
+
+pub(crate) fn is_chunked(headers: &GurtHeaders) -> bool {
+    headers.get_all("transfer-encoding")
+        .any(|value| value.split(',').any(|coding| coding.trim().eq_ignore_ascii_case("chunked")))
+}
+
+fn decode_chunked_body(data: &[u8]) -> Result<Vec<u8>> {
+    let mut decoder = ChunkedDecoder::new();
+    let body = decoder.decode(data)?;
+    
+    if !decoder.is_complete() {
+        return Err(GurtError::InvalidMessage("Incomplete chunked body".to_string()));
+    }
+    
+    Ok(body)
+}
+
+/// Encodes a body of unknown length as a sequence of `transfer-encoding: chunked` chunks.
+#[derive(Debug, Default)]
+pub struct ChunkedEncoder {
+    finished: bool,
+}
+
+impl ChunkedEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Encodes `data` as a single chunk. Empty input produces no output, since a
+    /// zero-length chunk terminates the body.
+    pub fn encode(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if self.finished {
+            return Err(GurtError::Protocol("Chunked body already finished".to_string()));
+        }
+        
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let mut chunk = format!("{:x}{}", data.len(), HEADER_SEPARATOR).into_bytes();
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(HEADER_SEPARATOR.as_bytes());
+        Ok(chunk)
+    }
+    
+    /// Produces the terminating zero-length chunk.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        if self.finished {
+            return Err(GurtError::Protocol("Chunked body already finished".to_string()));
+        }
+        
+        self.finished = true;
+        Ok(format!("0{}{}", HEADER_SEPARATOR, HEADER_SEPARATOR).into_bytes())
+    }
+    
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChunkedState {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailer,
+    Done,
+}
+
+/// Incrementally decodes a `transfer-encoding: chunked` body as bytes arrive.
+#[derive(Debug)]
+pub struct ChunkedDecoder {
+    state: ChunkedState,
+    pending: Vec<u8>,
+    decoded_len: usize,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: ChunkedState::Size,
+            pending: Vec::new(),
+            decoded_len: 0,
+        }
+    }
+    
+    /// Feeds raw bytes into the decoder and returns the body bytes decoded so far.
+    /// Bytes after the terminating chunk are kept and available via `remaining`.
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.pending.extend_from_slice(data);
+        
+        let separator = HEADER_SEPARATOR.as_bytes();
+        let mut output = Vec::new();
+        let mut pos = 0;
+        
+        loop {
+            match self.state {
+                ChunkedState::Size => {
+                    let line_end = match self.pending[pos..].windows(separator.len()).position(|w| w == separator) {
+                        Some(end) => end,
+                        None => break,
+                    };
+                    
+                    let line = std::str::from_utf8(&self.pending[pos..pos + line_end])
+                        .map_err(|_| GurtError::InvalidMessage("Invalid UTF-8 in chunk size".to_string()))?;
+                    let size_str = line.split(';').next().unwrap_or("").trim();
+                    let size = usize::from_str_radix(size_str, 16)
+                        .map_err(|_| GurtError::InvalidMessage(format!("Invalid chunk size: {}", size_str)))?;
+                    
+                    pos += line_end + separator.len();
+                    self.state = if size == 0 { ChunkedState::Trailer } else { ChunkedState::Data(size) };
+                }
+                ChunkedState::Data(remaining) => {
+                    let available = self.pending.len() - pos;
+                    if available == 0 {
+                        break;
+                    }
+                    
+                    let take = remaining.min(available);
+                    output.extend_from_slice(&self.pending[pos..pos + take]);
+                    pos += take;
+                    
+                    self.state = if take == remaining { ChunkedState::DataEnd } else { ChunkedState::Data(remaining - take) };
+                }
+                ChunkedState::DataEnd => {
+                    if self.pending.len() - pos < separator.len() {
+                        break;
+                    }
+                    
+                    if &self.pending[pos..pos + separator.len()] != separator {
+                        return Err(GurtError::InvalidMessage("Missing CRLF after chunk data".to_string()));
+                    }
+                    
+                    pos += separator.len();
+                    self.state = ChunkedState::Size;
+                }
+                ChunkedState::Trailer => {
+                    let line_end = match self.pending[pos..].windows(separator.len()).position(|w| w == separator) {
+                        Some(end) => end,
+                        None => break,
+                    };
+                    
+                    pos += line_end + separator.len();
+                    if line_end == 0 {
+                        self.state = ChunkedState::Done;
+                    }
+                }
+                ChunkedState::Done => break,
+            }
+        }
+        
+        self.pending.drain(..pos);
+        self.decoded_len += output.len();
+        Ok(output)
+    }
+    
+    pub fn is_complete(&self) -> bool {
+        self.state == ChunkedState::Done
+    }
+    
+    /// Total number of body bytes decoded so far.
+    pub fn decoded_len(&self) -> usize {
+        self.decoded_len
+    }
+    
+    /// Bytes received after the terminating chunk, empty until the body is complete.
+    pub fn remaining(&self) -> &[u8] {
+        if self.is_complete() {
+            &self.pending
+        } else {
+            &[]
+        }
+    }
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Finds where a chunked body ends by walking its chunk-size lines, skipping over
+/// chunk data without copying it. Returns `None` while the body is incomplete.
+fn chunked_body_end(body: &[u8]) -> Result<Option<usize>> {
+    let separator = HEADER_SEPARATOR.as_bytes();
+    let find_line_end = |from: usize| body.get(from..)
+        .and_then(|rest| rest.windows(separator.len()).position(|w| w == separator));
+    let mut pos = 0;
+    
+    loop {
+        let line_end = match find_line_end(pos) {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        
+        let line = std::str::from_utf8(&body[pos..pos + line_end])
+            .map_err(|_| GurtError::InvalidMessage("Invalid UTF-8 in chunk size".to_string()))?;
+        let size_str = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_str, 16)
+            .map_err(|_| GurtError::InvalidMessage(format!("Invalid chunk size: {}", size_str)))?;
+        pos += line_end + separator.len();
+        
+        if size == 0 {
+            break;
+        }
+        
+        let data_end = match pos.checked_add(size) {
+            Some(end) if end <= MAX_MESSAGE_SIZE => end,
+            _ => return Err(GurtError::InvalidMessage(format!("Chunk too large: {} bytes", size))),
+        };
+        if body.len() < data_end + separator.len() {
+            return Ok(None);
+        }
+        if &body[data_end..data_end + separator.len()] != separator {
+            return Err(GurtError::InvalidMessage("Missing CRLF after chunk data".to_string()));
+        }
+        pos = data_end + separator.len();
+    }
+    
+    loop {
+        let line_end = match find_line_end(pos) {
+            Some(end) => end,
+            None => return Ok(None),
+        };
+        
+        pos += line_end + separator.len();
+        if line_end == 0 {
+            return Ok(Some(pos));
+        }
+    }
+}
+
+pub(crate) enum MessageFrame {
+    Incomplete,
+    Complete(usize),
+    TooLarge,
+    Invalid(String),
+}
+
+/// Determines whether `buffer` starts with a complete message, using the
+/// `content-length` or `transfer-encoding: chunked` headers to frame the body.
+/// Messages with `has_body` unset, such as responses to HEAD, end at the headers.
+pub(crate) fn frame_message(buffer: &[u8], has_body: bool) -> MessageFrame {
+    let body_separator = BODY_SEPARATOR.as_bytes();
+    let headers_end = match buffer.windows(body_separator.len()).position(|w| w == body_separator) {
+        Some(pos) => pos,
+        None if buffer.len() > MAX_MESSAGE_SIZE => return MessageFrame::TooLarge,
+        None => return MessageFrame::Incomplete,
+    };
+    let body_start = headers_end + body_separator.len();
+    
+    if !has_body {
+        return MessageFrame::Complete(body_start);
+    }
+    
+    let headers_str = match std::str::from_utf8(&buffer[..headers_end]) {
+        Ok(s) => s,
+        Err(_) => return MessageFrame::Invalid("Invalid UTF-8 in headers".to_string()),
+    };
+    
+    let mut content_length: Option<usize> = None;
+    let mut chunked = false;
+    for line in headers_str.split(HEADER_SEPARATOR).skip(1) {
+        if let Some((key, value)) = line.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("content-length") {
+                let length = match value.trim().parse::<usize>() {
+                    Ok(length) => length,
+                    Err(_) => return MessageFrame::Invalid(format!("Invalid content-length: {}", value.trim())),
+                };
+                // Peers that pick different lengths would disagree on where the message ends
+                if content_length.is_some_and(|previous| previous != length) {
+                    return MessageFrame::Invalid("Conflicting content-length headers".to_string());
+                }
+                content_length = Some(length);
+            } else if key.trim().eq_ignore_ascii_case("transfer-encoding") {
+                chunked |= value.split(',').any(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+            }
+        }
+    }
+    
+    if chunked && content_length.is_some() {
+        return MessageFrame::Invalid("Both content-length and chunked transfer-encoding are set".to_string());
+    }
+    let content_length = content_length.unwrap_or(0);
+    
+    if chunked {
+        return match chunked_body_end(&buffer[body_start..]) {
+            Ok(Some(body_length)) => MessageFrame::Complete(body_start + body_length),
+            Ok(None) if buffer.len() > MAX_MESSAGE_SIZE => MessageFrame::TooLarge,
+            Ok(None) => MessageFrame::Incomplete,
+            Err(e) => MessageFrame::Invalid(e.to_string()),
+        };
+    }
+    
+    if body_start.saturating_add(content_length) > MAX_MESSAGE_SIZE {
+        return MessageFrame::TooLarge;
+    }
+    
+    if buffer.len() >= body_start + content_length {
+        MessageFrame::Complete(body_start + content_length)
+    } else {
+        MessageFrame::Incomplete
+    }
+}
+
+fn encode_body(headers: &mut GurtHeaders, body: &[u8]) -> Vec<u8> {
+    if is_chunked(headers) {
+        headers.remove("content-length");
+        
+        let mut encoder = ChunkedEncoder::new();
+        let mut encoded = encoder.encode(body).unwrap_or_default();
+        encoded.extend(encoder.finish().unwrap_or_default());
+        encoded
+    } else {
+        if !headers.contains_key("content-length") {
+            headers.insert("content-length".to_string(), body.len().to_string());
+        }
+        body.to_vec()
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GurtRequest {
@@ -72,7 +403,13 @@ impl GurtRequest {
     }
     
     pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
-        self.headers.insert(key.into().to_lowercase(), value.into());
+        self.headers.insert(key, value);
+        self
+    }
+    
+    /// Adds a value for `key` without replacing existing ones, e.g. for repeated `set-cookie` headers.
+    pub fn append_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.append(key, value);
         self
     }
     
@@ -87,7 +424,84 @@ impl GurtRequest {
     }
     
     pub fn header(&self, key: &str) -> Option<&String> {
-        self.headers.get(&key.to_lowercase())
+        self.headers.get(key)
+    }
+    
+    /// The value of the cookie called `name` sent in the `cookie` header.
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.cookies().into_iter()
+            .find(|(cookie, _)| cookie == name)
+            .map(|(_, value)| value)
+    }
+    
+    /// Every cookie sent with the request, as name/value pairs.
+    pub fn cookies(&self) -> Vec<(String, String)> {
+        self.headers.get_all("cookie")
+            .flat_map(|value| crate::cookie::parse_cookie_header(value))
+            .collect()
+    }
+    
+    /// The request path without its query string.
+    pub fn path_without_query(&self) -> &str {
+        split_path_and_query(&self.path).0
+    }
+    
+    /// The raw query string, without the leading `?`.
+    pub fn query_string(&self) -> Option<&str> {
+        split_path_and_query(&self.path).1
+    }
+    
+    pub fn query_params(&self) -> QueryParams {
+        self.query_string().map(QueryParams::parse).unwrap_or_default()
+    }
+    
+    /// Deserializes the query string into `T`, e.g. a struct of optional filters.
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T> {
+        deserialize_query(self.query_string())
+    }
+    
+    /// Sets an `application/x-www-form-urlencoded` body serialized from `data`.
+    pub fn with_form_body<T: Serialize>(mut self, data: &T) -> Result<Self> {
+        let form = serde_urlencoded::to_string(data)
+            .map_err(|e| GurtError::invalid_message(format!("Failed to encode form: {}", e)))?;
+        self.body = form.into_bytes();
+        self.headers.insert("content-type", "application/x-www-form-urlencoded");
+        Ok(self)
+    }
+    
+    /// Sets a `multipart/form-data` body.
+    pub fn with_multipart_body(mut self, form: &Multipart) -> Self {
+        self.body = form.to_bytes();
+        self.headers.insert("content-type", form.content_type());
+        self
+    }
+    
+    /// The fields of an `application/x-www-form-urlencoded` body.
+    pub fn form_params(&self) -> QueryParams {
+        QueryParams::parse(&String::from_utf8_lossy(&self.body))
+    }
+    
+    /// Deserializes an `application/x-www-form-urlencoded` body into `T`.
+    pub fn form<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_urlencoded::from_bytes(&self.body)
+            .map_err(|e| GurtError::invalid_message(format!("Invalid form body: {}", e)))
+    }
+    
+    /// Reads a `multipart/form-data` body part by part.
+    pub fn multipart(&self) -> Result<MultipartReader<&[u8]>> {
+        let content_type = self.header("content-type").map(|value| value.as_str()).unwrap_or_default();
+        MultipartReader::from_content_type(self.body.as_slice(), content_type)
+    }
+    
+    /// Marks the body to be sent with `transfer-encoding: chunked` instead of `content-length`.
+    pub fn with_chunked_encoding(mut self) -> Self {
+        self.headers.remove("content-length");
+        self.headers.insert("transfer-encoding".to_string(), "chunked".to_string());
+        self
+    }
+    
+    pub fn is_chunked(&self) -> bool {
+        is_chunked(&self.headers)
     }
     
     pub fn text(&self) -> Result<String> {
@@ -147,12 +561,12 @@ impl GurtRequest {
             }
             
             if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_lowercase();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
+                headers.append(line[..colon_pos].trim(), line[colon_pos + 1..].trim());
             }
         }
         
+        let body = if is_chunked(&headers) { decode_chunked_body(&body)? } else { body };
+        
         Ok(Self {
             method,
             path,
@@ -162,42 +576,12 @@ impl GurtRequest {
         })
     }
     
-    pub fn to_string(&self) -> String {
-        let mut message = format!("{} {} {}{}{}", 
-            self.method, self.path, PROTOCOL_PREFIX, self.version, HEADER_SEPARATOR);
-        
-        let mut headers = self.headers.clone();
-        if !headers.contains_key("content-length") {
-            headers.insert("content-length".to_string(), self.body.len().to_string());
-        }
-        
-        if !headers.contains_key("user-agent") {
-            headers.insert("user-agent".to_string(), format!("GURT-Client/{}", GURT_VERSION));
-        }
-        
-        for (key, value) in &headers {
-            message.push_str(&format!("{}: {}{}", key, value, HEADER_SEPARATOR));
-        }
-        
-        message.push_str(HEADER_SEPARATOR);
-        
-        if !self.body.is_empty() {
-            if let Ok(body_str) = std::str::from_utf8(&self.body) {
-                message.push_str(body_str);
-            }
-        }
-        
-        message
-    }
-    
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut message = format!("{} {} {}{}{}", 
             self.method, self.path, PROTOCOL_PREFIX, self.version, HEADER_SEPARATOR);
         
         let mut headers = self.headers.clone();
-        if !headers.contains_key("content-length") {
-            headers.insert("content-length".to_string(), self.body.len().to_string());
-        }
+        let body = encode_body(&mut headers, &self.body);
         
         if !headers.contains_key("user-agent") {
             headers.insert("user-agent".to_string(), format!("GURT-Client/{}", GURT_VERSION));
@@ -210,12 +594,18 @@ impl GurtRequest {
         message.push_str(HEADER_SEPARATOR);
         
         let mut bytes = message.into_bytes();
-        bytes.extend_from_slice(&self.body);
+        bytes.extend_from_slice(&body);
         
         bytes
     }
 }
 
+impl fmt::Display for GurtRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GurtResponse {
     pub version: String,
@@ -223,6 +613,9 @@ pub struct GurtResponse {
     pub status_message: String,
     pub headers: GurtHeaders,
     pub body: Vec<u8>,
+    /// Streamed body, sent in place of `body` when present.
+    #[serde(skip)]
+    pub body_stream: Option<GurtBodyStream>,
 }
 
 #[derive(Debug, Clone)]
@@ -233,14 +626,29 @@ pub struct GurtResponseHead {
     pub headers: GurtHeaders,
 }
 
+impl GurtResponseHead {
+    pub fn status(&self) -> GurtStatusCode {
+        GurtStatusCode::from_u16(self.status_code)
+    }
+    
+    pub fn header(&self, key: &str) -> Option<&String> {
+        self.headers.get(key)
+    }
+    
+    pub fn is_chunked(&self) -> bool {
+        is_chunked(&self.headers)
+    }
+}
+
 impl GurtResponse {
     pub fn new(status_code: GurtStatusCode) -> Self {
         Self {
             version: GURT_VERSION.to_string(),
-            status_code: status_code as u16,
+            status_code: status_code.as_u16(),
             status_message: status_code.message().to_string(),
             headers: GurtHeaders::new(),
             body: Vec::new(),
+            body_stream: None,
         }
     }
     
@@ -260,12 +668,34 @@ impl GurtResponse {
         Self::new(GurtStatusCode::Forbidden)
     }
     
+    pub fn no_content() -> Self {
+        Self::new(GurtStatusCode::NoContent)
+    }
+    
+    pub fn not_modified() -> Self {
+        Self::new(GurtStatusCode::NotModified)
+    }
+    
+    pub fn unauthorized() -> Self {
+        Self::new(GurtStatusCode::Unauthorized)
+    }
+    
+    pub fn conflict() -> Self {
+        Self::new(GurtStatusCode::Conflict)
+    }
+    
     pub fn internal_server_error() -> Self {
         Self::new(GurtStatusCode::InternalServerError)
     }
     
     pub fn with_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
-        self.headers.insert(key.into().to_lowercase(), value.into());
+        self.headers.insert(key, value);
+        self
+    }
+    
+    /// Adds a value for `key` without replacing existing ones, e.g. for repeated `set-cookie` headers.
+    pub fn append_header<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.headers.append(key, value);
         self
     }
     
@@ -279,6 +709,37 @@ impl GurtResponse {
         self
     }
     
+    /// Streams the body from `reader`. With a known `length` the body is sent with
+    /// `content-length`, otherwise it is sent with chunked transfer encoding.
+    pub fn with_reader_body<R: AsyncRead + Send + 'static>(self, reader: R, length: Option<u64>) -> Self {
+        self.with_stream_body(GurtBodyStream::from_reader(reader), length)
+    }
+    
+    pub fn with_stream_body(mut self, stream: GurtBodyStream, length: Option<u64>) -> Self {
+        self.body.clear();
+        self.body_stream = Some(stream);
+        match length {
+            Some(length) => self.with_header("content-length", length.to_string()),
+            None => self.with_chunked_encoding(),
+        }
+    }
+    
+    pub fn is_streaming(&self) -> bool {
+        self.body_stream.is_some()
+    }
+    
+    /// Creates a `200` response that stays open and emits the events sent through the
+    /// returned [`EventSender`], buffering up to `capacity` of them.
+    pub fn event_stream(capacity: usize) -> (EventSender, Self) {
+        let (sender, body) = GurtBodyStream::channel(capacity);
+        let response = Self::ok()
+            .with_header("content-type", EVENT_STREAM_CONTENT_TYPE)
+            .with_header("cache-control", "no-cache")
+            .with_stream_body(body, None);
+        
+        (EventSender::new(sender), response)
+    }
+    
     pub fn with_json_body<T: Serialize>(mut self, data: &T) -> Result<Self> {
         let json = serde_json::to_string(data)?;
         self.body = json.into_bytes();
@@ -287,7 +748,30 @@ impl GurtResponse {
     }
     
     pub fn header(&self, key: &str) -> Option<&String> {
-        self.headers.get(&key.to_lowercase())
+        self.headers.get(key)
+    }
+    
+    /// Adds a `set-cookie` header for `cookie`, keeping any cookies already set.
+    pub fn with_cookie(self, cookie: Cookie) -> Self {
+        self.append_header("set-cookie", cookie.to_string())
+    }
+    
+    /// The cookies set by the response's `set-cookie` headers.
+    pub fn set_cookies(&self) -> Vec<Cookie> {
+        self.headers.get_all("set-cookie")
+            .filter_map(|value| Cookie::parse(value))
+            .collect()
+    }
+    
+    /// Marks the body to be sent with `transfer-encoding: chunked` instead of `content-length`.
+    pub fn with_chunked_encoding(mut self) -> Self {
+        self.headers.remove("content-length");
+        self.headers.insert("transfer-encoding".to_string(), "chunked".to_string());
+        self
+    }
+    
+    pub fn is_chunked(&self) -> bool {
+        is_chunked(&self.headers)
     }
     
     pub fn text(&self) -> Result<String> {
@@ -296,16 +780,31 @@ impl GurtResponse {
             .map_err(|e| GurtError::InvalidMessage(format!("Invalid UTF-8 body: {}", e)))
     }
     
+    pub fn status(&self) -> GurtStatusCode {
+        GurtStatusCode::from_u16(self.status_code)
+    }
+    
+    /// Replaces the status code and resets the status message to match it.
+    pub fn with_status(mut self, status: GurtStatusCode) -> Self {
+        self.status_code = status.as_u16();
+        self.status_message = status.message().to_string();
+        self
+    }
+    
     pub fn is_success(&self) -> bool {
-        self.status_code >= 200 && self.status_code < 300
+        self.status().is_success()
+    }
+    
+    pub fn is_redirect(&self) -> bool {
+        self.status().is_redirect()
     }
     
     pub fn is_client_error(&self) -> bool {
-        self.status_code >= 400 && self.status_code < 500
+        self.status().is_client_error()
     }
     
     pub fn is_server_error(&self) -> bool {
-        self.status_code >= 500
+        self.status().is_server_error()
     }
     
     pub fn parse(data: &str) -> Result<Self> {
@@ -354,9 +853,7 @@ impl GurtResponse {
         let status_message = if parts.len() > 2 {
             parts[2].to_string()
         } else {
-            GurtStatusCode::from_u16(status_code)
-                .map(|sc| sc.message().to_string())
-                .unwrap_or_else(|| "Unknown".to_string())
+            GurtStatusCode::from_u16(status_code).message().to_string()
         };
         
         let mut headers = GurtHeaders::new();
@@ -367,64 +864,41 @@ impl GurtResponse {
             }
             
             if let Some(colon_pos) = line.find(':') {
-                let key = line[..colon_pos].trim().to_lowercase();
-                let value = line[colon_pos + 1..].trim().to_string();
-                headers.insert(key, value);
+                headers.append(line[..colon_pos].trim(), line[colon_pos + 1..].trim());
             }
         }
         
+        let body = if is_chunked(&headers) { decode_chunked_body(&body)? } else { body };
+        
         Ok(Self {
             version,
             status_code,
             status_message,
             headers,
             body,
+            body_stream: None,
         })
     }
     
-    pub fn to_string(&self) -> String {
-        let mut message = format!("{}{} {} {}{}", 
-            PROTOCOL_PREFIX, self.version, self.status_code, self.status_message, HEADER_SEPARATOR);
-        
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut headers = self.headers.clone();
-        if !headers.contains_key("content-length") {
-            headers.insert("content-length".to_string(), self.body.len().to_string());
-        }
+        let body = encode_body(&mut headers, &self.body);
         
-        if !headers.contains_key("server") {
-            headers.insert("server".to_string(), format!("GURT/{}", GURT_VERSION));
-        }
-        
-        if !headers.contains_key("date") {
-            let now = Utc::now();
-            let date_str = now.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
-            headers.insert("date".to_string(), date_str);
-        }
+        let mut bytes = self.encode_head(headers);
+        bytes.extend_from_slice(&body);
         
-        for (key, value) in &headers {
-            message.push_str(&format!("{}: {}{}", key, value, HEADER_SEPARATOR));
-        }
-        
-        message.push_str(HEADER_SEPARATOR);
-        
-        if !self.body.is_empty() {
-            if let Ok(body_str) = std::str::from_utf8(&self.body) {
-                message.push_str(body_str);
-            }
-        }
-        
-        message
+        bytes
     }
     
-    pub fn to_bytes(&self) -> Vec<u8> {
+    /// Serializes the status line and headers only, for responses whose body is written separately.
+    pub fn head_bytes(&self) -> Vec<u8> {
+        self.encode_head(self.headers.clone())
+    }
+    
+    fn encode_head(&self, mut headers: GurtHeaders) -> Vec<u8> {
         let mut message = format!("{}{} {} {}{}", 
             PROTOCOL_PREFIX, self.version, self.status_code, self.status_message, HEADER_SEPARATOR);
         
-        let mut headers = self.headers.clone();
-        if !headers.contains_key("content-length") {
-            headers.insert("content-length".to_string(), self.body.len().to_string());
-        }
-        
         if !headers.contains_key("server") {
             headers.insert("server".to_string(), format!("GURT/{}", GURT_VERSION));
         }
@@ -441,10 +915,13 @@ impl GurtResponse {
         
         message.push_str(HEADER_SEPARATOR);
         
-        let mut bytes = message.into_bytes();
-        bytes.extend_from_slice(&self.body);
-        
-        bytes
+        message.into_bytes()
+    }
+}
+
+impl fmt::Display for GurtResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
     }
 }
 
@@ -501,8 +978,8 @@ impl GurtMessage {
 impl fmt::Display for GurtMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            GurtMessage::Request(req) => write!(f, "{}", req.to_string()),
-            GurtMessage::Response(res) => write!(f, "{}", res.to_string()),
+            GurtMessage::Request(req) => write!(f, "{}", req),
+            GurtMessage::Response(res) => write!(f, "{}", res),
         }
     }
 }
@@ -536,6 +1013,30 @@ mod tests {
         assert_eq!(response.text().unwrap(), "<html></html>");
     }
     
+    #[test]
+    fn test_response_status_codes() {
+        let conflict = GurtResponse::parse("GURT/1.0.0 409 CONFLICT\r\n\r\n").unwrap();
+        assert_eq!(conflict.status(), GurtStatusCode::Conflict);
+        assert!(conflict.is_client_error());
+        
+        let unknown = GurtResponse::parse("GURT/1.0.0 299\r\n\r\n").unwrap();
+        assert_eq!(unknown.status(), GurtStatusCode::Other(299));
+        assert_eq!(unknown.status_message, "UNKNOWN");
+        assert!(unknown.is_success());
+        
+        let reparsed = GurtResponse::parse_bytes(&unknown.to_bytes()).unwrap();
+        assert_eq!(reparsed.status_code, 299);
+        
+        let not_modified = GurtResponse::ok().with_status(GurtStatusCode::NotModified);
+        assert_eq!(not_modified.status_code, 304);
+        assert_eq!(not_modified.status_message, "NOT_MODIFIED");
+        assert!(not_modified.is_redirect());
+        
+        for code in 100..600 {
+            assert_eq!(GurtStatusCode::from_u16(code).as_u16(), code);
+        }
+    }
+    
     #[test]
     fn test_request_building() {
         let request = GurtRequest::new(GurtMethod::GET, "/test".to_string())
@@ -562,4 +1063,153 @@ mod tests {
         assert_eq!(parsed.status_code, response.status_code);
         assert_eq!(parsed.body, response.body);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_repeated_headers_round_trip() {
+        let response = GurtResponse::ok()
+            .with_header("Content-Type", "text/plain")
+            .append_header("Set-Cookie", "a=1")
+            .append_header("Set-Cookie", "b=2")
+            .with_header("X-Injected", "ok\r\nevil: 1");
+        
+        let raw = response.to_string();
+        assert!(raw.contains("Content-Type: text/plain\r\nSet-Cookie: a=1\r\nSet-Cookie: b=2\r\n"));
+        assert!(!raw.contains("evil"));
+        
+        let parsed = GurtResponse::parse(&raw).unwrap();
+        assert_eq!(parsed.header("set-cookie").unwrap(), "a=1");
+        assert_eq!(parsed.headers.get_all("SET-COOKIE").collect::<Vec<_>>(), ["a=1", "b=2"]);
+    }
+    
+    #[test]
+    fn test_chunked_response_round_trip() {
+        let response = GurtResponse::ok()
+            .with_header("Content-Type", "text/plain")
+            .with_string_body("streamed body")
+            .with_chunked_encoding();
+        
+        let bytes = response.to_bytes();
+        let raw = String::from_utf8_lossy(&bytes);
+        assert!(!raw.contains("content-length"));
+        assert!(raw.ends_with("d\r\nstreamed body\r\n0\r\n\r\n"));
+        
+        let parsed = GurtResponse::parse_bytes(&bytes).expect("Failed to parse chunked response");
+        assert!(parsed.is_chunked());
+        assert_eq!(parsed.text().unwrap(), "streamed body");
+    }
+    
+    #[test]
+    fn test_chunked_request_parsing() {
+        let raw = "POST /upload GURT/1.0.0\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\r\n";
+        let request = GurtRequest::parse(raw).expect("Failed to parse chunked request");
+        
+        assert!(request.is_chunked());
+        assert_eq!(request.text().unwrap(), "hello world");
+        
+        let truncated = "POST /upload GURT/1.0.0\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhel";
+        assert!(GurtRequest::parse(truncated).is_err());
+    }
+    
+    #[test]
+    fn test_chunked_decoder_incremental() {
+        let mut encoder = ChunkedEncoder::new();
+        let mut encoded = encoder.encode(b"first").unwrap();
+        encoded.extend(encoder.encode(b"").unwrap());
+        encoded.extend(encoder.encode(b"second chunk").unwrap());
+        encoded.extend(encoder.finish().unwrap());
+        assert!(encoder.encode(b"late").is_err());
+        encoded.extend_from_slice(b"GET / GURT/1.0.0");
+        
+        let mut decoder = ChunkedDecoder::new();
+        let mut decoded = Vec::new();
+        for byte in &encoded {
+            decoded.extend(decoder.decode(std::slice::from_ref(byte)).unwrap());
+        }
+        
+        assert!(decoder.is_complete());
+        assert_eq!(decoded, b"firstsecond chunk");
+        assert_eq!(decoder.decoded_len(), decoded.len());
+        assert_eq!(decoder.remaining(), b"GET / GURT/1.0.0");
+        
+        let mut invalid = ChunkedDecoder::new();
+        assert!(invalid.decode(b"zz\r\n").is_err());
+    }
+    
+    #[test]
+    fn test_message_framing() {
+        let partial = b"POST /upload GURT/1.0.0\r\ncontent-length: 10\r\n\r\n{\"a\":";
+        assert!(matches!(frame_message(partial, true), MessageFrame::Incomplete));
+        
+        let complete = b"POST /upload GURT/1.0.0\r\nContent-Length: 10\r\n\r\n{\"a\":\"bc\"}";
+        assert!(matches!(frame_message(complete, true), MessageFrame::Complete(n) if n == complete.len()));
+        
+        let pipelined = b"GET /a GURT/1.0.0\r\n\r\nGET /b GURT/1.0.0\r\n\r\n";
+        assert!(matches!(frame_message(pipelined, true), MessageFrame::Complete(21)));
+        
+        assert!(matches!(frame_message(b"GET / GURT/1.0.0\r\nhost: a", true), MessageFrame::Incomplete));
+        
+        let chunked = b"POST /upload GURT/1.0.0\r\ntransfer-encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\nGET / GURT/1.0.0\r\n\r\n";
+        assert!(matches!(frame_message(chunked, true), MessageFrame::Complete(68)));
+        assert!(matches!(frame_message(&chunked[..60], true), MessageFrame::Incomplete));
+        
+        let head_response = b"GURT/1.0.0 200 OK\r\ncontent-length: 512\r\n\r\n";
+        assert!(matches!(frame_message(head_response, false), MessageFrame::Complete(n) if n == head_response.len()));
+        assert!(matches!(frame_message(head_response, true), MessageFrame::Incomplete));
+    }
+    
+    #[test]
+    fn test_message_framing_limits() {
+        let oversized = format!("POST / GURT/1.0.0\r\ncontent-length: {}\r\n\r\n", MAX_MESSAGE_SIZE);
+        assert!(matches!(frame_message(oversized.as_bytes(), true), MessageFrame::TooLarge));
+        
+        let invalid = b"POST / GURT/1.0.0\r\ncontent-length: ten\r\n\r\n";
+        assert!(matches!(frame_message(invalid, true), MessageFrame::Invalid(_)));
+        
+        let invalid_chunk = b"POST / GURT/1.0.0\r\ntransfer-encoding: chunked\r\n\r\n3\r\nabcd\r\n";
+        assert!(matches!(frame_message(invalid_chunk, true), MessageFrame::Invalid(_)));
+        
+        let huge_chunk = b"POST / GURT/1.0.0\r\ntransfer-encoding: chunked\r\n\r\nffffffffffffffff\r\n";
+        assert!(matches!(frame_message(huge_chunk, true), MessageFrame::Invalid(_)));
+        
+        let conflicting = b"POST / GURT/1.0.0\r\ncontent-length: 3\r\ncontent-length: 5\r\n\r\nabcde";
+        assert!(matches!(frame_message(conflicting, true), MessageFrame::Invalid(_)));
+        
+        let repeated = b"POST / GURT/1.0.0\r\ncontent-length: 3\r\ncontent-length: 3\r\n\r\nabc";
+        assert!(matches!(frame_message(repeated, true), MessageFrame::Complete(n) if n == repeated.len()));
+        
+        let smuggled = b"POST / GURT/1.0.0\r\ncontent-length: 3\r\ntransfer-encoding: chunked\r\n\r\n0\r\n\r\n";
+        assert!(matches!(frame_message(smuggled, true), MessageFrame::Invalid(_)));
+    }
+    
+    #[test]
+    fn test_chunked_framing_with_extensions_and_trailers() {
+        let message = b"POST / GURT/1.0.0\r\ntransfer-encoding: chunked\r\n\r\n3;name=value\r\nabc\r\n0\r\nx-checksum: 1\r\n\r\nGET";
+        assert!(matches!(frame_message(message, true), MessageFrame::Complete(n) if n == message.len() - 3));
+        
+        for end in 0..message.len() - 3 {
+            assert!(matches!(frame_message(&message[..end], true), MessageFrame::Incomplete));
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_form_bodies() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Signup {
+            name: String,
+            age: u32,
+        }
+        
+        let signup = Signup { name: "Ada Lovelace & co".to_string(), age: 36 };
+        let request = GurtRequest::new(GurtMethod::POST, "/signup".to_string()).with_form_body(&signup).unwrap();
+        assert_eq!(request.header("content-type").unwrap(), "application/x-www-form-urlencoded");
+        assert_eq!(request.form_params().get("name"), Some("Ada Lovelace & co"));
+        assert_eq!(request.form::<Signup>().unwrap(), signup);
+        
+        let form = crate::Multipart::new().text("name", "Ada").file("avatar", "ada.png", vec![1, 2, 3]);
+        let request = GurtRequest::parse_bytes(&GurtRequest::new(GurtMethod::POST, "/upload".to_string()).with_multipart_body(&form).to_bytes()).unwrap();
+        let mut reader = request.multipart().unwrap();
+        assert_eq!(reader.next_field().await.unwrap().unwrap().text().await.unwrap(), "Ada");
+        assert_eq!(reader.next_field().await.unwrap().unwrap().bytes().await.unwrap(), vec![1, 2, 3]);
+        assert!(reader.next_field().await.unwrap().is_none());
+    }
+}
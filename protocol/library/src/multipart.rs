@@ -0,0 +1,467 @@
+use crate::{GurtError, GurtHeaders, Result};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+const READ_CHUNK_SIZE: usize = 8192;
+/// Upper bound on the header block of a single part.
+const MAX_PART_HEADER_SIZE: usize = 16 * 1024;
+
+/// One part of a [`Multipart`] body.
+#[derive(Debug, Clone)]
+pub struct Part {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    data: Vec<u8>,
+}
+
+impl Part {
+    pub fn text<N: Into<String>, V: Into<String>>(name: N, value: V) -> Self {
+        Self {
+            name: name.into(),
+            file_name: None,
+            content_type: None,
+            data: value.into().into_bytes(),
+        }
+    }
+
+    /// A file upload. The content type defaults to `application/octet-stream`.
+    pub fn file<N: Into<String>, F: Into<String>, B: Into<Vec<u8>>>(name: N, file_name: F, data: B) -> Self {
+        Self {
+            name: name.into(),
+            file_name: Some(file_name.into()),
+            content_type: None,
+            data: data.into(),
+        }
+    }
+
+    pub fn with_content_type<S: Into<String>>(mut self, content_type: S) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    fn write_to(&self, boundary: &str, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+
+        let mut disposition = format!("Content-Disposition: form-data; name=\"{}\"", escape_quoted(&self.name));
+        if let Some(file_name) = &self.file_name {
+            disposition.push_str(&format!("; filename=\"{}\"", escape_quoted(file_name)));
+        }
+        out.extend_from_slice(disposition.as_bytes());
+        out.extend_from_slice(b"\r\n");
+
+        let content_type = match (&self.content_type, &self.file_name) {
+            (Some(content_type), _) => Some(content_type.as_str()),
+            (None, Some(_)) => Some("application/octet-stream"),
+            (None, None) => None,
+        };
+        if let Some(content_type) = content_type {
+            out.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+
+        out.extend_from_slice(b"\r\n");
+        out.extend_from_slice(&self.data);
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+/// Builds a `multipart/form-data` body, e.g. for a form with file uploads.
+#[derive(Debug, Clone)]
+pub struct Multipart {
+    boundary: String,
+    parts: Vec<Part>,
+}
+
+impl Default for Multipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Multipart {
+    pub fn new() -> Self {
+        Self::with_boundary(random_boundary("gurt-form-"))
+    }
+
+    pub fn with_boundary<S: Into<String>>(boundary: S) -> Self {
+        Self { boundary: boundary.into(), parts: Vec::new() }
+    }
+
+    pub fn text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Self {
+        self.part(Part::text(name, value))
+    }
+
+    pub fn file<N: Into<String>, F: Into<String>, B: Into<Vec<u8>>>(self, name: N, file_name: F, data: B) -> Self {
+        self.part(Part::file(name, file_name, data))
+    }
+
+    pub fn part(mut self, part: Part) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// The `content-type` header value to send the body with.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.parts.iter().map(|part| part.data.len() + 128).sum());
+        for part in &self.parts {
+            part.write_to(&self.boundary, &mut out);
+        }
+        out.extend_from_slice(format!("--{}--\r\n", self.boundary).as_bytes());
+        out
+    }
+}
+
+/// A boundary starting with `prefix` that is vanishingly unlikely to occur in any body.
+pub(crate) fn random_boundary(prefix: &str) -> String {
+    // RandomState is seeded randomly per instance, which is plenty for a boundary
+    let mut halves = [0u64; 2];
+    for half in &mut halves {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_nanos());
+        *half = hasher.finish();
+    }
+    format!("{}{:016x}{:016x}", prefix, halves[0], halves[1])
+}
+
+/// Quotes a name for `content-disposition` the way browsers do.
+fn escape_quoted(value: &str) -> String {
+    value.replace('"', "%22").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// The `boundary` parameter of a `multipart/*` content type.
+pub fn parse_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    let mime = params.next()?.trim().to_ascii_lowercase();
+    if !mime.starts_with("multipart/") {
+        return None;
+    }
+
+    params
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("boundary"))
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+/// Looks up a parameter such as `name` or `filename` in a `content-disposition` value.
+fn disposition_param(disposition: &str, param: &str) -> Option<String> {
+    let mut rest = disposition.split_once(';')?.1;
+
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            return None;
+        }
+
+        let (key, after_key) = rest.split_once('=')?;
+        let after_key = after_key.trim_start();
+        let (value, remaining) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((index, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = index + 1;
+                            break;
+                        }
+                        _ => value.push(c),
+                    }
+                }
+                (value, &quoted[end..])
+            }
+            None => match after_key.split_once(';') {
+                Some((value, remaining)) => (value.trim().to_string(), remaining),
+                None => (after_key.trim().to_string(), ""),
+            },
+        };
+
+        if key.trim().eq_ignore_ascii_case(param) {
+            return Some(value);
+        }
+        rest = remaining;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Preamble,
+    AfterBoundary,
+    Body,
+    Done,
+}
+
+/// Reads the parts of a `multipart/form-data` body one at a time, without holding
+/// the whole body in memory.
+pub struct MultipartReader<R> {
+    reader: R,
+    delimiter: Vec<u8>,
+    buffer: Vec<u8>,
+    state: State,
+    eof: bool,
+}
+
+impl<R: AsyncRead + Unpin> MultipartReader<R> {
+    pub fn new(reader: R, boundary: &str) -> Self {
+        Self {
+            reader,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            // The first boundary has no line break before it, so supply one
+            buffer: b"\r\n".to_vec(),
+            state: State::Preamble,
+            eof: false,
+        }
+    }
+
+    /// Reads from `reader`, taking the boundary from a `multipart/form-data` content type.
+    pub fn from_content_type(reader: R, content_type: &str) -> Result<Self> {
+        let boundary = parse_boundary(content_type)
+            .ok_or_else(|| GurtError::invalid_message("Content type is not multipart or has no boundary"))?;
+        Ok(Self::new(reader, &boundary))
+    }
+
+    /// Moves to the next part, skipping whatever is left of the current one.
+    /// Returns `None` after the last part.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_, R>>> {
+        if self.state == State::Body {
+            while self.read_body_chunk().await?.is_some() {}
+        }
+
+        if self.state == State::Preamble {
+            loop {
+                if let Some(position) = find(&self.buffer, &self.delimiter) {
+                    self.buffer.drain(..position + self.delimiter.len());
+                    self.state = State::AfterBoundary;
+                    break;
+                }
+
+                let keep = self.delimiter.len().min(self.buffer.len());
+                self.buffer.drain(..self.buffer.len() - keep);
+                if !self.fill().await? {
+                    return Err(GurtError::invalid_message("Multipart body has no opening boundary"));
+                }
+            }
+        }
+
+        if self.state == State::Done {
+            return Ok(None);
+        }
+
+        while self.buffer.len() < 2 {
+            if !self.fill().await? {
+                return Err(unexpected_end());
+            }
+        }
+        if self.buffer.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(None);
+        }
+
+        let headers_end = loop {
+            if let Some(position) = find(&self.buffer, b"\r\n\r\n") {
+                break position;
+            }
+            if self.buffer.len() > MAX_PART_HEADER_SIZE {
+                return Err(GurtError::invalid_message("Multipart part headers are too large"));
+            }
+            if !self.fill().await? {
+                return Err(unexpected_end());
+            }
+        };
+
+        let header_block = self.buffer.drain(..headers_end + 4).collect::<Vec<u8>>();
+        let header_text = std::str::from_utf8(&header_block)
+            .map_err(|_| GurtError::invalid_message("Multipart part headers are not valid UTF-8"))?;
+
+        let mut headers = GurtHeaders::new();
+        // The boundary line may end in transport padding before its line break
+        for line in header_text.split("\r\n").skip(1) {
+            if let Some((name, value)) = line.split_once(':') {
+                headers.try_append(name.trim(), value.trim())?;
+            }
+        }
+
+        let disposition = headers.get("content-disposition").map(|value| value.as_str()).unwrap_or_default();
+        let name = disposition_param(disposition, "name").unwrap_or_default();
+        let file_name = disposition_param(disposition, "filename");
+
+        self.state = State::Body;
+        Ok(Some(Field { reader: self, headers, name, file_name }))
+    }
+
+    async fn read_body_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            if self.state != State::Body {
+                return Ok(None);
+            }
+
+            if let Some(position) = find(&self.buffer, &self.delimiter) {
+                let chunk: Vec<u8> = self.buffer.drain(..position).collect();
+                self.buffer.drain(..self.delimiter.len());
+                self.state = State::AfterBoundary;
+                if chunk.is_empty() {
+                    return Ok(None);
+                }
+                return Ok(Some(chunk));
+            }
+
+            // Hold back enough bytes to catch a delimiter split across reads
+            let safe = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+            if safe > 0 {
+                return Ok(Some(self.buffer.drain(..safe).collect()));
+            }
+
+            if !self.fill().await? {
+                return Err(unexpected_end());
+            }
+        }
+    }
+
+    async fn fill(&mut self) -> Result<bool> {
+        if self.eof {
+            return Ok(false);
+        }
+
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        let read = self.reader.read(&mut chunk).await?;
+        if read == 0 {
+            self.eof = true;
+            return Ok(false);
+        }
+        self.buffer.extend_from_slice(&chunk[..read]);
+        Ok(true)
+    }
+}
+
+fn unexpected_end() -> GurtError {
+    GurtError::invalid_message("Multipart body ended before its closing boundary")
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// A part being read from a [`MultipartReader`].
+pub struct Field<'a, R> {
+    reader: &'a mut MultipartReader<R>,
+    headers: GurtHeaders,
+    name: String,
+    file_name: Option<String>,
+}
+
+impl<R: AsyncRead + Unpin> Field<'_, R> {
+    /// The form field name from `content-disposition`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The uploaded file's name, for file fields.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers.get("content-type").map(|value| value.as_str())
+    }
+
+    pub fn headers(&self) -> &GurtHeaders {
+        &self.headers
+    }
+
+    /// The next piece of the part's content, or `None` once it has all been read.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        self.reader.read_body_chunk().await
+    }
+
+    /// Reads the rest of the part's content into memory.
+    pub async fn bytes(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        while let Some(chunk) = self.chunk().await? {
+            data.extend(chunk);
+        }
+        Ok(data)
+    }
+
+    pub async fn text(&mut self) -> Result<String> {
+        String::from_utf8(self.bytes().await?)
+            .map_err(|e| GurtError::invalid_message(format!("Invalid UTF-8 in field {}: {}", self.name, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_multipart_round_trip() {
+        let image: Vec<u8> = (0..=255u8).cycle().take(50_000).collect();
+        let form = Multipart::new()
+            .text("title", "A \"quoted\" title")
+            .part(Part::file("photo", "beach.png", image.clone()).with_content_type("image/png"))
+            .text("empty", "");
+        let body = form.to_bytes();
+
+        let mut reader = MultipartReader::from_content_type(body.as_slice(), &form.content_type()).unwrap();
+
+        let mut field = reader.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), "title");
+        assert_eq!(field.file_name(), None);
+        assert_eq!(field.text().await.unwrap(), "A \"quoted\" title");
+
+        let mut field = reader.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), "photo");
+        assert_eq!(field.file_name(), Some("beach.png"));
+        assert_eq!(field.content_type(), Some("image/png"));
+        let mut received = Vec::new();
+        let mut chunks = 0;
+        while let Some(chunk) = field.chunk().await.unwrap() {
+            received.extend(chunk);
+            chunks += 1;
+        }
+        assert_eq!(received, image);
+        assert!(chunks > 1);
+
+        let mut field = reader.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), "empty");
+        assert!(field.bytes().await.unwrap().is_empty());
+
+        assert!(reader.next_field().await.unwrap().is_none());
+        assert!(reader.next_field().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_multipart_parsing_edge_cases() {
+        let body = b"preamble\r\n--XyZ  \r\nContent-Disposition: form-data; name=\"skipped\"\r\n\r\nnot read\r\n--XyZ\r\ncontent-disposition: form-data; name=b; filename=\"a\\\"b.txt\"\r\n\r\n--XyZ-ish\r\n--XyZ--\r\nepilogue";
+        let mut reader = MultipartReader::from_content_type(&body[..], "multipart/form-data; boundary=\"XyZ\"").unwrap();
+
+        assert_eq!(reader.next_field().await.unwrap().unwrap().name(), "skipped");
+        let mut field = reader.next_field().await.unwrap().unwrap();
+        assert_eq!(field.name(), "b");
+        assert_eq!(field.file_name(), Some("a\"b.txt"));
+        assert_eq!(field.text().await.unwrap(), "--XyZ-ish");
+        assert!(reader.next_field().await.unwrap().is_none());
+
+        let truncated = b"--XyZ\r\nContent-Disposition: form-data; name=a\r\n\r\nvalue";
+        let mut reader = MultipartReader::new(&truncated[..], "XyZ");
+        let mut field = reader.next_field().await.unwrap().unwrap();
+        assert!(field.bytes().await.is_err());
+
+        assert!(MultipartReader::from_content_type(&body[..], "application/json").is_err());
+        assert_eq!(parse_boundary("multipart/mixed; charset=utf-8; Boundary=abc"), Some("abc".to_string()));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::{
+    GurtError, GurtRequest, GurtResponse,
+    message::{GurtMethod, MessageFrame, frame_message},
+};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::debug;
+
+/// Why a pipelined request got no response.
+#[derive(Debug)]
+pub(crate) enum SendFailure {
+    /// The connection ended before any of the response arrived.
+    Unanswered(GurtError),
+    Failed(GurtError),
+}
+
+impl SendFailure {
+    pub(crate) fn into_error(self) -> GurtError {
+        match self {
+            Self::Unanswered(error) | Self::Failed(error) => error,
+        }
+    }
+}
+
+type Responder = oneshot::Sender<std::result::Result<GurtResponse, SendFailure>>;
+
+struct PipelinedRequest {
+    bytes: Vec<u8>,
+    head_only: bool,
+    responder: Responder,
+    // Held until the matching response is read, so the in-flight limit tracks the wire.
+    permit: OwnedSemaphorePermit,
+}
+
+struct PendingResponse {
+    head_only: bool,
+    responder: Responder,
+    _permit: OwnedSemaphorePermit,
+}
+
+/// A connection that accepts several requests at once and writes them back to back.
+///
+/// Responses arrive in request order, so a background task pairs each response
+/// with the oldest request still waiting on one.
+#[derive(Clone)]
+pub(crate) struct PipelinedConnection {
+    sender: mpsc::Sender<PipelinedRequest>,
+    in_flight: Arc<Semaphore>,
+    closed: Arc<AtomicBool>,
+}
+
+impl PipelinedConnection {
+    pub(crate) fn spawn<S>(stream: S, max_in_flight: usize) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let max_in_flight = max_in_flight.max(1);
+        let (sender, receiver) = mpsc::channel(max_in_flight);
+        let closed = Arc::new(AtomicBool::new(false));
+
+        tokio::spawn(drive_connection(stream, receiver, closed.clone()));
+
+        Self {
+            sender,
+            in_flight: Arc::new(Semaphore::new(max_in_flight)),
+            closed,
+        }
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) || self.sender.is_closed()
+    }
+
+    /// Number of requests that can be queued without waiting for a response.
+    pub(crate) fn available_slots(&self) -> usize {
+        self.in_flight.available_permits()
+    }
+
+    /// Sends `request` and waits for its response. Requests that fail with
+    /// `SendFailure::Unanswered` may be sent again on another connection.
+    pub(crate) async fn send(&self, request: &GurtRequest) -> std::result::Result<GurtResponse, SendFailure> {
+        let closed = || SendFailure::Unanswered(GurtError::connection("Pipelined connection closed"));
+        let permit = self.in_flight.clone().acquire_owned().await
+            .map_err(|_| closed())?;
+
+        let (responder, response) = oneshot::channel();
+        let pipelined = PipelinedRequest {
+            bytes: request.to_bytes(),
+            head_only: request.method == GurtMethod::HEAD,
+            responder,
+            permit,
+        };
+
+        self.sender.send(pipelined).await
+            .map_err(|_| closed())?;
+
+        // A dropped responder means the request was never written
+        response.await
+            .map_err(|_| SendFailure::Unanswered(GurtError::connection("Pipelined connection closed before responding")))?
+    }
+}
+
+async fn drive_connection<S>(stream: S, mut requests: mpsc::Receiver<PipelinedRequest>, closed: Arc<AtomicBool>)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, writer) = tokio::io::split(stream);
+    let (pending_sender, pending_receiver) = mpsc::unbounded_channel();
+    let mut pending: VecDeque<PendingResponse> = VecDeque::new();
+
+    // Writing and reading run side by side, so responses keep draining while a large
+    // request is blocked on a full socket.
+    let failure = {
+        let writing = write_requests(writer, &mut requests, pending_sender);
+        let reading = read_responses(reader, pending_receiver, &mut pending);
+        tokio::pin!(writing, reading);
+
+        tokio::select! {
+            failure = &mut reading => failure,
+            written = &mut writing => match written {
+                Ok(()) => reading.await,
+                Err(message) => Some(ReadFailure { message, partial: false }),
+            },
+        }
+    };
+
+    closed.store(true, Ordering::Release);
+    requests.close();
+
+    if let Some(ReadFailure { message, partial }) = failure {
+        debug!("Pipelined connection ended: {}", message);
+        for (index, waiting) in pending.drain(..).enumerate() {
+            let error = GurtError::connection(message.clone());
+            let failure = if index == 0 && partial { SendFailure::Failed(error) } else { SendFailure::Unanswered(error) };
+            let _ = waiting.responder.send(Err(failure));
+        }
+    }
+}
+
+/// Writes requests until every sender is gone, handing each one to the reader first.
+async fn write_requests<W>(mut writer: W, requests: &mut mpsc::Receiver<PipelinedRequest>, pending: mpsc::UnboundedSender<PendingResponse>) -> std::result::Result<(), String>
+where
+    W: AsyncWrite + Unpin,
+{
+    while let Some(request) = requests.recv().await {
+        // Queued before writing, so the reader knows of the request before its response can arrive
+        let waiting = PendingResponse {
+            head_only: request.head_only,
+            responder: request.responder,
+            _permit: request.permit,
+        };
+        if pending.send(waiting).is_err() {
+            return Err("Pipelined connection closed".to_string());
+        }
+
+        writer.write_all(&request.bytes).await
+            .map_err(|e| format!("Failed to write request: {}", e))?;
+        writer.flush().await
+            .map_err(|e| format!("Failed to write request: {}", e))?;
+    }
+
+    Ok(())
+}
+
+struct ReadFailure {
+    message: String,
+    /// Whether part of the oldest pending response had arrived.
+    partial: bool,
+}
+
+/// Reads responses until the connection fails, or until the writer is done and every
+/// written request has been answered.
+async fn read_responses<R>(mut reader: R, mut incoming: mpsc::UnboundedReceiver<PendingResponse>, pending: &mut VecDeque<PendingResponse>) -> Option<ReadFailure>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buffer = Vec::new();
+    let mut temp_buffer = [0u8; 8192];
+    let mut writer_done = false;
+    let fail = |message: String, buffer: &[u8]| Some(ReadFailure { message, partial: !buffer.is_empty() });
+
+    loop {
+        if writer_done && pending.is_empty() {
+            return None;
+        }
+
+        tokio::select! {
+            waiting = incoming.recv(), if !writer_done => match waiting {
+                Some(waiting) => pending.push_back(waiting),
+                None => writer_done = true,
+            },
+            read = reader.read(&mut temp_buffer) => match read {
+                Ok(0) => return fail("Connection closed by server".to_string(), &buffer),
+                Ok(n) => {
+                    buffer.extend_from_slice(&temp_buffer[..n]);
+                    while let Ok(waiting) = incoming.try_recv() {
+                        pending.push_back(waiting);
+                    }
+
+                    if let Err(message) = dispatch_responses(&mut buffer, pending) {
+                        return fail(message, &buffer);
+                    }
+                }
+                Err(e) => return fail(format!("Read error: {}", e), &buffer),
+            },
+        }
+    }
+}
+
+/// Hands every complete response in `buffer` to the request waiting on it.
+fn dispatch_responses(buffer: &mut Vec<u8>, pending: &mut VecDeque<PendingResponse>) -> std::result::Result<(), String> {
+    while !buffer.is_empty() {
+        let Some(next) = pending.front() else {
+            return Err("Received data with no request in flight".to_string());
+        };
+
+        match frame_message(buffer, !next.head_only) {
+            MessageFrame::Complete(length) => {
+                let response = GurtResponse::parse_bytes(&buffer[..length]);
+                buffer.drain(..length);
+
+                if let Some(waiting) = pending.pop_front() {
+                    let _ = waiting.responder.send(response.map_err(SendFailure::Failed));
+                }
+            }
+            MessageFrame::Incomplete => break,
+            MessageFrame::TooLarge => return Err("Response exceeds maximum message size".to_string()),
+            MessageFrame::Invalid(message) => return Err(message),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pipelined_responses_matched_in_order() {
+        let (client_side, mut server_side) = tokio::io::duplex(4096);
+        let connection = PipelinedConnection::spawn(client_side, 4);
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            let mut temp_buffer = [0u8; 1024];
+            let mut requests = 0;
+
+            while requests < 3 {
+                let n = server_side.read(&mut temp_buffer).await.unwrap();
+                received.extend_from_slice(&temp_buffer[..n]);
+
+                while let MessageFrame::Complete(length) = frame_message(&received, true) {
+                    received.drain(..length);
+                    requests += 1;
+                }
+            }
+
+            let responses = [
+                GurtResponse::ok().with_string_body("first").to_bytes(),
+                b"GURT/1.0.0 200 OK\r\ncontent-length: 42\r\n\r\n".to_vec(),
+                GurtResponse::ok().with_string_body("third").to_bytes(),
+            ].concat();
+            server_side.write_all(&responses).await.unwrap();
+            server_side
+        });
+
+        let first = GurtRequest::new(GurtMethod::GET, "/first".to_string());
+        let second = GurtRequest::new(GurtMethod::HEAD, "/second".to_string());
+        let third = GurtRequest::new(GurtMethod::GET, "/third".to_string());
+
+        let (first, second, third) = tokio::join!(
+            connection.send(&first),
+            connection.send(&second),
+            connection.send(&third),
+        );
+
+        assert_eq!(first.unwrap().text().unwrap(), "first");
+        assert!(second.unwrap().body.is_empty());
+        assert_eq!(third.unwrap().text().unwrap(), "third");
+        assert_eq!(connection.available_slots(), 4);
+
+        drop(server.await.unwrap());
+
+        let failed = connection.send(&GurtRequest::new(GurtMethod::GET, "/".to_string())).await;
+        assert!(failed.is_err());
+        assert!(connection.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_responses_read_while_large_request_is_written() {
+        let (client_side, mut server_side) = tokio::io::duplex(4096);
+        let connection = PipelinedConnection::spawn(client_side, 2);
+        let large_body = vec![b'x'; 64 * 1024];
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            let mut temp_buffer = [0u8; 1024];
+
+            // Answer the first request with a large body before reading the second, which
+            // only fits through the pipe once the client reads that response.
+            for response_size in [64 * 1024 + 1, 1] {
+                loop {
+                    if let MessageFrame::Complete(length) = frame_message(&received, true) {
+                        received.drain(..length);
+                        break;
+                    }
+                    let n = server_side.read(&mut temp_buffer).await.unwrap();
+                    received.extend_from_slice(&temp_buffer[..n]);
+                }
+
+                let response = GurtResponse::ok().with_body(vec![b'y'; response_size]);
+                server_side.write_all(&response.to_bytes()).await.unwrap();
+            }
+            server_side
+        });
+
+        let first = GurtRequest::new(GurtMethod::GET, "/first".to_string());
+        let second = GurtRequest::new(GurtMethod::POST, "/second".to_string()).with_body(large_body);
+
+        let (first, second) = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            tokio::join!(connection.send(&first), connection.send(&second))
+        }).await.expect("pipelined connection deadlocked");
+
+        assert_eq!(first.unwrap().body.len(), 64 * 1024 + 1);
+        assert_eq!(second.unwrap().body.len(), 1);
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_failures_report_whether_a_response_started() {
+        let (client_side, mut server_side) = tokio::io::duplex(4096);
+        let connection = PipelinedConnection::spawn(client_side, 4);
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            let mut temp_buffer = [0u8; 1024];
+            let mut requests = 0;
+
+            while requests < 2 {
+                let n = server_side.read(&mut temp_buffer).await.unwrap();
+                received.extend_from_slice(&temp_buffer[..n]);
+
+                while let MessageFrame::Complete(length) = frame_message(&received, true) {
+                    received.drain(..length);
+                    requests += 1;
+                }
+            }
+
+            // Part of the first response, then nothing for the second
+            server_side.write_all(b"GURT/1.0.0 200 OK\r\ncontent-length: 10\r\n\r\nabc").await.unwrap();
+        });
+
+        let first = GurtRequest::new(GurtMethod::GET, "/first".to_string());
+        let second = GurtRequest::new(GurtMethod::GET, "/second".to_string());
+        let (first, second) = tokio::join!(connection.send(&first), connection.send(&second));
+        server.await.unwrap();
+
+        assert!(matches!(first, Err(SendFailure::Failed(_))));
+        assert!(matches!(second, Err(SendFailure::Unanswered(_))));
+
+        let closed = connection.send(&GurtRequest::new(GurtMethod::GET, "/".to_string())).await;
+        assert!(matches!(closed, Err(SendFailure::Unanswered(_))));
+    }
+}
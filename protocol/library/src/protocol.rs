@@ -17,107 +17,272 @@ pub const MAX_MESSAGE_SIZE: usize = 10 * 1024 * 1024;
 pub const MAX_POOL_SIZE: usize = 10;
 pub const POOL_IDLE_TIMEOUT: u64 = 300;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum GurtStatusCode {
+    // Informational
+    Continue,
+    SwitchingProtocols,
+    
     // Success
-    Ok = 200,
-    Created = 201,
-    Accepted = 202,
-    NoContent = 204,
+    Ok,
+    Created,
+    Accepted,
+    NoContent,
+    ResetContent,
+    PartialContent,
     
-    // Handshake
-    SwitchingProtocols = 101,
+    // Redirects
+    MultipleChoices,
+    MovedPermanently,
+    Found,
+    SeeOther,
+    NotModified,
+    TemporaryRedirect,
+    PermanentRedirect,
     
     // Client errors
-    BadRequest = 400,
-    Unauthorized = 401,
-    Forbidden = 403,
-    NotFound = 404,
-    MethodNotAllowed = 405,
-    Timeout = 408,
-    TooLarge = 413,
-    UnsupportedMediaType = 415,
-    TooManyRequests = 429,
+    BadRequest,
+    Unauthorized,
+    PaymentRequired,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    NotAcceptable,
+    Timeout,
+    Conflict,
+    Gone,
+    LengthRequired,
+    PreconditionFailed,
+    TooLarge,
+    UriTooLong,
+    UnsupportedMediaType,
+    RangeNotSatisfiable,
+    ExpectationFailed,
+    MisdirectedRequest,
+    UnprocessableEntity,
+    Locked,
+    TooEarly,
+    UpgradeRequired,
+    PreconditionRequired,
+    TooManyRequests,
+    HeaderFieldsTooLarge,
+    UnavailableForLegalReasons,
     
     // Server errors
-    InternalServerError = 500,
-    NotImplemented = 501,
-    BadGateway = 502,
-    ServiceUnavailable = 503,
-    GatewayTimeout = 504,
+    InternalServerError,
+    NotImplemented,
+    BadGateway,
+    ServiceUnavailable,
+    GatewayTimeout,
+    VersionNotSupported,
+    InsufficientStorage,
+    NetworkAuthenticationRequired,
+    
+    /// A status code without a named variant, kept as-is so it round-trips.
+    Other(u16),
 }
 
 impl GurtStatusCode {
-    pub fn from_u16(code: u16) -> Option<Self> {
+    pub fn from_u16(code: u16) -> Self {
         match code {
-            200 => Some(Self::Ok),
-            201 => Some(Self::Created),
-            202 => Some(Self::Accepted),
-            204 => Some(Self::NoContent),
-            101 => Some(Self::SwitchingProtocols),
-            400 => Some(Self::BadRequest),
-            401 => Some(Self::Unauthorized),
-            403 => Some(Self::Forbidden),
-            404 => Some(Self::NotFound),
-            405 => Some(Self::MethodNotAllowed),
-            408 => Some(Self::Timeout),
-            413 => Some(Self::TooLarge),
-            415 => Some(Self::UnsupportedMediaType),
-            429 => Some(Self::TooManyRequests),
-            500 => Some(Self::InternalServerError),
-            501 => Some(Self::NotImplemented),
-            502 => Some(Self::BadGateway),
-            503 => Some(Self::ServiceUnavailable),
-            504 => Some(Self::GatewayTimeout),
-            _ => None,
+            100 => Self::Continue,
+            101 => Self::SwitchingProtocols,
+            200 => Self::Ok,
+            201 => Self::Created,
+            202 => Self::Accepted,
+            204 => Self::NoContent,
+            205 => Self::ResetContent,
+            206 => Self::PartialContent,
+            300 => Self::MultipleChoices,
+            301 => Self::MovedPermanently,
+            302 => Self::Found,
+            303 => Self::SeeOther,
+            304 => Self::NotModified,
+            307 => Self::TemporaryRedirect,
+            308 => Self::PermanentRedirect,
+            400 => Self::BadRequest,
+            401 => Self::Unauthorized,
+            402 => Self::PaymentRequired,
+            403 => Self::Forbidden,
+            404 => Self::NotFound,
+            405 => Self::MethodNotAllowed,
+            406 => Self::NotAcceptable,
+            408 => Self::Timeout,
+            409 => Self::Conflict,
+            410 => Self::Gone,
+            411 => Self::LengthRequired,
+            412 => Self::PreconditionFailed,
+            413 => Self::TooLarge,
+            414 => Self::UriTooLong,
+            415 => Self::UnsupportedMediaType,
+            416 => Self::RangeNotSatisfiable,
+            417 => Self::ExpectationFailed,
+            421 => Self::MisdirectedRequest,
+            422 => Self::UnprocessableEntity,
+            423 => Self::Locked,
+            425 => Self::TooEarly,
+            426 => Self::UpgradeRequired,
+            428 => Self::PreconditionRequired,
+            429 => Self::TooManyRequests,
+            431 => Self::HeaderFieldsTooLarge,
+            451 => Self::UnavailableForLegalReasons,
+            500 => Self::InternalServerError,
+            501 => Self::NotImplemented,
+            502 => Self::BadGateway,
+            503 => Self::ServiceUnavailable,
+            504 => Self::GatewayTimeout,
+            505 => Self::VersionNotSupported,
+            507 => Self::InsufficientStorage,
+            511 => Self::NetworkAuthenticationRequired,
+            other => Self::Other(other),
+        }
+    }
+    
+    pub fn as_u16(&self) -> u16 {
+        match self {
+            Self::Continue => 100,
+            Self::SwitchingProtocols => 101,
+            Self::Ok => 200,
+            Self::Created => 201,
+            Self::Accepted => 202,
+            Self::NoContent => 204,
+            Self::ResetContent => 205,
+            Self::PartialContent => 206,
+            Self::MultipleChoices => 300,
+            Self::MovedPermanently => 301,
+            Self::Found => 302,
+            Self::SeeOther => 303,
+            Self::NotModified => 304,
+            Self::TemporaryRedirect => 307,
+            Self::PermanentRedirect => 308,
+            Self::BadRequest => 400,
+            Self::Unauthorized => 401,
+            Self::PaymentRequired => 402,
+            Self::Forbidden => 403,
+            Self::NotFound => 404,
+            Self::MethodNotAllowed => 405,
+            Self::NotAcceptable => 406,
+            Self::Timeout => 408,
+            Self::Conflict => 409,
+            Self::Gone => 410,
+            Self::LengthRequired => 411,
+            Self::PreconditionFailed => 412,
+            Self::TooLarge => 413,
+            Self::UriTooLong => 414,
+            Self::UnsupportedMediaType => 415,
+            Self::RangeNotSatisfiable => 416,
+            Self::ExpectationFailed => 417,
+            Self::MisdirectedRequest => 421,
+            Self::UnprocessableEntity => 422,
+            Self::Locked => 423,
+            Self::TooEarly => 425,
+            Self::UpgradeRequired => 426,
+            Self::PreconditionRequired => 428,
+            Self::TooManyRequests => 429,
+            Self::HeaderFieldsTooLarge => 431,
+            Self::UnavailableForLegalReasons => 451,
+            Self::InternalServerError => 500,
+            Self::NotImplemented => 501,
+            Self::BadGateway => 502,
+            Self::ServiceUnavailable => 503,
+            Self::GatewayTimeout => 504,
+            Self::VersionNotSupported => 505,
+            Self::InsufficientStorage => 507,
+            Self::NetworkAuthenticationRequired => 511,
+            Self::Other(code) => *code,
         }
     }
     
     pub fn message(&self) -> &'static str {
         match self {
+            Self::Continue => "CONTINUE",
+            Self::SwitchingProtocols => "SWITCHING_PROTOCOLS",
             Self::Ok => "OK",
             Self::Created => "CREATED",
             Self::Accepted => "ACCEPTED",
             Self::NoContent => "NO_CONTENT",
-            Self::SwitchingProtocols => "SWITCHING_PROTOCOLS",
+            Self::ResetContent => "RESET_CONTENT",
+            Self::PartialContent => "PARTIAL_CONTENT",
+            Self::MultipleChoices => "MULTIPLE_CHOICES",
+            Self::MovedPermanently => "MOVED_PERMANENTLY",
+            Self::Found => "FOUND",
+            Self::SeeOther => "SEE_OTHER",
+            Self::NotModified => "NOT_MODIFIED",
+            Self::TemporaryRedirect => "TEMPORARY_REDIRECT",
+            Self::PermanentRedirect => "PERMANENT_REDIRECT",
             Self::BadRequest => "BAD_REQUEST",
             Self::Unauthorized => "UNAUTHORIZED",
+            Self::PaymentRequired => "PAYMENT_REQUIRED",
             Self::Forbidden => "FORBIDDEN",
             Self::NotFound => "NOT_FOUND",
             Self::MethodNotAllowed => "METHOD_NOT_ALLOWED",
+            Self::NotAcceptable => "NOT_ACCEPTABLE",
             Self::Timeout => "TIMEOUT",
+            Self::Conflict => "CONFLICT",
+            Self::Gone => "GONE",
+            Self::LengthRequired => "LENGTH_REQUIRED",
+            Self::PreconditionFailed => "PRECONDITION_FAILED",
             Self::TooLarge => "TOO_LARGE",
+            Self::UriTooLong => "URI_TOO_LONG",
             Self::UnsupportedMediaType => "UNSUPPORTED_MEDIA_TYPE",
+            Self::RangeNotSatisfiable => "RANGE_NOT_SATISFIABLE",
+            Self::ExpectationFailed => "EXPECTATION_FAILED",
+            Self::MisdirectedRequest => "MISDIRECTED_REQUEST",
+            Self::UnprocessableEntity => "UNPROCESSABLE_ENTITY",
+            Self::Locked => "LOCKED",
+            Self::TooEarly => "TOO_EARLY",
+            Self::UpgradeRequired => "UPGRADE_REQUIRED",
+            Self::PreconditionRequired => "PRECONDITION_REQUIRED",
             Self::TooManyRequests => "TOO_MANY_REQUESTS",
+            Self::HeaderFieldsTooLarge => "HEADER_FIELDS_TOO_LARGE",
+            Self::UnavailableForLegalReasons => "UNAVAILABLE_FOR_LEGAL_REASONS",
             Self::InternalServerError => "INTERNAL_SERVER_ERROR",
             Self::NotImplemented => "NOT_IMPLEMENTED",
             Self::BadGateway => "BAD_GATEWAY",
             Self::ServiceUnavailable => "SERVICE_UNAVAILABLE",
             Self::GatewayTimeout => "GATEWAY_TIMEOUT",
+            Self::VersionNotSupported => "VERSION_NOT_SUPPORTED",
+            Self::InsufficientStorage => "INSUFFICIENT_STORAGE",
+            Self::NetworkAuthenticationRequired => "NETWORK_AUTHENTICATION_REQUIRED",
+            Self::Other(_) => "UNKNOWN",
         }
     }
     
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.as_u16())
+    }
+    
     pub fn is_success(&self) -> bool {
-        matches!(self, Self::Ok | Self::Created | Self::Accepted | Self::NoContent)
+        (200..300).contains(&self.as_u16())
+    }
+    
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.as_u16())
     }
     
     pub fn is_client_error(&self) -> bool {
-        (*self as u16) >= 400 && (*self as u16) < 500
+        (400..500).contains(&self.as_u16())
     }
     
     pub fn is_server_error(&self) -> bool {
-        (*self as u16) >= 500
+        (500..600).contains(&self.as_u16())
+    }
+}
+
+impl From<u16> for GurtStatusCode {
+    fn from(code: u16) -> Self {
+        Self::from_u16(code)
     }
 }
 
 impl From<GurtStatusCode> for u16 {
     fn from(code: GurtStatusCode) -> Self {
-        code as u16
+        code.as_u16()
     }
 }
 
 impl fmt::Display for GurtStatusCode {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", *self as u16)
+        write!(f, "{}", self.as_u16())
     }
 }
\ No newline at end of file
@@ -0,0 +1,110 @@
+use crate::{GurtError, Result};
+use serde::de::DeserializeOwned;
+use url::form_urlencoded;
+
+/// Percent-decoded query string parameters, in the order they appear.
+///
+/// Keys may repeat, as in `?tag=a&tag=b`; `get` returns the first value and
+/// `get_all` returns every value for a key.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    pairs: Vec<(String, String)>,
+}
+
+impl QueryParams {
+    /// Parses a query string without the leading `?`. `+` decodes to a space.
+    pub fn parse(query: &str) -> Self {
+        Self {
+            pairs: form_urlencoded::parse(query.as_bytes())
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs.iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.pairs.iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    /// Parses the first value for `key` into `T`, returning `None` if it is missing or malformed.
+    pub fn get_as<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
+/// Splits a request target into its path and query string.
+pub(crate) fn split_path_and_query(target: &str) -> (&str, Option<&str>) {
+    match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    }
+}
+
+/// Deserializes a query string into `T`, treating a missing query as empty.
+pub(crate) fn deserialize_query<T: DeserializeOwned>(query: Option<&str>) -> Result<T> {
+    serde_urlencoded::from_str(query.unwrap_or(""))
+        .map_err(|e| GurtError::invalid_message(format!("Invalid query string: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[test]
+    fn test_query_params_decoding() {
+        let params = QueryParams::parse("q=hello+world%21&tag=a&tag=b%26c&empty=&flag");
+
+        assert_eq!(params.get("q"), Some("hello world!"));
+        assert_eq!(params.get("tag"), Some("a"));
+        assert_eq!(params.get_all("tag"), vec!["a", "b&c"]);
+        assert_eq!(params.get("empty"), Some(""));
+        assert!(params.contains_key("flag"));
+        assert!(params.get("missing").is_none());
+        assert_eq!(params.len(), 5);
+    }
+
+    #[test]
+    fn test_deserialize_query() {
+        #[derive(Deserialize)]
+        struct Pagination {
+            page: u32,
+            limit: Option<u32>,
+            q: String,
+        }
+
+        let pagination: Pagination = deserialize_query(Some("page=2&q=caf%C3%A9")).unwrap();
+        assert_eq!(pagination.page, 2);
+        assert_eq!(pagination.limit, None);
+        assert_eq!(pagination.q, "café");
+
+        assert!(deserialize_query::<Pagination>(Some("page=two&q=x")).is_err());
+        assert!(deserialize_query::<Pagination>(None).is_err());
+        assert_eq!(split_path_and_query("/search?q=1"), ("/search", Some("q=1")));
+        assert_eq!(split_path_and_query("/search"), ("/search", None));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{GurtBodyStream, GurtResponse, multipart::random_boundary, protocol::GurtStatusCode};
+
+/// Requests with more ranges than this are answered with the full resource.
+pub const MAX_RANGES: usize = 16;
+
+/// An inclusive byte range within a resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Inclusive ranges always cover at least one byte.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The `content-range` value for this range of a resource of `total_len` bytes.
+    pub fn content_range(&self, total_len: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total_len)
+    }
+}
+
+/// How to answer a `Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeRequest {
+    /// The header is absent, malformed or asks for too many ranges; send the whole resource.
+    Full,
+    Partial(Vec<ByteRange>),
+    /// No requested range overlaps the resource; answer 416.
+    Unsatisfiable,
+}
+
+impl RangeRequest {
+    /// Resolves a `Range` header value such as `bytes=0-99,-500` against a resource of `total_len` bytes.
+    pub fn parse(header: Option<&str>, total_len: u64) -> Self {
+        let Some(specs) = header.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+            return Self::Full;
+        };
+
+        let mut ranges = Vec::new();
+        for spec in specs.split(',') {
+            let Some((start, end)) = spec.trim().split_once('-') else {
+                return Self::Full;
+            };
+
+            let range = match (start.trim(), end.trim()) {
+                ("", suffix) => match suffix.parse::<u64>() {
+                    Ok(0) => None,
+                    Ok(suffix) if total_len > 0 => Some(ByteRange { start: total_len.saturating_sub(suffix), end: total_len - 1 }),
+                    Ok(_) => None,
+                    Err(_) => return Self::Full,
+                },
+                (start, end) => {
+                    let Ok(start) = start.parse::<u64>() else {
+                        return Self::Full;
+                    };
+                    let end = match end {
+                        "" => u64::MAX,
+                        end => match end.parse::<u64>() {
+                            Ok(end) if end >= start => end,
+                            _ => return Self::Full,
+                        },
+                    };
+                    (start < total_len).then(|| ByteRange { start, end: end.min(total_len - 1) })
+                }
+            };
+
+            ranges.extend(range);
+        }
+
+        if ranges.len() > MAX_RANGES {
+            Self::Full
+        } else if ranges.is_empty() {
+            Self::Unsatisfiable
+        } else {
+            Self::Partial(ranges)
+        }
+    }
+}
+
+/// Parses a `content-range` value such as `bytes 100-199/1000` into the range and
+/// the total length, which is `None` when the server sent `*`.
+pub fn parse_content_range(value: &str) -> Option<(ByteRange, Option<u64>)> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let range = ByteRange { start: start.trim().parse().ok()?, end: end.trim().parse().ok()? };
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    (range.start <= range.end).then_some((range, total))
+}
+
+/// Builds a 206 response from the requested ranges and a body for each. A single range
+/// is sent as-is; several are sent as `multipart/byteranges`.
+pub fn partial_response(parts: Vec<(ByteRange, GurtBodyStream)>, content_type: &str, total_len: u64) -> GurtResponse {
+    let response = GurtResponse::new(GurtStatusCode::PartialContent)
+        .with_header("accept-ranges", "bytes");
+
+    if let [(range, _)] = parts.as_slice() {
+        let range = *range;
+        let (_, body) = parts.into_iter().next().unwrap();
+        return response
+            .with_header("content-type", content_type)
+            .with_header("content-range", range.content_range(total_len))
+            .with_stream_body(body, Some(range.len()));
+    }
+
+    // A fixed boundary could appear inside the ranges themselves and break the parts apart
+    let boundary = random_boundary("gurt-byteranges-");
+    let mut length = 0;
+    let mut bodies = Vec::with_capacity(parts.len() * 2 + 1);
+    for (range, body) in parts {
+        let part_head = format!(
+            "\r\n--{}\r\ncontent-type: {}\r\ncontent-range: {}\r\n\r\n",
+            boundary, content_type, range.content_range(total_len)
+        );
+        length += part_head.len() as u64 + range.len();
+        bodies.push(GurtBodyStream::from_bytes(part_head));
+        bodies.push(body);
+    }
+    let closing = format!("\r\n--{}--\r\n", boundary);
+    length += closing.len() as u64;
+    bodies.push(GurtBodyStream::from_bytes(closing));
+
+    response
+        .with_header("content-type", format!("multipart/byteranges; boundary={}", boundary))
+        .with_stream_body(GurtBodyStream::concat(bodies), Some(length))
+}
+
+/// The 416 response for a resource of `total_len` bytes.
+pub fn range_not_satisfiable(total_len: u64) -> GurtResponse {
+    GurtResponse::new(GurtStatusCode::RangeNotSatisfiable)
+        .with_header("accept-ranges", "bytes")
+        .with_header("content-range", format!("bytes */{}", total_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_range_parsing() {
+        let range = |start, end| ByteRange { start, end };
+
+        assert_eq!(RangeRequest::parse(None, 100), RangeRequest::Full);
+        assert_eq!(RangeRequest::parse(Some("bytes=0-9"), 100), RangeRequest::Partial(vec![range(0, 9)]));
+        assert_eq!(RangeRequest::parse(Some("bytes=90-"), 100), RangeRequest::Partial(vec![range(90, 99)]));
+        assert_eq!(RangeRequest::parse(Some("bytes=-10"), 100), RangeRequest::Partial(vec![range(90, 99)]));
+        assert_eq!(RangeRequest::parse(Some("bytes=-500"), 100), RangeRequest::Partial(vec![range(0, 99)]));
+        assert_eq!(RangeRequest::parse(Some("bytes=50-500"), 100), RangeRequest::Partial(vec![range(50, 99)]));
+        assert_eq!(
+            RangeRequest::parse(Some("bytes=0-0, 200-300, -1"), 100),
+            RangeRequest::Partial(vec![range(0, 0), range(99, 99)])
+        );
+
+        assert_eq!(RangeRequest::parse(Some("bytes=100-"), 100), RangeRequest::Unsatisfiable);
+        assert_eq!(RangeRequest::parse(Some("bytes=-0"), 100), RangeRequest::Unsatisfiable);
+
+        assert_eq!(RangeRequest::parse(Some("bytes=9-0"), 100), RangeRequest::Full);
+        assert_eq!(RangeRequest::parse(Some("items=0-9"), 100), RangeRequest::Full);
+        assert_eq!(RangeRequest::parse(Some("bytes=abc"), 100), RangeRequest::Full);
+
+        assert_eq!(parse_content_range("bytes 0-9/100"), Some((range(0, 9), Some(100))));
+        assert_eq!(parse_content_range("bytes 0-9/*"), Some((range(0, 9), None)));
+        assert_eq!(parse_content_range("bytes */100"), None);
+    }
+
+    #[tokio::test]
+    async fn test_multipart_partial_response() {
+        let parts = vec![
+            (ByteRange { start: 0, end: 2 }, GurtBodyStream::from_bytes("abc")),
+            (ByteRange { start: 7, end: 9 }, GurtBodyStream::from_bytes("hij")),
+        ];
+        let response = partial_response(parts, "text/plain", 10);
+
+        assert_eq!(response.status_code, 206);
+        let boundary_of = |response: &GurtResponse| response.header("content-type").unwrap()
+            .strip_prefix("multipart/byteranges; boundary=").unwrap()
+            .to_string();
+        let boundary = boundary_of(&response);
+        assert_ne!(boundary, boundary_of(&partial_response(Vec::new(), "text/plain", 10)));
+        let length: usize = response.header("content-length").unwrap().parse().unwrap();
+        let body = response.body_stream.unwrap().collect().await.unwrap();
+        let body = String::from_utf8(body).unwrap();
+
+        assert_eq!(body.len(), length);
+        assert!(body.contains("content-range: bytes 0-2/10\r\n\r\nabc"));
+        assert!(body.contains("content-range: bytes 7-9/10\r\n\r\nhij"));
+        assert!(body.starts_with(&format!("\r\n--{}\r\n", boundary)));
+        assert!(body.ends_with(&format!("--{}--\r\n", boundary)));
+    }
+}
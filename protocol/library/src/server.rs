@@ -1,28 +1,94 @@
 use crate::{
-    GurtError, Result, GurtRequest, GurtResponse, GurtMessage, 
+    GurtError, Result, GurtRequest, GurtResponse, GurtMessage, GurtHeaders,
     protocol::{BODY_SEPARATOR, MAX_MESSAGE_SIZE},
-    message::GurtMethod,
+    message::{GurtMethod, ChunkedEncoder, MessageFrame, frame_message},
     protocol::GurtStatusCode,
-    crypto::{TLS_VERSION, GURT_ALPN, TlsConfig},
+    crypto::{self, ClientAuthMode, PeerIdentity, TlsConfig},
+    query::{QueryParams, split_path_and_query},
+    multipart::MultipartReader,
+    channel::{self, GurtChannel},
+    events,
+    handshake::{self, Capabilities, Handshake},
 };
+use serde::de::DeserializeOwned;
+use percent_encoding::percent_decode_str;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::time::{Duration, Instant};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
 use tokio_rustls::{TlsAcceptor, server::TlsStream};
-use rustls::pki_types::CertificateDer;
+use rustls::RootCertStore;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::any::{Any, TypeId};
 use std::fs;
 use tracing::{info, warn, error, debug};
 
+/// How many over-limit connections are answered with a 503 at once under
+/// `ConnectionLimitPolicy::Reject`. Beyond that, new connections are closed right away.
+const MAX_REJECTING_CONNECTIONS: usize = 32;
+
 #[derive(Debug, Clone)]
 pub struct ServerContext {
     pub remote_addr: SocketAddr,
     pub request: GurtRequest,
+    /// Values captured by `:name` segments of the matched route.
+    pub params: HashMap<String, String>,
+    /// Values attached by middleware, such as an authenticated user.
+    pub extensions: Extensions,
+    /// The version and capabilities negotiated for the connection the request arrived on.
+    pub handshake: Handshake,
+    /// The client certificate the connection was verified with, when the server asks for one
+    /// with [`GurtServer::with_client_auth`] and the client presented it.
+    pub peer: Option<PeerIdentity>,
+}
+
+/// A type-keyed map that lets middleware pass data to handlers.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+    
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+    
+    pub fn contains<T: Send + Sync + 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.values.len())
+            .finish()
+    }
 }
 
 impl ServerContext {
+    /// A context for `request` with no route params, extensions or client certificate,
+    /// and the default handshake.
+    pub fn new(remote_addr: SocketAddr, request: GurtRequest) -> Self {
+        Self {
+            remote_addr,
+            request,
+            params: HashMap::new(),
+            extensions: Extensions::default(),
+            handshake: Handshake::default(),
+            peer: None,
+        }
+    }
+    
     pub fn client_ip(&self) -> std::net::IpAddr {
         self.remote_addr.ip()
     }
@@ -40,7 +106,7 @@ impl ServerContext {
         &self.request.path
     }
     
-    pub fn headers(&self) -> &HashMap<String, String> {
+    pub fn headers(&self) -> &GurtHeaders {
         &self.request.headers
     }
     
@@ -55,6 +121,54 @@ impl ServerContext {
     pub fn header(&self, key: &str) -> Option<&String> {
         self.request.header(key)
     }
+
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.request.cookie(name)
+    }
+
+    pub fn query_params(&self) -> QueryParams {
+        self.request.query_params()
+    }
+    
+    /// Deserializes the query string into `T`, e.g. `ctx.query::<Pagination>()`.
+    pub fn query<T: DeserializeOwned>(&self) -> Result<T> {
+        self.request.query()
+    }
+
+    pub fn form_params(&self) -> QueryParams {
+        self.request.form_params()
+    }
+
+    /// Deserializes an `application/x-www-form-urlencoded` body into `T`.
+    pub fn form<T: DeserializeOwned>(&self) -> Result<T> {
+        self.request.form()
+    }
+
+    /// Reads a `multipart/form-data` body part by part, e.g. to save uploaded files.
+    pub fn multipart(&self) -> Result<MultipartReader<&[u8]>> {
+        self.request.multipart()
+    }
+    
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|value| value.as_str())
+    }
+    
+    /// Parses a path parameter into `T`, failing if it is missing or malformed.
+    pub fn param_as<T: std::str::FromStr>(&self, name: &str) -> Result<T> {
+        let value = self.param(name)
+            .ok_or_else(|| GurtError::invalid_message(format!("Missing path parameter '{}'", name)))?;
+        
+        value.parse()
+            .map_err(|_| GurtError::invalid_message(format!("Invalid value for path parameter '{}': {}", name, value)))
+    }
+    
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+    
+    pub fn insert_extension<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.extensions.insert(value);
+    }
 }
 
 pub trait GurtHandler: Send + Sync {
@@ -65,6 +179,13 @@ pub struct FnHandler<F> {
     handler: F,
 }
 
+impl<F> FnHandler<F> {
+    /// Wraps a closure so it can be passed to [`GurtServer::route`].
+    pub fn new(handler: F) -> Self {
+        Self { handler }
+    }
+}
+
 impl<F, Fut> GurtHandler for FnHandler<F>
 where
     F: Fn(&ServerContext) -> Fut + Send + Sync,
@@ -75,15 +196,109 @@ where
     }
 }
 
-#[derive(Debug, Clone)]
+/// Takes over a connection once a client's channel upgrade has been accepted.
+/// Registered with [`GurtServer::channel`] or [`GurtServer::channel_route`].
+pub trait ChannelHandler: Send + Sync {
+    fn handle(&self, ctx: ServerContext, channel: GurtChannel) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>>;
+}
+
+pub struct FnChannelHandler<F> {
+    handler: F,
+}
+
+impl<F> FnChannelHandler<F> {
+    /// Wraps a closure so it can be passed to [`GurtServer::channel_route`].
+    pub fn new(handler: F) -> Self {
+        Self { handler }
+    }
+}
+
+impl<F, Fut> ChannelHandler for FnChannelHandler<F>
+where
+    F: Fn(ServerContext, GurtChannel) -> Fut + Send + Sync,
+    Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+{
+    fn handle(&self, ctx: ServerContext, channel: GurtChannel) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send>> {
+        Box::pin((self.handler)(ctx, channel))
+    }
+}
+
+/// The route handler of a channel route. It only answers the upgrade request; the
+/// channel handler takes over after the `101` has been sent.
+struct ChannelUpgrade;
+
+impl GurtHandler for ChannelUpgrade {
+    fn handle(&self, ctx: &ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<GurtResponse>> + Send + '_>> {
+        let response = if channel::is_upgrade_request(&ctx.request) {
+            channel::accept_response()
+        } else {
+            GurtResponse::new(GurtStatusCode::UpgradeRequired)
+                .with_header("upgrade", channel::CHANNEL_PROTOCOL)
+                .with_string_body("This route only accepts channel upgrades")
+        };
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+/// An accepted upgrade, waiting for its `101` to be written.
+struct PendingUpgrade {
+    ctx: ServerContext,
+    handler: Arc<dyn ChannelHandler>,
+}
+
+/// Hooks that run around route handlers, registered globally with
+/// [`GurtServer::with_middleware`] or per route with [`Route::with_middleware`].
+///
+/// `before` hooks run in registration order, global middleware first. Returning a
+/// response from `before` skips the remaining middleware and the handler. `after`
+/// hooks run in reverse order for every middleware whose `before` ran, including
+/// the one that short-circuited, so they can always release what `before` acquired.
+pub trait GurtMiddleware: Send + Sync {
+    fn before<'a>(&'a self, _ctx: &'a mut ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<GurtResponse>>> + Send + 'a>> {
+        Box::pin(async { Ok(None) })
+    }
+    
+    fn after<'a>(&'a self, _ctx: &'a ServerContext, response: GurtResponse) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<GurtResponse>> + Send + 'a>> {
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+impl<M: GurtMiddleware + ?Sized> GurtMiddleware for Arc<M> {
+    fn before<'a>(&'a self, ctx: &'a mut ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<GurtResponse>>> + Send + 'a>> {
+        (**self).before(ctx)
+    }
+    
+    fn after<'a>(&'a self, ctx: &'a ServerContext, response: GurtResponse) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<GurtResponse>> + Send + 'a>> {
+        (**self).after(ctx, response)
+    }
+}
+
+#[derive(Clone)]
 pub struct Route {
     method: Option<GurtMethod>,
     path_pattern: String,
+    middleware: Vec<Arc<dyn GurtMiddleware>>,
+}
+
+impl std::fmt::Debug for Route {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Route")
+            .field("method", &self.method)
+            .field("path_pattern", &self.path_pattern)
+            .field("middleware", &self.middleware.len())
+            .finish()
+    }
 }
 
 impl Route {
     pub fn new(method: Option<GurtMethod>, path_pattern: String) -> Self {
-        Self { method, path_pattern }
+        Self { method, path_pattern, middleware: Vec::new() }
+    }
+    
+    /// Adds middleware that only runs for requests dispatched to this route.
+    pub fn with_middleware<M: GurtMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
     }
     
     pub fn get(path: &str) -> Self {
@@ -129,37 +344,274 @@ impl Route {
     }
     
     pub fn matches_path(&self, path: &str) -> bool {
-        // Strip query parameters from path for matching
-        let path_without_query = if let Some(query_pos) = path.find('?') {
-            &path[..query_pos]
-        } else {
-            path
+        self.match_path(path).is_some()
+    }
+    
+    /// Matches `path` against the pattern, returning the values of any `:name` segments.
+    ///
+    /// A trailing `*` matches any remainder of the path, as in `/api/*`.
+    pub fn match_path(&self, path: &str) -> Option<HashMap<String, String>> {
+        let (path_without_query, _) = split_path_and_query(path);
+        
+        let (pattern, wildcard) = match self.path_pattern.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (self.path_pattern.as_str(), false),
+        };
+        
+        let pattern_segments: Vec<&str> = pattern.split('/').collect();
+        let path_segments: Vec<&str> = path_without_query.split('/').collect();
+        
+        if path_segments.len() < pattern_segments.len() || (!wildcard && path_segments.len() != pattern_segments.len()) {
+            return None;
+        }
+        
+        let mut params = HashMap::new();
+        let last = pattern_segments.len() - 1;
+        
+        for (index, (pattern_segment, path_segment)) in pattern_segments.iter().zip(&path_segments).enumerate() {
+            if let Some(name) = pattern_segment.strip_prefix(':').filter(|name| !name.is_empty()) {
+                if path_segment.is_empty() {
+                    return None;
+                }
+                // Segments that don't decode to UTF-8 can't be a valid value for the param
+                let value = percent_decode_str(path_segment).decode_utf8().ok()?;
+                params.insert(name.to_string(), value.into_owned());
+            } else if wildcard && index == last {
+                if !path_segment.starts_with(pattern_segment) {
+                    return None;
+                }
+            } else if pattern_segment != path_segment {
+                return None;
+            }
+        }
+        
+        Some(params)
+    }
+    
+    /// Ranks routes segment by segment from the left, so that an exact segment beats a
+    /// parameter and a parameter beats a wildcard at the first place they differ. Routes
+    /// for a specific method beat `any` routes with the same segments.
+    fn specificity(&self) -> (Vec<u8>, bool) {
+        let (pattern, wildcard) = match self.path_pattern.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (self.path_pattern.as_str(), false),
+        };
+        
+        let segments: Vec<&str> = pattern.split('/').collect();
+        let last = segments.len() - 1;
+        let ranks = segments.iter().enumerate()
+            .map(|(index, segment)| {
+                if wildcard && index == last {
+                    0
+                } else if segment.strip_prefix(':').is_some_and(|name| !name.is_empty()) {
+                    1
+                } else {
+                    2
+                }
+            })
+            .collect();
+        
+        (ranks, self.method.is_some())
+    }
+}
+
+/// Peeks at the first byte of a new connection to tell a direct TLS ClientHello from
+/// a plaintext HANDSHAKE, without consuming it.
+async fn starts_with_tls(stream: &TcpStream) -> Result<bool> {
+    let mut first_byte = [0u8; 1];
+    if stream.peek(&mut first_byte).await? == 0 {
+        return Err(GurtError::connection("Connection closed during handshake"));
+    }
+    Ok(handshake::is_direct_tls(first_byte[0]))
+}
+
+/// Resolves once shutdown has been requested. Never resolves if the server is gone
+/// without requesting one.
+async fn wait_for_shutdown(shutdown: &mut watch::Receiver<bool>) {
+    while !*shutdown.borrow_and_update() {
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Completes when the process receives Ctrl+C or, on Unix, SIGTERM. Intended as the
+/// signal for [`GurtServer::listen_with_shutdown`].
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    
+    info!("Shutdown signal received");
+}
+
+/// Drops the body of a response to a HEAD request, keeping the headers that describe it.
+/// Streamed bodies keep the length or chunked encoding their handler declared.
+fn without_body(mut response: GurtResponse) -> GurtResponse {
+    if response.body_stream.take().is_none() && !response.is_chunked() && response.header("content-length").is_none() {
+        let length = response.body.len();
+        response = response.with_header("content-length", length.to_string());
+    }
+    response.body.clear();
+    response
+}
+
+/// Writes `response` to `stream`, pulling streamed bodies through in chunks rather
+/// than buffering them. Event streams are flushed as each event arrives and are ended
+/// early on shutdown, since they would otherwise never finish.
+///
+/// A stream that yields more or fewer bytes than its `content-length` is cut off at
+/// that length and fails with a connection error, as the connection can no longer be
+/// used for further responses.
+async fn write_response<W: AsyncWrite + Unpin>(stream: &mut W, mut response: GurtResponse, shutdown: &mut watch::Receiver<bool>) -> Result<()> {
+    let source = response.body_stream.take().and_then(|body_stream| body_stream.take());
+    
+    let mut source = match source {
+        Some(source) => source,
+        None => {
+            stream.write_all(&response.to_bytes()).await?;
+            return Ok(());
+        }
+    };
+    
+    if response.header("content-length").is_none() && !response.is_chunked() {
+        response = response.with_chunked_encoding();
+    }
+    let chunked = response.is_chunked();
+    let event_stream = response.header("content-type").is_some_and(|value| events::is_event_stream(value));
+    let declared_length = response.header("content-length")
+        .filter(|_| !chunked)
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let mut written: u64 = 0;
+    
+    stream.write_all(&response.head_bytes()).await?;
+    
+    let mut encoder = ChunkedEncoder::new();
+    loop {
+        let chunk = tokio::select! {
+            chunk = source.next_chunk() => chunk?,
+            _ = wait_for_shutdown(shutdown), if event_stream => {
+                debug!("Ending event stream for shutdown");
+                None
+            }
         };
+        let Some(chunk) = chunk else { break };
         
-        self.path_pattern == path_without_query || 
-        (self.path_pattern.ends_with('*') && path_without_query.starts_with(&self.path_pattern[..self.path_pattern.len()-1]))
+        if chunked {
+            stream.write_all(&encoder.encode(&chunk)?).await?;
+        } else if let Some(declared) = declared_length {
+            let remaining = (declared - written) as usize;
+            stream.write_all(&chunk[..chunk.len().min(remaining)]).await?;
+            written += chunk.len().min(remaining) as u64;
+            
+            if chunk.len() > remaining {
+                stream.flush().await?;
+                return Err(GurtError::connection(format!("Response body is longer than its content-length of {}", declared)));
+            }
+        } else {
+            stream.write_all(&chunk).await?;
+        }
+        if event_stream {
+            stream.flush().await?;
+        }
+    }
+    
+    if chunked {
+        stream.write_all(&encoder.finish()?).await?;
+    }
+    
+    stream.flush().await?;
+    
+    match declared_length {
+        Some(declared) if written < declared => Err(GurtError::connection(format!(
+            "Response body ended after {} of its {} declared bytes", written, declared
+        ))),
+        _ => Ok(()),
     }
 }
 
+/// What `GurtServer` does with new connections once `max_connections` are open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionLimitPolicy {
+    /// Stop accepting until a connection closes, leaving new clients in the listen backlog.
+    #[default]
+    Wait,
+    /// Accept the connection and answer its HANDSHAKE with `503 Service Unavailable`.
+    Reject,
+}
+
 pub struct GurtServer {
     routes: Vec<(Route, Arc<dyn GurtHandler>)>,
+    /// Channel handlers, keyed by the index of their route in `routes`.
+    channel_handlers: HashMap<usize, Arc<dyn ChannelHandler>>,
+    middleware: Vec<Arc<dyn GurtMiddleware>>,
     tls_acceptor: Option<TlsAcceptor>,
+    /// The loaded certificate chain and key, kept to rebuild the acceptor when client
+    /// authentication is configured after them.
+    tls_identity: Option<Arc<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>>,
+    client_auth: Option<(RootCertStore, ClientAuthMode)>,
     handshake_timeout: Duration,
     request_timeout: Duration,
+    handler_timeout: Duration,
     connection_timeout: Duration,
+    shutdown_timeout: Duration,
+    max_connections: Option<usize>,
+    connection_limit_policy: ConnectionLimitPolicy,
+    capabilities: Capabilities,
 }
 
 impl GurtServer {
     pub fn new() -> Self {
         Self {
             routes: Vec::new(),
+            channel_handlers: HashMap::new(),
+            middleware: Vec::new(),
             tls_acceptor: None,
+            tls_identity: None,
+            client_auth: None,
             handshake_timeout: Duration::from_secs(5),
             request_timeout: Duration::from_secs(30),
+            handler_timeout: Duration::from_secs(30),
             connection_timeout: Duration::from_secs(10),
+            shutdown_timeout: Duration::from_secs(30),
+            max_connections: None,
+            connection_limit_policy: ConnectionLimitPolicy::default(),
+            capabilities: Capabilities { compression: false, pipelining: true, streaming: true, direct_tls: true },
         }
     }
     
+    /// Sets the connection deadlines:
+    ///
+    /// - `handshake_timeout` covers the plaintext HANDSHAKE and the TLS upgrade.
+    /// - `request_timeout` bounds how long a request may take to arrive once its first
+    ///   byte is read (408 on expiry).
+    /// - `connection_timeout` is how long an idle keep-alive connection stays open.
+    ///
+    /// How long handlers may run is set separately with `with_handler_timeout`.
     pub fn with_timeouts(mut self, handshake_timeout: Duration, request_timeout: Duration, connection_timeout: Duration) -> Self {
         self.handshake_timeout = handshake_timeout;
         self.request_timeout = request_timeout;
@@ -167,6 +619,42 @@ impl GurtServer {
         self
     }
     
+    /// Sets how long a route handler may run before it is abandoned and the client gets
+    /// a 504. Defaults to 30 seconds.
+    pub fn with_handler_timeout(mut self, handler_timeout: Duration) -> Self {
+        self.handler_timeout = handler_timeout;
+        self
+    }
+    
+    /// Sets how long `listen_with_shutdown` waits for open connections to finish
+    /// before closing them forcibly.
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout: Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+    
+    /// Limits how many connections are served at once. `policy` decides what happens
+    /// to connections that arrive while the limit is reached.
+    pub fn with_max_connections(mut self, max_connections: usize, policy: ConnectionLimitPolicy) -> Self {
+        self.max_connections = Some(max_connections);
+        self.connection_limit_policy = policy;
+        self
+    }
+    
+    /// Sets the optional features advertised in the handshake. Everything but compression
+    /// is on by default; turn it on when handlers compress their responses. Turning off
+    /// `direct_tls` also refuses connections that skip the plaintext HANDSHAKE.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+    
+    /// Adds middleware that runs for every request, including those no route matches.
+    pub fn with_middleware<M: GurtMiddleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+    
     pub fn with_tls_certificates(cert_path: &str, key_path: &str) -> Result<Self> {
         let mut server = Self::new();
         server.load_tls_certificates(cert_path, key_path)?;
@@ -182,27 +670,62 @@ impl GurtServer {
         let key_data = fs::read(key_path)
             .map_err(|e| GurtError::Crypto(format!("Failed to read private key file '{}': {}", key_path, e)))?;
         
-        let mut cursor = std::io::Cursor::new(cert_data);
-        let certs: Vec<CertificateDer<'static>> = rustls_pemfile::certs(&mut cursor)
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| GurtError::Crypto(format!("Failed to parse certificates: {}", e)))?;
-        
+        let certs = crypto::parse_certificates(&cert_data)?;
         if certs.is_empty() {
             return Err(GurtError::Crypto("No certificates found in certificate file".to_string()));
         }
         
-        let mut key_cursor = std::io::Cursor::new(key_data);
-        let private_key = rustls_pemfile::private_key(&mut key_cursor)
-            .map_err(|e| GurtError::Crypto(format!("Failed to parse private key: {}", e)))?
-            .ok_or_else(|| GurtError::Crypto("No private key found in key file".to_string()))?;
+        let private_key = crypto::parse_private_key(&key_data)?;
         
-        let tls_config = TlsConfig::new_server(certs, private_key)?;
-        self.tls_acceptor = Some(tls_config.get_acceptor()?);
+        self.tls_identity = Some(Arc::new((certs, private_key)));
+        self.build_tls_acceptor()?;
         
         info!("TLS certificates loaded successfully");
         Ok(())
     }
     
+    /// Verifies client certificates against the CA certificates in `ca_path`, exposing the
+    /// identity of verified clients as `ctx.peer`. With [`ClientAuthMode::Require`], clients
+    /// without a valid certificate cannot connect.
+    pub fn with_client_auth(mut self, ca_path: &str, mode: ClientAuthMode) -> Result<Self> {
+        self.load_client_auth(ca_path, mode)?;
+        Ok(self)
+    }
+    
+    pub fn load_client_auth(&mut self, ca_path: &str, mode: ClientAuthMode) -> Result<()> {
+        info!("Loading client CA certificates: ca={}, mode={:?}", ca_path, mode);
+        
+        let ca_data = fs::read(ca_path)
+            .map_err(|e| GurtError::Crypto(format!("Failed to read client CA file '{}': {}", ca_path, e)))?;
+        
+        let mut roots = RootCertStore::empty();
+        for cert in crypto::parse_certificates(&ca_data)? {
+            roots.add(cert)
+                .map_err(|e| GurtError::Crypto(format!("Invalid client CA certificate: {}", e)))?;
+        }
+        
+        if roots.is_empty() {
+            return Err(GurtError::Crypto("No certificates found in client CA file".to_string()));
+        }
+        
+        self.client_auth = Some((roots, mode));
+        self.build_tls_acceptor()
+    }
+    
+    fn build_tls_acceptor(&mut self) -> Result<()> {
+        let Some(identity) = &self.tls_identity else {
+            return Ok(());
+        };
+        let (certs, private_key) = identity.as_ref();
+        
+        let tls_config = match &self.client_auth {
+            Some((roots, mode)) => TlsConfig::new_server_with_client_auth(certs.clone(), private_key.clone_key(), roots.clone(), *mode)?,
+            None => TlsConfig::new_server(certs.clone(), private_key.clone_key())?,
+        };
+        self.tls_acceptor = Some(tls_config.get_acceptor()?);
+        Ok(())
+    }
+    
     pub fn route<H>(mut self, route: Route, handler: H) -> Self
     where
         H: GurtHandler + 'static,
@@ -275,48 +798,212 @@ impl GurtServer {
         self.route(Route::any(path), FnHandler { handler })
     }
     
+    /// Accepts channel upgrades on `route`. Middleware runs for the upgrade request
+    /// as for any other, so it can reject it, e.g. with `401`. Requests without an
+    /// `upgrade: gurt-channel` header are answered with `426 Upgrade Required`.
+    pub fn channel_route<H>(mut self, route: Route, handler: H) -> Self
+    where
+        H: ChannelHandler + 'static,
+    {
+        self.channel_handlers.insert(self.routes.len(), Arc::new(handler));
+        self.route(route, ChannelUpgrade)
+    }
+    
+    /// Accepts channel upgrades on `GET path`, handing each channel to `handler`.
+    /// The connection closes when `handler` returns.
+    pub fn channel<F, Fut>(self, path: &str, handler: F) -> Self
+    where
+        F: Fn(ServerContext, GurtChannel) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+    {
+        self.channel_route(Route::get(path), FnChannelHandler { handler })
+    }
+    
     pub async fn listen(self, addr: &str) -> Result<()> {
+        self.listen_with_shutdown(addr, std::future::pending()).await
+    }
+    
+    /// Serves connections until `signal` completes, then stops accepting and lets open
+    /// connections finish their current request. Connections still open after the
+    /// shutdown timeout are dropped.
+    pub async fn listen_with_shutdown<F>(self, addr: &str, signal: F) -> Result<()>
+    where
+        F: std::future::Future<Output = ()>,
+    {
         let listener = TcpListener::bind(addr).await?;
         info!("GURT server listening on {}", addr);
         
+        self.serve_with_shutdown(listener, signal).await
+    }
+    
+    /// Serves connections accepted from an already bound `listener`, e.g. one bound to
+    /// port 0 to let the OS pick a free port.
+    pub async fn serve(self, listener: TcpListener) -> Result<()> {
+        self.serve_with_shutdown(listener, std::future::pending()).await
+    }
+    
+    /// Like [`GurtServer::listen_with_shutdown`], for an already bound `listener`.
+    pub async fn serve_with_shutdown<F>(self, listener: TcpListener, signal: F) -> Result<()>
+    where
+        F: std::future::Future<Output = ()>,
+    {
+        let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+        let mut connections = JoinSet::new();
+        let limit = self.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+        let rejecting = Arc::new(Semaphore::new(MAX_REJECTING_CONNECTIONS));
+        tokio::pin!(signal);
+        
         loop {
-            match listener.accept().await {
-                Ok((stream, addr)) => {
-                    info!("Client connected: {}", addr);
-                    let server = self.clone();
-                    
-                    tokio::spawn(async move {
-                        if let Err(e) = server.handle_connection(stream, addr).await {
-                            error!("Connection error from {}: {}", addr, e);
-                        }
-                        info!("Client disconnected: {}", addr);
-                    });
-                }
-                Err(e) => {
-                    error!("Failed to accept connection: {}", e);
-                }
+            tokio::select! {
+                _ = &mut signal => break,
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                accepted = self.accept_connection(&listener, limit.as_ref()) => match accepted {
+                    Ok((stream, addr, None)) if limit.is_some() => {
+                        let Ok(rejecting_permit) = rejecting.clone().try_acquire_owned() else {
+                            warn!("Connection limit reached, closing {}", addr);
+                            drop(stream);
+                            continue;
+                        };
+                        
+                        warn!("Connection limit reached, rejecting {}", addr);
+                        let server = self.clone();
+                        
+                        connections.spawn(async move {
+                            if let Err(e) = server.reject_connection(stream).await {
+                                debug!("Failed to reject connection from {}: {}", addr, e);
+                            }
+                            drop(rejecting_permit);
+                        });
+                    }
+                    Ok((stream, addr, permit)) => {
+                        info!("Client connected: {}", addr);
+                        let server = self.clone();
+                        let shutdown = shutdown_receiver.clone();
+                        
+                        connections.spawn(async move {
+                            if let Err(e) = server.handle_connection(stream, addr, shutdown).await {
+                                error!("Connection error from {}: {}", addr, e);
+                            }
+                            info!("Client disconnected: {}", addr);
+                            drop(permit);
+                        });
+                    }
+                    Err(e) => {
+                        error!("Failed to accept connection: {}", e);
+                    }
+                },
             }
         }
+        
+        drop(listener);
+        info!("Shutting down, waiting for {} open connection(s)", connections.len());
+        let _ = shutdown_sender.send(true);
+        
+        let drained = tokio::time::timeout(self.shutdown_timeout, async {
+            while connections.join_next().await.is_some() {}
+        }).await;
+        
+        if drained.is_err() {
+            warn!("Shutdown timeout reached, closing {} remaining connection(s)", connections.len());
+            connections.shutdown().await;
+        }
+        
+        info!("GURT server stopped");
+        Ok(())
+    }
+    
+    /// Accepts the next connection along with its slot under the connection limit.
+    /// Under `Reject` the slot is `None` when the limit is reached.
+    async fn accept_connection(&self, listener: &TcpListener, limit: Option<&Arc<Semaphore>>) -> Result<(TcpStream, SocketAddr, Option<OwnedSemaphorePermit>)> {
+        let Some(limit) = limit else {
+            let (stream, addr) = listener.accept().await?;
+            return Ok((stream, addr, None));
+        };
+        
+        match self.connection_limit_policy {
+            ConnectionLimitPolicy::Wait => {
+                let permit = limit.clone().acquire_owned().await
+                    .map_err(|_| GurtError::connection("Connection limit closed"))?;
+                let (stream, addr) = listener.accept().await?;
+                Ok((stream, addr, Some(permit)))
+            }
+            ConnectionLimitPolicy::Reject => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((stream, addr, limit.clone().try_acquire_owned().ok()))
+            }
+        }
+    }
+    
+    async fn reject_connection(&self, mut stream: TcpStream) -> Result<()> {
+        tokio::time::timeout(self.handshake_timeout, async {
+            // A direct TLS client cannot read a plaintext 503, so it is just disconnected
+            if starts_with_tls(&stream).await? {
+                return Ok(());
+            }
+            self.read_handshake_request(&mut stream).await?;
+            
+            let response = GurtResponse::new(GurtStatusCode::ServiceUnavailable)
+                .with_string_body("Server is at its connection limit");
+            stream.write_all(&response.to_bytes()).await?;
+            Ok(())
+        }).await
+            .map_err(|_| GurtError::timeout("Timed out waiting for HANDSHAKE"))?
+    }
+    
+    async fn handle_connection(&self, stream: TcpStream, addr: SocketAddr, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let (tls_stream, handshake) = tokio::time::timeout(self.handshake_timeout, self.establish_tls(stream, addr)).await
+            .map_err(|_| GurtError::timeout(format!("Handshake with {} timed out", addr)))??;
+        
+        self.handle_tls_connection(tls_stream, addr, handshake, shutdown).await
     }
     
-    async fn handle_connection(&self, mut stream: TcpStream, addr: SocketAddr) -> Result<()> {
-        self.handle_initial_handshake(&mut stream, addr).await?;
+    async fn establish_tls(&self, mut stream: TcpStream, addr: SocketAddr) -> Result<(TlsStream<TcpStream>, Handshake)> {
+        let negotiated = if starts_with_tls(&stream).await? {
+            if !self.capabilities.direct_tls {
+                return Err(GurtError::handshake(format!("Direct TLS from {} refused: it is disabled", addr)));
+            }
+            debug!("Direct TLS connection from {}", addr);
+            None
+        } else {
+            Some(self.handle_initial_handshake(&mut stream, addr).await?)
+        };
         
         if let Some(tls_acceptor) = &self.tls_acceptor {
             info!("Upgrading connection to TLS for {}", addr);
             let tls_stream = tls_acceptor.accept(stream).await
                 .map_err(|e| GurtError::Crypto(format!("TLS upgrade failed: {}", e)))?;
             
-            info!("TLS upgrade completed for {}", addr);
+            let handshake = match negotiated {
+                Some(handshake) => handshake,
+                None => handshake::from_alpn(tls_stream.get_ref().1.alpn_protocol())?,
+            };
             
-            self.handle_tls_connection(tls_stream, addr).await
+            info!("TLS upgrade completed for {}", addr);
+            Ok((tls_stream, handshake))
         } else {
             warn!("No TLS configuration available, but handshake completed - this violates GURT protocol");
             Err(GurtError::Protocol("TLS is required after handshake but no TLS configuration available".to_string()))
         }
     }
     
-    async fn handle_initial_handshake(&self, stream: &mut TcpStream, addr: SocketAddr) -> Result<()> {
+    async fn handle_initial_handshake(&self, stream: &mut TcpStream, addr: SocketAddr) -> Result<Handshake> {
+        let request = self.read_handshake_request(stream).await?;
+        
+        match handshake::negotiate(&request, self.capabilities) {
+            Ok(negotiated) => {
+                self.send_handshake_response(stream, addr, &negotiated).await?;
+                Ok(negotiated)
+            }
+            Err(e) => {
+                warn!("Rejecting handshake from {}: {}", addr, e);
+                let response = handshake::reject_response(&e.to_string());
+                let _ = stream.write_all(&response.to_bytes()).await;
+                Err(e)
+            }
+        }
+    }
+    
+    async fn read_handshake_request(&self, stream: &mut TcpStream) -> Result<GurtRequest> {
         let mut buffer = Vec::new();
         let mut temp_buffer = [0u8; 8192];
         
@@ -343,7 +1030,7 @@ impl GurtServer {
         match message {
             GurtMessage::Request(request) => {
                 if request.method == GurtMethod::HANDSHAKE {
-                    self.send_handshake_response(stream, addr, &request).await
+                    Ok(request)
                 } else {
                     Err(GurtError::Protocol("First message must be HANDSHAKE".to_string()))
                 }
@@ -354,12 +1041,95 @@ impl GurtServer {
         }
     }
     
-    async fn handle_tls_connection(&self, mut tls_stream: TlsStream<TcpStream>, addr: SocketAddr) -> Result<()> {
+    async fn handle_tls_connection(&self, mut tls_stream: TlsStream<TcpStream>, addr: SocketAddr, handshake: Handshake, mut shutdown: watch::Receiver<bool>) -> Result<()> {
+        let peer = tls_stream.get_ref().1.peer_certificates().and_then(PeerIdentity::from_certificates);
+        if let Some(name) = peer.as_ref().and_then(|peer| peer.name()) {
+            debug!("Client {} authenticated as {}", addr, name);
+        }
+        
         let mut buffer = Vec::new();
         let mut temp_buffer = [0u8; 8192];
+        // When the first byte of the request currently being read arrived.
+        let mut request_started: Option<Instant> = None;
         
         loop {
-            let bytes_read = match tls_stream.read(&mut temp_buffer).await {
+            match frame_message(&buffer, true) {
+                MessageFrame::Complete(message_len) => {
+                    let message: Vec<u8> = buffer.drain(..message_len).collect();
+                    request_started = (!buffer.is_empty()).then(Instant::now);
+                    
+                    match self.process_tls_message(&mut tls_stream, addr, handshake, peer.as_ref(), &message, &mut shutdown).await {
+                        Ok(None) => {
+                            debug!("Processed message from {} successfully", addr);
+                        }
+                        Ok(Some(upgrade)) => {
+                            // Anything read past the upgrade request already belongs to the channel
+                            let buffered = std::mem::take(&mut buffer);
+                            return self.run_channel(tls_stream, addr, upgrade, buffered, shutdown).await;
+                        }
+                        Err(e @ GurtError::Connection(_)) => {
+                            // The response was cut off, so nothing more can be sent on this connection
+                            return Err(e);
+                        }
+                        Err(e) => {
+                            error!("Encrypted message processing error from {}: {}", addr, e);
+                            let error_response = GurtResponse::internal_server_error()
+                                .with_string_body("Internal server error");
+                            let _ = tls_stream.write_all(&error_response.to_bytes()).await;
+                        }
+                    }
+                    
+                    continue;
+                }
+                MessageFrame::TooLarge => {
+                    warn!("Message too large from {}, closing connection", addr);
+                    let response = GurtResponse::new(GurtStatusCode::TooLarge)
+                        .with_string_body("Message too large");
+                    let _ = tls_stream.write_all(&response.to_bytes()).await;
+                    break;
+                }
+                MessageFrame::Invalid(reason) => {
+                    warn!("Malformed message from {}: {}", addr, reason);
+                    let response = GurtResponse::bad_request()
+                        .with_string_body(reason);
+                    let _ = tls_stream.write_all(&response.to_bytes()).await;
+                    break;
+                }
+                MessageFrame::Incomplete => {}
+            }
+            
+            // Between requests the connection can close without cutting off a response.
+            let idle = buffer.is_empty();
+            if idle && *shutdown.borrow() {
+                debug!("Closing idle connection from {} for shutdown", addr);
+                let _ = tls_stream.shutdown().await;
+                break;
+            }
+            
+            let deadline = match request_started {
+                Some(started) => started + self.request_timeout,
+                None => Instant::now() + self.connection_timeout,
+            };
+            
+            let read = tokio::select! {
+                read = tokio::time::timeout_at(deadline, tls_stream.read(&mut temp_buffer)) => read,
+                _ = wait_for_shutdown(&mut shutdown), if idle => continue,
+            };
+            
+            let Ok(read) = read else {
+                if idle {
+                    debug!("Closing idle connection from {}", addr);
+                    let _ = tls_stream.shutdown().await;
+                } else {
+                    warn!("Timed out reading request from {}, closing connection", addr);
+                    let response = GurtResponse::new(GurtStatusCode::Timeout)
+                        .with_string_body("Request timeout");
+                    let _ = tls_stream.write_all(&response.to_bytes()).await;
+                }
+                break;
+            };
+            
+            let bytes_read = match read {
                 Ok(n) => n,
                 Err(e) => {
                     if e.kind() == std::io::ErrorKind::UnexpectedEof {
@@ -370,48 +1140,25 @@ impl GurtServer {
                 }
             };
             if bytes_read == 0 {
-                break;
-            }
-            
-            buffer.extend_from_slice(&temp_buffer[..bytes_read]);
-            
-            let body_separator = BODY_SEPARATOR.as_bytes();
-            let has_complete_message = buffer.windows(body_separator.len()).any(|w| w == body_separator) ||
-                (buffer.starts_with(b"{") && buffer.ends_with(b"}"));
-            
-            if has_complete_message {
-                // Remove timeout wrapper that causes connection aborts
-                match self.process_tls_message(&mut tls_stream, addr, &buffer).await {
-                    Ok(()) => {
-                        debug!("Processed message from {} successfully", addr);
-                    }
-                    Err(e) => {
-                        error!("Encrypted message processing error from {}: {}", addr, e);
-                        let error_response = GurtResponse::internal_server_error()
-                            .with_string_body("Internal server error");
-                        let _ = tls_stream.write_all(&error_response.to_bytes()).await;
-                    }
+                if !buffer.is_empty() {
+                    debug!("Client {} closed connection with {} bytes of an incomplete message", addr, buffer.len());
                 }
-                
-                buffer.clear();
+                break;
             }
             
-            if buffer.len() > MAX_MESSAGE_SIZE {
-                warn!("Message too large from {}, closing connection", addr);
-                break;
+            if idle {
+                request_started = Some(Instant::now());
             }
+            buffer.extend_from_slice(&temp_buffer[..bytes_read]);
         }
         
         Ok(())
     }
     
-    async fn send_handshake_response(&self, stream: &mut TcpStream, addr: SocketAddr, _request: &GurtRequest) -> Result<()> {
-        info!("Sending handshake response to {}", addr);
+    async fn send_handshake_response(&self, stream: &mut TcpStream, addr: SocketAddr, negotiated: &Handshake) -> Result<()> {
+        info!("Sending handshake response to {} (GURT/{}, capabilities: {})", addr, negotiated.version, negotiated.capabilities);
         
-        let response = GurtResponse::new(GurtStatusCode::SwitchingProtocols)
-            .with_header("GURT-Version", crate::GURT_VERSION.to_string())
-            .with_header("Encryption", TLS_VERSION)
-            .with_header("ALPN", std::str::from_utf8(GURT_ALPN).unwrap_or("GURT/1.0"));
+        let response = handshake::accept_response(negotiated);
         
         let response_bytes = response.to_string().into_bytes();
         stream.write_all(&response_bytes).await?;
@@ -420,7 +1167,35 @@ impl GurtServer {
         Ok(())
     }
     
-    async fn process_tls_message(&self, tls_stream: &mut TlsStream<TcpStream>, addr: SocketAddr, data: &[u8]) -> Result<()> {
+    /// Serves a channel until its handler returns. On shutdown the channel is closed,
+    /// giving the handler the chance to finish before the shutdown timeout.
+    async fn run_channel(&self, tls_stream: TlsStream<TcpStream>, addr: SocketAddr, upgrade: PendingUpgrade, buffered: Vec<u8>, mut shutdown: watch::Receiver<bool>) -> Result<()> {
+        let path = upgrade.ctx.request.path.clone();
+        info!("Opened channel on {} for {}", path, addr);
+        
+        let channel = GurtChannel::new(tls_stream, buffered);
+        let sender = channel.sender();
+        let handling = upgrade.handler.handle(upgrade.ctx, channel);
+        tokio::pin!(handling);
+        
+        let result = tokio::select! {
+            result = &mut handling => result,
+            _ = wait_for_shutdown(&mut shutdown) => {
+                debug!("Closing channel on {} for {} for shutdown", path, addr);
+                let _ = sender.close().await;
+                handling.await
+            }
+        };
+        
+        if let Err(e) = result {
+            error!("Channel handler error for {} from {}: {}", path, addr, e);
+        }
+        let _ = sender.close().await;
+        info!("Closed channel on {} for {}", path, addr);
+        Ok(())
+    }
+    
+    async fn process_tls_message(&self, tls_stream: &mut TlsStream<TcpStream>, addr: SocketAddr, handshake: Handshake, peer: Option<&PeerIdentity>, data: &[u8], shutdown: &mut watch::Receiver<bool>) -> Result<Option<PendingUpgrade>> {
         let message = GurtMessage::parse_bytes(data)?;
         
         match message {
@@ -428,17 +1203,40 @@ impl GurtServer {
                 if request.method == GurtMethod::HANDSHAKE {
                     Err(GurtError::Protocol("Received HANDSHAKE over TLS - protocol violation".to_string()))
                 } else {
-                    self.handle_encrypted_request(tls_stream, addr, &request).await
+                    self.handle_encrypted_request(tls_stream, addr, handshake, peer, &request, shutdown).await
                 }
             }
             GurtMessage::Response(_) => {
                 warn!("Received response on server, ignoring");
-                Ok(())
+                Ok(None)
+            }
+        }
+    }
+    
+    /// Finds the most specific route for the request, preferring the earliest registered on ties.
+    fn find_route(&self, method: &GurtMethod, path: &str) -> Option<(usize, HashMap<String, String>)> {
+        let mut best: Option<(usize, HashMap<String, String>)> = None;
+        
+        for (index, (route, _)) in self.routes.iter().enumerate() {
+            if route.method.as_ref().is_some_and(|route_method| route_method != method) {
+                continue;
+            }
+            
+            if let Some(params) = route.match_path(path) {
+                let better = match &best {
+                    Some((current, _)) => route.specificity() > self.routes[*current].0.specificity(),
+                    None => true,
+                };
+                if better {
+                    best = Some((index, params));
+                }
             }
         }
+        
+        best
     }
     
-    async fn handle_default_options(&self, tls_stream: &mut TlsStream<TcpStream>, request: &GurtRequest) -> Result<()> {
+    fn default_options_response(&self, request: &GurtRequest) -> GurtResponse {
         let mut allowed_methods = std::collections::HashSet::new();
         
         for (route, _) in &self.routes {
@@ -460,89 +1258,132 @@ impl GurtServer {
         allowed_methods_vec.sort();
         let allow_header = allowed_methods_vec.join(", ");
         
-        let response = GurtResponse::ok()
+        GurtResponse::ok()
             .with_header("Allow", allow_header)
             .with_header("Access-Control-Allow-Origin", "*")
             .with_header("Access-Control-Allow-Methods", "GET, POST, PUT, DELETE, HEAD, OPTIONS, PATCH")
-            .with_header("Access-Control-Allow-Headers", "Content-Type, Authorization");
+            .with_header("Access-Control-Allow-Headers", "Content-Type, Authorization")
+    }
+    
+    /// Runs the matched handler, or the built-in fallback when no route matches.
+    async fn invoke_handler(&self, route_index: Option<usize>, head_via_get: bool, ctx: &ServerContext) -> GurtResponse {
+        let request = &ctx.request;
         
-        tls_stream.write_all(&response.to_bytes()).await?;
-        Ok(())
+        let Some(index) = route_index else {
+            return match request.method {
+                GurtMethod::OPTIONS => self.default_options_response(request),
+                GurtMethod::HEAD => GurtResponse::not_found(),
+                _ => GurtResponse::not_found().with_string_body("Not found"),
+            };
+        };
+        
+        let Ok(result) = tokio::time::timeout(self.handler_timeout, self.routes[index].1.handle(ctx)).await else {
+            warn!("Handler for {} {} timed out after {:?}", request.method, request.path, self.handler_timeout);
+            return GurtResponse::new(GurtStatusCode::GatewayTimeout)
+                .with_string_body("Gateway timeout");
+        };
+        
+        match result {
+            Ok(response) if head_via_get => without_body(response),
+            Ok(response) => response,
+            Err(e) if head_via_get => {
+                error!("Handler error for HEAD {} (via GET): {}", request.path, e);
+                GurtResponse::internal_server_error()
+            }
+            Err(e) => {
+                error!("Handler error for {} {}: {}", request.method, request.path, e);
+                GurtResponse::internal_server_error()
+                    .with_string_body("Internal server error")
+            }
+        }
     }
     
-    async fn handle_default_head(&self, tls_stream: &mut TlsStream<TcpStream>, addr: SocketAddr, request: &GurtRequest) -> Result<()> {
-        for (route, handler) in &self.routes {
-            if route.method == Some(GurtMethod::GET) && route.matches(&GurtMethod::GET, &request.path) {
-                let context = ServerContext {
-                    remote_addr: addr,
-                    request: request.clone(),
-                };
-                
-                match handler.handle(&context).await {
-                    Ok(mut response) => {
-                        let original_content_length = response.body.len();
-                        response.body.clear();
-                        response = response.with_header("content-length", original_content_length.to_string());
-                        
-                        tls_stream.write_all(&response.to_bytes()).await?;
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        error!("Handler error for HEAD {} (via GET): {}", request.path, e);
-                        let error_response = GurtResponse::internal_server_error();
-                        tls_stream.write_all(&error_response.to_bytes()).await?;
-                        return Ok(());
-                    }
+    #[cfg(test)]
+    async fn respond(&self, addr: SocketAddr, request: &GurtRequest) -> GurtResponse {
+        self.respond_with_upgrade(addr, Handshake::default(), None, request).await.0
+    }
+    
+    /// Produces the response for `request` by running the middleware chain around its handler,
+    /// along with the channel to open when the response accepts an upgrade.
+    async fn respond_with_upgrade(&self, addr: SocketAddr, handshake: Handshake, peer: Option<&PeerIdentity>, request: &GurtRequest) -> (GurtResponse, Option<PendingUpgrade>) {
+        let mut route_match = self.find_route(&request.method, &request.path);
+        let mut head_via_get = false;
+        
+        if route_match.is_none() && request.method == GurtMethod::HEAD {
+            route_match = self.find_route(&GurtMethod::GET, &request.path);
+            head_via_get = route_match.is_some();
+        }
+        
+        let (route_index, params) = match route_match {
+            Some((index, params)) => (Some(index), params),
+            None => (None, HashMap::new()),
+        };
+        
+        let mut ctx = ServerContext {
+            remote_addr: addr,
+            request: request.clone(),
+            params,
+            extensions: Extensions::default(),
+            handshake,
+            peer: peer.cloned(),
+        };
+        
+        let route_middleware = route_index.map(|index| self.routes[index].0.middleware.as_slice()).unwrap_or_default();
+        let layers: Vec<&Arc<dyn GurtMiddleware>> = self.middleware.iter().chain(route_middleware).collect();
+        
+        let mut entered = 0;
+        let mut short_circuit = None;
+        
+        for layer in &layers {
+            entered += 1;
+            match layer.before(&mut ctx).await {
+                Ok(None) => {}
+                Ok(Some(response)) => {
+                    short_circuit = Some(response);
+                    break;
+                }
+                Err(e) => {
+                    error!("Middleware error for {} {}: {}", request.method, request.path, e);
+                    short_circuit = Some(GurtResponse::internal_server_error().with_string_body("Internal server error"));
+                    break;
                 }
             }
         }
         
-        let not_found_response = GurtResponse::not_found();
-        tls_stream.write_all(&not_found_response.to_bytes()).await?;
-        Ok(())
-    }
-
-    async fn handle_encrypted_request(&self, tls_stream: &mut TlsStream<TcpStream>, addr: SocketAddr, request: &GurtRequest) -> Result<()> {
-        debug!("Handling encrypted {} request to {} from {}", request.method, request.path, addr);
+        let mut response = match short_circuit {
+            Some(response) => response,
+            None => self.invoke_handler(route_index, head_via_get, &ctx).await,
+        };
         
-        for (route, handler) in &self.routes {
-            if route.matches(&request.method, &request.path) {
-                let context = ServerContext {
-                    remote_addr: addr,
-                    request: request.clone(),
-                };
-                
-                match handler.handle(&context).await {
-                    Ok(response) => {
-                        let response_bytes = response.to_bytes();
-                        tls_stream.write_all(&response_bytes).await?;
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        error!("Handler error for {} {}: {}", request.method, request.path, e);
-                        let error_response = GurtResponse::internal_server_error()
-                            .with_string_body("Internal server error");
-                        tls_stream.write_all(&error_response.to_bytes()).await?;
-                        return Ok(());
-                    }
+        for layer in layers[..entered].iter().rev() {
+            response = match layer.after(&ctx, response).await {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Middleware error for {} {}: {}", request.method, request.path, e);
+                    GurtResponse::internal_server_error().with_string_body("Internal server error")
                 }
-            }
+            };
         }
         
-        match request.method {
-            GurtMethod::OPTIONS => {
-                self.handle_default_options(tls_stream, request).await
-            }
-            GurtMethod::HEAD => {
-                self.handle_default_head(tls_stream, addr, request).await
-            }
-            _ => {
-                let not_found_response = GurtResponse::not_found()
-                    .with_string_body("Not found");
-                tls_stream.write_all(&not_found_response.to_bytes()).await?;
-                Ok(())
-            }
+        let upgrade = route_index
+            .filter(|_| !head_via_get && response.status_code == GurtStatusCode::SwitchingProtocols.as_u16())
+            .and_then(|index| self.channel_handlers.get(&index))
+            .map(|handler| PendingUpgrade { ctx, handler: handler.clone() });
+        
+        (response, upgrade)
+    }
+
+    async fn handle_encrypted_request(&self, tls_stream: &mut TlsStream<TcpStream>, addr: SocketAddr, handshake: Handshake, peer: Option<&PeerIdentity>, request: &GurtRequest, shutdown: &mut watch::Receiver<bool>) -> Result<Option<PendingUpgrade>> {
+        debug!("Handling encrypted {} request to {} from {}", request.method, request.path, addr);
+        
+        let (response, upgrade) = self.respond_with_upgrade(addr, handshake, peer, request).await;
+        if request.method == GurtMethod::HEAD {
+            tls_stream.write_all(&without_body(response).head_bytes()).await?;
+        } else {
+            write_response(tls_stream, response, shutdown).await
+                .map_err(|e| GurtError::connection(format!("Failed to write response: {}", e)))?;
         }
+        Ok(upgrade)
     }
 }
 
@@ -550,10 +1391,19 @@ impl Clone for GurtServer {
     fn clone(&self) -> Self {
         Self {
             routes: self.routes.clone(),
+            channel_handlers: self.channel_handlers.clone(),
+            middleware: self.middleware.clone(),
             tls_acceptor: self.tls_acceptor.clone(),
+            tls_identity: self.tls_identity.clone(),
+            client_auth: self.client_auth.clone(),
             handshake_timeout: self.handshake_timeout,
             request_timeout: self.request_timeout,
+            handler_timeout: self.handler_timeout,
             connection_timeout: self.connection_timeout,
+            shutdown_timeout: self.shutdown_timeout,
+            max_connections: self.max_connections,
+            connection_limit_policy: self.connection_limit_policy,
+            capabilities: self.capabilities,
         }
     }
 }
@@ -568,6 +1418,9 @@ impl Default for GurtServer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{bind_local, spawn_server, TestPki};
+    use crate::{GurtClient, GurtClientConfig};
+    use rcgen::ExtendedKeyUsagePurpose;
     use tokio::test;
     
     #[test]
@@ -589,4 +1442,432 @@ mod tests {
         assert!(wildcard_route.matches(&GurtMethod::GET, "/api/posts?page=2"));
     }
     
-}
\ No newline at end of file
+    #[test]
+    async fn test_route_params() {
+        let route = Route::delete("/domain/:name/records/:id");
+        let params = route.match_path("/domain/example.web/records/42?force=1").unwrap();
+        assert_eq!(params.get("name").unwrap(), "example.web");
+        assert_eq!(params.get("id").unwrap(), "42");
+        
+        assert!(route.match_path("/domain/example.web/records").is_none());
+        assert!(route.match_path("/domain//records/42").is_none());
+        assert!(route.match_path("/domain/example.web/records/42/extra").is_none());
+        
+        let params = route.match_path("/domain/caf%C3%A9%20bar.web/records/a%2Fb").unwrap();
+        assert_eq!(params.get("name").unwrap(), "café bar.web");
+        assert_eq!(params.get("id").unwrap(), "a/b");
+        assert!(route.match_path("/domain/%FF/records/42").is_none());
+        
+        let prefixed = Route::get("/files/:bucket/*");
+        let params = prefixed.match_path("/files/images/a/b.png").unwrap();
+        assert_eq!(params.get("bucket").unwrap(), "images");
+        
+        let context = ServerContext {
+            params: route.match_path("/domain/example.web/records/42").unwrap(),
+            ..ServerContext::new("127.0.0.1:4878".parse().unwrap(), GurtRequest::new(GurtMethod::DELETE, "/domain/example.web/records/42".to_string()))
+        };
+        assert_eq!(context.param("name"), Some("example.web"));
+        assert_eq!(context.param_as::<i32>("id").unwrap(), 42);
+        assert!(context.param_as::<i32>("name").is_err());
+        assert!(context.param("missing").is_none());
+    }
+    
+    #[test]
+    async fn test_most_specific_route_wins() {
+        let server = GurtServer::new()
+            .get("/*", |_ctx| async { Ok(GurtResponse::ok().with_string_body("wildcard")) })
+            .get("/domain/:name", |_ctx| async { Ok(GurtResponse::ok().with_string_body("param")) })
+            .get("/domain/tlds", |_ctx| async { Ok(GurtResponse::ok().with_string_body("exact")) })
+            .get("/domain/*", |_ctx| async { Ok(GurtResponse::ok().with_string_body("prefix")) });
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        
+        for (path, expected) in [
+            ("/domain/tlds", "exact"),
+            ("/domain/example.web", "param"),
+            ("/domain/example.web/records", "prefix"),
+            ("/index.html", "wildcard"),
+        ] {
+            let request = GurtRequest::new(GurtMethod::GET, path.to_string());
+            let response = server.respond(addr, &request).await;
+            assert_eq!(response.text().unwrap(), expected, "path {}", path);
+        }
+        
+        assert!(server.find_route(&GurtMethod::POST, "/domain/tlds").is_none());
+    }
+    
+    #[test]
+    async fn test_route_specificity_by_segment() {
+        let server = GurtServer::new()
+            .get("/:aaaaaa/b", |_ctx| async { Ok(GurtResponse::ok().with_string_body("first param")) })
+            .get("/a/:b", |_ctx| async { Ok(GurtResponse::ok().with_string_body("second param")) })
+            .any("/c/:d", |_ctx| async { Ok(GurtResponse::ok().with_string_body("any")) })
+            .get("/c/:d", |_ctx| async { Ok(GurtResponse::ok().with_string_body("get")) });
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        
+        for (path, expected) in [
+            ("/a/b", "second param"),
+            ("/z/b", "first param"),
+            ("/c/d", "get"),
+        ] {
+            let request = GurtRequest::new(GurtMethod::GET, path.to_string());
+            let response = server.respond(addr, &request).await;
+            assert_eq!(response.text().unwrap(), expected, "path {}", path);
+        }
+    }
+    
+    struct Recorder {
+        name: &'static str,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+        reject: bool,
+    }
+    
+    impl GurtMiddleware for Recorder {
+        fn before<'a>(&'a self, ctx: &'a mut ServerContext) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<Option<GurtResponse>>> + Send + 'a>> {
+            Box::pin(async move {
+                self.log.lock().unwrap().push(format!("before {}", self.name));
+                ctx.insert_extension(self.name);
+                if self.reject {
+                    return Ok(Some(GurtResponse::new(GurtStatusCode::Unauthorized)));
+                }
+                Ok(None)
+            })
+        }
+        
+        fn after<'a>(&'a self, _ctx: &'a ServerContext, response: GurtResponse) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<GurtResponse>> + Send + 'a>> {
+            Box::pin(async move {
+                self.log.lock().unwrap().push(format!("after {}", self.name));
+                Ok(response.with_header(format!("x-{}", self.name), "1"))
+            })
+        }
+    }
+    
+    #[test]
+    async fn test_middleware_chain() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = |name, reject| Recorder { name, log: log.clone(), reject };
+        
+        let server = GurtServer::new()
+            .with_middleware(recorder("global", false))
+            .route(Route::get("/open"), FnHandler { handler: |ctx: &ServerContext| {
+                let seen = ctx.extension::<&'static str>().copied().unwrap_or_default();
+                async move { Ok(GurtResponse::ok().with_string_body(seen)) }
+            }})
+            .route(Route::get("/private").with_middleware(recorder("auth", true)), FnHandler { handler: |_ctx: &ServerContext| async {
+                Ok(GurtResponse::ok().with_string_body("secret"))
+            }});
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        
+        let response = server.respond(addr, &GurtRequest::new(GurtMethod::GET, "/open".to_string())).await;
+        assert_eq!(response.text().unwrap(), "global");
+        assert_eq!(response.header("x-global").map(String::as_str), Some("1"));
+        
+        log.lock().unwrap().clear();
+        let response = server.respond(addr, &GurtRequest::new(GurtMethod::GET, "/private".to_string())).await;
+        assert_eq!(response.status_code, 401);
+        assert!(response.body.is_empty());
+        assert!(response.header("x-auth").is_some());
+        assert_eq!(*log.lock().unwrap(), vec!["before global", "before auth", "after auth", "after global"]);
+        
+        let response = server.respond(addr, &GurtRequest::new(GurtMethod::GET, "/missing".to_string())).await;
+        assert_eq!(response.status_code, 404);
+        assert!(response.header("x-global").is_some());
+    }
+    
+    #[test]
+    async fn test_listen_with_shutdown() {
+        let (listener, addr) = bind_local().await;
+        
+        let server = GurtServer::new().with_shutdown_timeout(Duration::from_millis(200));
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let listening = tokio::spawn(async move {
+            server.serve_with_shutdown(listener, async { let _ = signal.await; }).await
+        });
+        
+        // A client that never finishes its handshake holds the server open until the deadline
+        let _idle_client = TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        
+        let started = std::time::Instant::now();
+        trigger.send(()).unwrap();
+        
+        let result = tokio::time::timeout(Duration::from_secs(5), listening).await
+            .expect("server did not stop")
+            .unwrap();
+        assert!(result.is_ok());
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+    
+    #[test]
+    async fn test_handler_timeout() {
+        let server = GurtServer::new()
+            .with_handler_timeout(Duration::from_millis(50))
+            .get("/slow", |_ctx| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(GurtResponse::ok())
+            })
+            .get("/fast", |_ctx| async { Ok(GurtResponse::ok()) });
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        
+        let slow = server.respond(addr, &GurtRequest::new(GurtMethod::GET, "/slow".to_string())).await;
+        assert_eq!(slow.status_code, GurtStatusCode::GatewayTimeout.as_u16());
+        
+        let fast = server.respond(addr, &GurtRequest::new(GurtMethod::GET, "/fast".to_string())).await;
+        assert_eq!(fast.status_code, 200);
+    }
+    
+    #[test]
+    async fn test_handshake_timeout() {
+        let (listener, addr) = bind_local().await;
+        
+        let server = GurtServer::new()
+            .with_timeouts(Duration::from_millis(100), Duration::from_secs(30), Duration::from_secs(10));
+        let listening = tokio::spawn(async move { server.serve(listener).await });
+        
+        let mut silent_client = TcpStream::connect(addr).await.unwrap();
+        
+        // The server drops a client that never sends its HANDSHAKE
+        let mut buffer = [0u8; 64];
+        let read = tokio::time::timeout(Duration::from_secs(5), silent_client.read(&mut buffer)).await
+            .expect("connection was not closed");
+        assert!(matches!(read, Ok(0) | Err(_)));
+        
+        listening.abort();
+    }
+    
+    #[test]
+    async fn test_connection_limit_rejects_when_full() {
+        let (listener, addr) = bind_local().await;
+        
+        let server = GurtServer::new().with_max_connections(1, ConnectionLimitPolicy::Reject);
+        let listening = tokio::spawn(async move { server.serve(listener).await });
+        
+        let _first_client = TcpStream::connect(addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        
+        let mut second_client = TcpStream::connect(addr).await.unwrap();
+        let handshake = GurtRequest::new(GurtMethod::HANDSHAKE, "/".to_string());
+        second_client.write_all(&handshake.to_bytes()).await.unwrap();
+        
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), second_client.read_to_end(&mut response)).await
+            .expect("rejected connection was not answered")
+            .unwrap();
+        let response = GurtResponse::parse_bytes(&response).unwrap();
+        assert_eq!(response.status_code, GurtStatusCode::ServiceUnavailable.as_u16());
+        
+        listening.abort();
+    }
+    
+    #[test]
+    async fn test_client_certificate_authentication() {
+        let pki = TestPki::new();
+        let ca_path = pki.write("client-ca.pem", &pki.ca_pem);
+        let peer_names = |ctx: &ServerContext| {
+            let names = ctx.peer.as_ref().map(|peer| peer.dns_names.join(",")).unwrap_or_else(|| "anonymous".to_string());
+            async move { Ok(GurtResponse::ok().with_string_body(names)) }
+        };
+        
+        let (certificate, key) = pki.issue("billing.internal", ExtendedKeyUsagePurpose::ClientAuth);
+        let with_certificate = GurtClient::with_config(GurtClientConfig {
+            client_certificate: Some(certificate),
+            client_key: Some(key),
+            ..pki.client_config()
+        });
+        let (untrusted_certificate, untrusted_key) = TestPki::new().issue("billing.internal", ExtendedKeyUsagePurpose::ClientAuth);
+        let with_untrusted_certificate = GurtClient::with_config(GurtClientConfig {
+            client_certificate: Some(untrusted_certificate),
+            client_key: Some(untrusted_key),
+            ..pki.client_config()
+        });
+        let without_certificate = GurtClient::with_config(pki.client_config());
+        
+        let required = pki.server().with_client_auth(&ca_path, ClientAuthMode::Require).unwrap().get("/", peer_names);
+        let url = format!("gurt://localhost:{}/", spawn_server(required).await.port());
+        assert_eq!(with_certificate.get(&url).await.unwrap().text().unwrap(), "billing.internal");
+        assert!(without_certificate.get(&url).await.is_err());
+        assert!(with_untrusted_certificate.get(&url).await.is_err());
+        
+        let requested = pki.server().with_client_auth(&ca_path, ClientAuthMode::Request).unwrap().get("/", peer_names);
+        let url = format!("gurt://localhost:{}/", spawn_server(requested).await.port());
+        assert_eq!(with_certificate.get(&url).await.unwrap().text().unwrap(), "billing.internal");
+        assert_eq!(without_certificate.get(&url).await.unwrap().text().unwrap(), "anonymous");
+        assert!(with_untrusted_certificate.get(&url).await.is_err());
+    }
+    
+    #[test]
+    async fn test_connection_limit_closes_when_rejecters_are_busy() {
+        let (listener, addr) = bind_local().await;
+        
+        let server = GurtServer::new().with_max_connections(1, ConnectionLimitPolicy::Reject);
+        let listening = tokio::spawn(async move { server.serve(listener).await });
+        
+        // One connection fills the limit and the rest occupy every rejecter by never sending a HANDSHAKE
+        let mut idle_clients = Vec::new();
+        for _ in 0..=MAX_REJECTING_CONNECTIONS {
+            idle_clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        
+        let mut extra_client = TcpStream::connect(addr).await.unwrap();
+        let mut response = Vec::new();
+        tokio::time::timeout(Duration::from_secs(1), extra_client.read_to_end(&mut response)).await
+            .expect("connection beyond the rejecters was not closed")
+            .unwrap();
+        assert!(response.is_empty());
+        
+        listening.abort();
+    }
+    
+    #[test]
+    async fn test_write_streamed_response() {
+        let body = vec![b'x'; crate::body::STREAM_CHUNK_SIZE + 100];
+        
+        let response = GurtResponse::ok()
+            .with_reader_body(std::io::Cursor::new(body.clone()), None);
+        let mut output = Vec::new();
+        write_response(&mut output, response, &mut watch::channel(false).1).await.unwrap();
+        
+        let parsed = GurtResponse::parse_bytes(&output).unwrap();
+        assert!(parsed.is_chunked());
+        assert_eq!(parsed.body, body);
+        
+        let response = GurtResponse::ok()
+            .with_reader_body(std::io::Cursor::new(body.clone()), Some(body.len() as u64));
+        let mut output = Vec::new();
+        write_response(&mut output, response, &mut watch::channel(false).1).await.unwrap();
+        
+        let parsed = GurtResponse::parse_bytes(&output).unwrap();
+        assert_eq!(parsed.header("content-length"), Some(&body.len().to_string()));
+        assert_eq!(parsed.body, body);
+    }
+    
+    #[test]
+    async fn test_write_response_enforces_content_length() {
+        let declared = |body: Vec<u8>, length: u64| GurtResponse::ok().with_reader_body(std::io::Cursor::new(body), Some(length));
+        
+        let mut output = Vec::new();
+        let result = write_response(&mut output, declared(vec![b'x'; 20], 10), &mut watch::channel(false).1).await;
+        assert!(matches!(result, Err(GurtError::Connection(_))));
+        assert_eq!(GurtResponse::parse_bytes(&output).unwrap().body, vec![b'x'; 10]);
+        assert!(output.ends_with(b"\r\n\r\nxxxxxxxxxx"));
+        
+        let mut output = Vec::new();
+        let result = write_response(&mut output, declared(vec![b'x'; 5], 10), &mut watch::channel(false).1).await;
+        assert!(matches!(result, Err(GurtError::Connection(_))));
+        
+        let mut output = Vec::new();
+        write_response(&mut output, declared(vec![b'x'; 10], 10), &mut watch::channel(false).1).await.unwrap();
+    }
+    
+    #[test]
+    async fn test_head_via_get_keeps_streamed_length() {
+        let body = vec![b'x'; 4096];
+        let server = GurtServer::new()
+            .get("/file", move |_ctx| {
+                let body = body.clone();
+                async move { Ok(GurtResponse::ok().with_reader_body(std::io::Cursor::new(body), Some(4096))) }
+            })
+            .get("/stream", |_ctx| async {
+                Ok(GurtResponse::ok().with_reader_body(std::io::Cursor::new(vec![b'y'; 10]), None))
+            })
+            .get("/memory", |_ctx| async { Ok(GurtResponse::ok().with_string_body("hello")) });
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        let head = |path: &str| GurtRequest::new(GurtMethod::HEAD, path.to_string());
+        
+        let file = server.respond(addr, &head("/file")).await;
+        assert!(!file.is_streaming());
+        assert_eq!(file.header("content-length").map(String::as_str), Some("4096"));
+        
+        let stream = server.respond(addr, &head("/stream")).await;
+        assert!(stream.is_chunked());
+        assert!(stream.header("content-length").is_none());
+        
+        let memory = server.respond(addr, &head("/memory")).await;
+        assert!(memory.body.is_empty());
+        assert_eq!(memory.header("content-length").map(String::as_str), Some("5"));
+    }
+    
+    #[test]
+    async fn test_direct_tls_detection() {
+        let (listener, addr) = bind_local().await;
+        
+        for (opening, direct) in [(&[0x16u8, 0x03, 0x01][..], true), (b"HANDSHAKE / GURT/1.0.0\r\n", false)] {
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            client.write_all(opening).await.unwrap();
+            
+            let (mut stream, _) = listener.accept().await.unwrap();
+            assert_eq!(starts_with_tls(&stream).await.unwrap(), direct);
+            
+            // Peeking leaves the bytes for the TLS acceptor or HANDSHAKE parser
+            let mut received = vec![0u8; opening.len()];
+            stream.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, opening);
+        }
+    }
+    
+    #[test]
+    async fn test_event_stream_ends_on_shutdown() {
+        let (events, response) = GurtResponse::event_stream(4);
+        let (shutdown_sender, mut shutdown) = watch::channel(false);
+        
+        tokio::spawn(async move {
+            events.send(crate::Event::new("ready")).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            shutdown_sender.send(true).unwrap();
+            // Keep the stream open past the shutdown
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            drop(events);
+        });
+        
+        let mut output = Vec::new();
+        tokio::time::timeout(Duration::from_secs(2), write_response(&mut output, response, &mut shutdown)).await
+            .expect("event stream did not end on shutdown")
+            .unwrap();
+        
+        let parsed = GurtResponse::parse_bytes(&output).unwrap();
+        assert_eq!(parsed.body, b"data: ready\n\n");
+    }
+    
+    #[test]
+    async fn test_channel_upgrade() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let server = GurtServer::new()
+            .channel("/echo", |_ctx, mut channel| async move {
+                while let Some(message) = channel.recv().await? {
+                    channel.send(message).await?;
+                }
+                Ok(())
+            })
+            .channel_route(Route::get("/private").with_middleware(Recorder { name: "auth", log, reject: true }), FnChannelHandler::new(|_ctx, _channel| async { Ok(()) }));
+        
+        let addr: SocketAddr = "127.0.0.1:4878".parse().unwrap();
+        
+        let (response, upgrade) = server.respond_with_upgrade(addr, Handshake::default(), None, &channel::upgrade_request("/echo")).await;
+        assert_eq!(response.status_code, GurtStatusCode::SwitchingProtocols.as_u16());
+        assert_eq!(response.header("upgrade").map(String::as_str), Some(channel::CHANNEL_PROTOCOL));
+        let upgrade = upgrade.expect("upgrade was not accepted");
+        
+        let (client_io, server_io) = tokio::io::duplex(1024);
+        let handling = tokio::spawn(upgrade.handler.handle(upgrade.ctx, GurtChannel::new(server_io, Vec::new())));
+        let mut client = GurtChannel::new(client_io, Vec::new());
+        client.send("ping").await.unwrap();
+        assert_eq!(client.recv().await.unwrap().unwrap().as_text(), Some("ping"));
+        client.close().await.unwrap();
+        assert!(client.recv().await.unwrap().is_none());
+        handling.await.unwrap().unwrap();
+        
+        let (response, upgrade) = server.respond_with_upgrade(addr, Handshake::default(), None, &GurtRequest::new(GurtMethod::GET, "/echo".to_string())).await;
+        assert_eq!(response.status_code, GurtStatusCode::UpgradeRequired.as_u16());
+        assert!(upgrade.is_none());
+        
+        let (response, upgrade) = server.respond_with_upgrade(addr, Handshake::default(), None, &channel::upgrade_request("/private")).await;
+        assert_eq!(response.status_code, 401);
+        assert!(upgrade.is_none());
+    }
+}
@@ -0,0 +1,84 @@
+//! Certificates and listeners for tests that run a real server over TLS.
+
+use crate::{GurtClientConfig, GurtServer};
+use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::net::TcpListener;
+
+static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// Binds a listener on a free local port for a server under test.
+pub(crate) async fn bind_local() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    (listener, addr)
+}
+
+/// Starts `server` on a free local port.
+pub(crate) async fn spawn_server(server: GurtServer) -> SocketAddr {
+    let (listener, addr) = bind_local().await;
+    tokio::spawn(server.serve(listener));
+    addr
+}
+
+/// A throwaway CA that issues `localhost` server certificates and client certificates.
+pub(crate) struct TestPki {
+    pub(crate) ca_pem: String,
+    ca_cert: rcgen::Certificate,
+    ca_key: KeyPair,
+    dir: PathBuf,
+}
+
+impl TestPki {
+    pub(crate) fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca_key = KeyPair::generate().unwrap();
+        let ca_cert = params.self_signed(&ca_key).unwrap();
+
+        let dir = std::env::temp_dir().join(format!(
+            "gurtlib-test-{}-{}", std::process::id(), NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        Self { ca_pem: ca_cert.pem(), ca_cert, ca_key, dir }
+    }
+
+    /// Issues a certificate for `name`, returning its PEM chain and key.
+    pub(crate) fn issue(&self, name: &str, purpose: ExtendedKeyUsagePurpose) -> (String, String) {
+        let mut params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        params.extended_key_usages = vec![purpose];
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca_cert, &self.ca_key).unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+
+    /// Writes `contents` to a file in this CA's temporary directory, returning its path.
+    pub(crate) fn write(&self, name: &str, contents: &str) -> String {
+        let path = self.dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// A server with a `localhost` certificate issued by this CA.
+    pub(crate) fn server(&self) -> GurtServer {
+        let (cert, key) = self.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth);
+        GurtServer::with_tls_certificates(&self.write("server.pem", &cert), &self.write("server-key.pem", &key)).unwrap()
+    }
+
+    /// A client configuration that trusts this CA.
+    pub(crate) fn client_config(&self) -> GurtClientConfig {
+        GurtClientConfig {
+            custom_ca_certificates: vec![self.ca_pem.clone()],
+            ..Default::default()
+        }
+    }
+}
+
+impl Drop for TestPki {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -48,6 +48,12 @@ pub struct CrawlerConfig {
     pub follow_external_links: bool,
     pub max_depth: usize,
     pub request_headers: Vec<(String, String)>,
+    /// PEM certificate and key presented to servers that ask for one, such as the DNS
+    /// server identifying internal services.
+    #[serde(default)]
+    pub client_certificate: Option<PathBuf>,
+    #[serde(default)]
+    pub client_key: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -1,6 +1,6 @@
 use anyhow::{Result, Context};
 use chrono::Utc;
-use gurtlib::{GurtClient, GurtClientConfig};
+use gurtlib::{CacheConfig, GurtClient, GurtClientConfig};
 use scraper::{Html, Selector};
 use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
@@ -33,11 +33,21 @@ impl DomainCrawler {
         let ca_cert = Self::fetch_ca_certificate().await
             .context("Failed to fetch Gurted CA certificate")?;
         
+        let client_certificate = config.crawler.client_certificate.as_ref()
+            .map(|path| std::fs::read_to_string(path).with_context(|| format!("Failed to read client certificate {}", path.display())))
+            .transpose()?;
+        let client_key = config.crawler.client_key.as_ref()
+            .map(|path| std::fs::read_to_string(path).with_context(|| format!("Failed to read client key {}", path.display())))
+            .transpose()?;
+        
         let gurt_config = GurtClientConfig {
             request_timeout: config.crawler_timeout(),
             user_agent: config.search.crawler_user_agent.clone(),
             max_redirects: config.crawler.max_redirects,
             custom_ca_certificates: vec![ca_cert, ARSON_CA.to_string()],
+            cache: Some(CacheConfig::default()),
+            client_certificate,
+            client_key,
             ..Default::default()
         };
         
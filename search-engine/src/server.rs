@@ -50,9 +50,9 @@ impl SearchServer {
                 move |ctx| {
                     let search_engine = search_engine.clone();
                     let config = config.clone();
-                    let path = ctx.path().to_string();
+                    let query = ctx.query_params();
                     async move {
-                        handle_search(path, search_engine, config).await
+                        handle_search(query, search_engine, config).await
                     }
                 }
             })
@@ -63,9 +63,9 @@ impl SearchServer {
                     let search_engine = search_engine.clone();
                     let config = config.clone();
                     
-                    let path = ctx.path().to_string();
+                    let query = ctx.query_params();
                     async move {
-                        handle_api_search(path, search_engine, config).await
+                        handle_api_search(query, search_engine, config).await
                     }
                 }
             })
@@ -90,34 +90,12 @@ pub async fn run_server(config: Config) -> Result<()> {
     server.run().await
 }
 
-fn parse_query_param(path: &str, param: &str) -> String {
-    let param_with_eq = format!("{}=", param);
-    if let Some(start) = path.find(&format!("?{}", param_with_eq)) {
-        let start_pos = start + 1 + param_with_eq.len(); // Skip the '?' and 'param='
-        let query_part = &path[start_pos..];
-        let end_pos = query_part.find('&').unwrap_or(query_part.len());
-        urlencoding::decode(&query_part[..end_pos]).unwrap_or_default().to_string()
-    } else if let Some(start) = path.find(&format!("&{}", param_with_eq)) {
-        let start_pos = start + 1 + param_with_eq.len(); // Skip the '&' and 'param='
-        let query_part = &path[start_pos..];
-        let end_pos = query_part.find('&').unwrap_or(query_part.len());
-        urlencoding::decode(&query_part[..end_pos]).unwrap_or_default().to_string()
-    } else {
-        String::new()
-    }
-}
-
-fn parse_query_param_usize(path: &str, param: &str) -> Option<usize> {
-    let value = parse_query_param(path, param);
-    if value.is_empty() { None } else { value.parse().ok() }
-}
-
 async fn handle_search(
-    path: String,
+    params: QueryParams,
     search_engine: Arc<SearchEngine>,
     config: Config
 ) -> Result<GurtResponse, GurtError> {
-    let query = parse_query_param(&path, "q");
+    let query = params.get("q").unwrap_or_default().to_string();
     
     if query.is_empty() {
         return Ok(GurtResponse::bad_request()
@@ -126,7 +104,7 @@ async fn handle_search(
 
     println!("Search query: '{}'", query);
 
-    let limit = parse_query_param_usize(&path, "limit")
+    let limit = params.get_as::<usize>("limit")
         .unwrap_or(config.search.search_results_per_page)
         .min(config.search.max_search_results);
 
@@ -151,22 +129,22 @@ async fn handle_search(
 }
 
 async fn handle_api_search(
-    path: String, 
+    params: QueryParams,
     search_engine: Arc<SearchEngine>,
     config: Config
 ) -> Result<GurtResponse, GurtError> {
-    let query = parse_query_param(&path, "q");
+    let query = params.get("q").unwrap_or_default().to_string();
     
     if query.is_empty() {
         return Ok(GurtResponse::bad_request()
             .with_json_body(&json!({"error": "Query parameter 'q' is required"}))?);
     }
 
-    let page = parse_query_param_usize(&path, "page")
+    let page = params.get_as::<usize>("page")
         .unwrap_or(1)
         .max(1);
 
-    let per_page = parse_query_param_usize(&path, "per_page")
+    let per_page = params.get_as::<usize>("per_page")
         .unwrap_or(config.search.search_results_per_page)
         .min(config.search.max_search_results);
 